@@ -0,0 +1,3 @@
+fn main() {
+    let _ = std::time::Duration::new(1, 1).as_secs_f32();
+}
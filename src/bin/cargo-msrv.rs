@@ -9,6 +9,7 @@ use tracing_subscriber::filter::LevelFilter;
 fn main() {
     if let Err(err) = _main(args) {
         eprintln!("{}", err);
+        std::process::exit(1);
     }
 }
 
@@ -68,7 +69,8 @@ fn init_and_run(config: &Config) -> TResult<()> {
     let _ = match config.output_format() {
         config::OutputFormat::Human => {
             let custom_cmd = config.check_command_string();
-            let reporter = reporter::ui::HumanPrinter::new(1, config.target(), &custom_cmd);
+            let reporter = reporter::ui::HumanPrinter::new(1, config.target(), &custom_cmd)
+                .with_jobs(config.jobs());
             run_app(config, &reporter)
         }
         config::OutputFormat::Json => {
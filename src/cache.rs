@@ -0,0 +1,363 @@
+//! A persistent, on-disk cache of check outcomes.
+//!
+//! Each outcome is stored under a key which is derived from the toolchain specifier, the check
+//! command, the target, the environment variables set for the check, and a fingerprint of the crate
+//! (its `Cargo.toml`, its source tree, and the `Cargo.lock` of its workspace). If any of these
+//! change, the key changes, and the toolchain will be checked again.
+
+use crate::check::Status;
+use crate::config::Config;
+use crate::dependencies::find_lockfile;
+use crate::diagnostics::Diagnostics;
+use crate::errors::{CargoMSRVError, TResult};
+use crate::lockfile::{CARGO_LOCK, CARGO_LOCK_ORIGINAL, CARGO_LOCK_REPLACEMENT};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const OUTCOMES_FOLDER: &str = "outcomes";
+
+/// Folders which are never part of the fingerprint of a crate.
+const SKIPPED_FOLDERS: &[&str] = &["target", ".git"];
+
+#[derive(Debug)]
+pub struct OutcomeCache {
+    folder: PathBuf,
+}
+
+impl OutcomeCache {
+    /// Opens the cache in the cache folder of the config or, if it has none, the cache located in
+    /// the user's cache folder, e.g. `~/.cache/cargo-msrv` on Linux.
+    pub fn open(config: &Config) -> TResult<Self> {
+        if let Some(folder) = config.cache_folder() {
+            return Ok(Self::with_folder(folder));
+        }
+
+        let folder = dirs::cache_dir()
+            .map(|path| path.join("cargo-msrv").join(OUTCOMES_FOLDER))
+            .ok_or(CargoMSRVError::UnableToAccessCacheFolder)?;
+
+        Ok(Self::with_folder(folder))
+    }
+
+    pub fn with_folder<P: AsRef<Path>>(folder: P) -> Self {
+        Self {
+            folder: folder.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the status of a previous check with the same key, if any, and the diagnostics it
+    /// failed with.
+    ///
+    /// Entries which can't be read or parsed are treated as if they do not exist, and so are failed
+    /// checks which were stored without their diagnostics.
+    pub fn get(&self, key: &CacheKey) -> Option<(Status, Diagnostics)> {
+        let contents = std::fs::read_to_string(self.entry_path(key)).ok()?;
+        let entry = json::parse(&contents).ok()?;
+
+        match entry["status"].as_str()? {
+            "success" => Some((Status::Success, Diagnostics::default())),
+            "failure" => Some((
+                Status::Failure,
+                Diagnostics::from_json(&entry["diagnostics"])?,
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn store(
+        &self,
+        key: &CacheKey,
+        toolchain: &str,
+        status: Status,
+        diagnostics: &Diagnostics,
+    ) -> TResult<()> {
+        std::fs::create_dir_all(&self.folder)?;
+
+        let status = match status {
+            Status::Success => "success",
+            Status::Failure => "failure",
+            // the check may finish in time on another run, e.g. on a less busy machine
+            Status::TimedOut => return Ok(()),
+        };
+
+        let entry = json::object! {
+            toolchain: toolchain,
+            status: status,
+            diagnostics: diagnostics.to_json(),
+        };
+
+        std::fs::write(self.entry_path(key), entry.dump())?;
+
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.folder.join(format!("{:016x}.json", key.digest))
+    }
+}
+
+/// Identifies a single check: a toolchain, checked with a command, against the current state of a crate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CacheKey {
+    digest: u64,
+}
+
+impl CacheKey {
+    pub fn new(
+        toolchain: &str,
+        check_command: &str,
+        target: &str,
+        ignore_lockfile: bool,
+        translate_lockfile: bool,
+        env: &[(&str, &str)],
+        fingerprint: Fingerprint,
+    ) -> Self {
+        let mut hasher = Fnv64::default();
+
+        for component in &[toolchain, check_command, target] {
+            hasher.write_component(component.as_bytes());
+        }
+
+        hasher.write_component(&[ignore_lockfile as u8]);
+
+        // only hashed when set, so the keys of existing entries remain valid
+        if translate_lockfile {
+            hasher.write_component(b"translate-lockfile");
+        }
+
+        for (name, value) in env {
+            hasher.write_component(name.as_bytes());
+            hasher.write_component(value.as_bytes());
+        }
+
+        hasher.write_component(&fingerprint.0.to_le_bytes());
+
+        Self {
+            digest: hasher.finish(),
+        }
+    }
+}
+
+/// A digest of the `Cargo.toml` manifest and the source tree of a crate, and of the `Cargo.lock`
+/// lockfile it's built with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// The lockfile of a workspace member is found in the root of the workspace. Since it may
+    /// change between checks, e.g. when dependencies are downgraded, it's read for every
+    /// fingerprint, while the source tree is only read once per run.
+    pub fn of_crate<P: AsRef<Path>>(crate_root: P, sources: &SourceDigests) -> TResult<Self> {
+        let root = crate_root.as_ref();
+
+        let mut hasher = Fnv64::default();
+        hasher.write_component(&sources.digest(root)?.to_le_bytes());
+
+        if let Some(lockfile) = find_lockfile(root) {
+            hasher.write_component(&std::fs::read(lockfile)?);
+        }
+
+        Ok(Self(hasher.finish()))
+    }
+}
+
+/// The digests of the source trees of the crates checked during a run, which are computed once,
+/// when a crate is first checked.
+#[derive(Clone, Debug, Default)]
+pub struct SourceDigests(Arc<Mutex<HashMap<PathBuf, u64>>>);
+
+impl SourceDigests {
+    fn digest(&self, crate_root: &Path) -> TResult<u64> {
+        let mut digests = self
+            .0
+            .lock()
+            .expect("A thread panicked while hashing a crate");
+
+        if let Some(digest) = digests.get(crate_root) {
+            return Ok(*digest);
+        }
+
+        let mut files = Vec::new();
+        collect_files(crate_root, crate_root, &mut files)?;
+
+        // visit the files in a fixed order, so the fingerprint doesn't depend on the order in
+        // which the file system lists them
+        files.sort();
+
+        let mut hasher = Fnv64::default();
+
+        for file in files {
+            let relative = file.strip_prefix(crate_root).unwrap_or(&file);
+            hasher.write_component(relative.to_string_lossy().as_bytes());
+            hasher.write_component(&std::fs::read(&file)?);
+        }
+
+        let digest = hasher.finish();
+        digests.insert(crate_root.to_path_buf(), digest);
+
+        Ok(digest)
+    }
+}
+
+// The lockfile in the crate root is left out, since it's hashed separately
+fn collect_files(root: &Path, folder: &Path, files: &mut Vec<PathBuf>) -> TResult<()> {
+    for entry in std::fs::read_dir(folder)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let name = entry.file_name();
+
+        if file_type.is_dir() {
+            if !SKIPPED_FOLDERS.iter().any(|skip| name == *skip) {
+                collect_files(root, &path, files)?;
+            }
+        } else if file_type.is_file()
+            && name != CARGO_LOCK_REPLACEMENT
+            && name != CARGO_LOCK_ORIGINAL
+            && !(folder == root && name == CARGO_LOCK)
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The 64 bit FNV-1a hash function.
+///
+/// We don't use the hasher of the standard library, since its output is not guaranteed to be
+/// stable across Rust releases, while the keys of the cache are persisted to disk.
+struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv64 {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // Prefix each component with its length, so ("ab", "c") and ("a", "bc") hash differently
+    fn write_component(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(toolchain: &str, fingerprint: u64) -> CacheKey {
+        CacheKey::new(
+            toolchain,
+            "cargo check --all",
+            "x86_64-unknown-linux-gnu",
+            false,
+            false,
+            &[],
+            Fingerprint(fingerprint),
+        )
+    }
+
+    #[test]
+    fn fnv_known_values() {
+        let mut hasher = Fnv64::default();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn keys_differ_per_component() {
+        let base = key("1.38.0-x86_64-unknown-linux-gnu", 1);
+
+        assert_eq!(base, key("1.38.0-x86_64-unknown-linux-gnu", 1));
+        assert_ne!(base, key("1.37.0-x86_64-unknown-linux-gnu", 1));
+        assert_ne!(base, key("1.38.0-x86_64-unknown-linux-gnu", 2));
+
+        let with_env = CacheKey::new(
+            "1.38.0-x86_64-unknown-linux-gnu",
+            "cargo check --all",
+            "x86_64-unknown-linux-gnu",
+            false,
+            false,
+            &[("RUSTFLAGS", "-Dwarnings")],
+            Fingerprint(1),
+        );
+        assert_ne!(base, with_env);
+    }
+
+    #[test]
+    fn store_and_get() {
+        let folder =
+            std::env::temp_dir().join(format!("cargo-msrv-cache-test-{}", std::process::id()));
+        let cache = OutcomeCache::with_folder(&folder);
+
+        let success = key("1.38.0-x86_64-unknown-linux-gnu", 1);
+        let failure = key("1.37.0-x86_64-unknown-linux-gnu", 1);
+        let missing = key("1.36.0-x86_64-unknown-linux-gnu", 1);
+
+        let diagnostics = Diagnostics::parse(
+            "",
+            "error: package `b v0.1.0` cannot be built because it requires rustc 1.56 or newer",
+        );
+
+        cache
+            .store(
+                &success,
+                "1.38.0-x86_64-unknown-linux-gnu",
+                Status::Success,
+                &Diagnostics::default(),
+            )
+            .unwrap();
+        cache
+            .store(
+                &failure,
+                "1.37.0-x86_64-unknown-linux-gnu",
+                Status::Failure,
+                &diagnostics,
+            )
+            .unwrap();
+
+        assert!(matches!(cache.get(&success), Some((Status::Success, _))));
+        assert!(
+            matches!(cache.get(&failure), Some((Status::Failure, cached)) if cached == diagnostics)
+        );
+        assert!(cache.get(&missing).is_none());
+
+        let _ = std::fs::remove_dir_all(folder);
+    }
+    #[test]
+    fn fingerprint_of_workspace_member() {
+        let workspace =
+            std::env::temp_dir().join(format!("cargo-msrv-fingerprint-{}", std::process::id()));
+        let member = workspace.join("member");
+        std::fs::create_dir_all(member.join("src")).unwrap();
+        std::fs::write(member.join("Cargo.toml"), "[package]\nname = \"member\"\n").unwrap();
+        std::fs::write(member.join("src").join("lib.rs"), "").unwrap();
+        std::fs::write(workspace.join(CARGO_LOCK), "version = 3\n").unwrap();
+
+        let sources = SourceDigests::default();
+        let before = Fingerprint::of_crate(&member, &sources).unwrap();
+        assert_eq!(before, Fingerprint::of_crate(&member, &sources).unwrap());
+
+        // the lockfile in the workspace root is read again for each fingerprint
+        std::fs::write(workspace.join(CARGO_LOCK), "version = 3\n\n[[package]]\n").unwrap();
+        assert_ne!(before, Fingerprint::of_crate(&member, &sources).unwrap());
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+}
@@ -1,11 +1,21 @@
-use crate::command::command;
-use crate::config::Config;
+use crate::cache::{CacheKey, Fingerprint, OutcomeCache};
+use crate::command::wait_with_timeout;
+use crate::config::{Config, TimeoutPolicy};
 use crate::crate_root_folder;
+use crate::diagnostics::{with_json_messages, Diagnostics};
 use crate::errors::{CargoMSRVError, TResult};
-use crate::lockfile::{LockfileHandler, CARGO_LOCK};
+use crate::failure::Failure;
+use crate::lockfile::{translate_lockfile, LockfileHandler, LockfileVersion, CARGO_LOCK};
+use crate::nightly::{toolchain_channel, NightlyDate};
 use crate::reporter::{Output, ProgressAction};
+use crate::toolchain::ToolchainProvider;
+use crate::workspace;
 use rust_releases::semver;
-use std::path::Path;
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 #[derive(Clone, Debug)]
 pub struct Outcome {
@@ -14,13 +24,37 @@ pub struct Outcome {
     toolchain: String,
     // checked Rust version
     version: semver::Version,
+    // the errors reported by a failed check
+    diagnostics: Diagnostics,
+    // why the check failed; `None` for passed and timed out checks
+    failure: Option<Failure>,
 }
 
 impl Outcome {
+    // Only checks which passed, or failed to compile, are cached
+    fn cached(
+        (result, diagnostics): (Status, Diagnostics),
+        toolchain: String,
+        version: &semver::Version,
+    ) -> Self {
+        let failure = match result {
+            Status::Failure => Some(Failure::Compilation),
+            Status::Success | Status::TimedOut => None,
+        };
+
+        Self {
+            result,
+            toolchain,
+            version: version.to_owned(),
+            diagnostics,
+            failure,
+        }
+    }
+
     pub(crate) fn is_success(&self) -> bool {
         match self.result {
             Status::Success => true,
-            Status::Failure => false,
+            Status::Failure | Status::TimedOut => false,
         }
     }
 
@@ -31,21 +65,332 @@ impl Outcome {
     pub(crate) fn toolchain(&self) -> &str {
         &self.toolchain
     }
+
+    pub(crate) fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    // A check which failed for a reason unrelated to the Rust version may pass on another run, so
+    // its outcome shouldn't be cached
+    fn failed_spuriously(&self) -> bool {
+        matches!(self.failure, Some(failure) if failure.is_spurious())
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum Status {
     Success,
     Failure,
+    /// The check didn't finish within the check timeout, and was stopped
+    TimedOut,
 }
 
 pub fn check_toolchain<'a>(
     version: &'a semver::Version,
     config: &'a Config,
     output: &'a impl Output,
+) -> TResult<Outcome> {
+    if config.no_cache() {
+        return check_toolchain_uncached(version, config, output);
+    }
+
+    let toolchain = as_toolchain_specifier(version, config.target());
+    let fingerprint = Fingerprint::of_crate(crate_root_folder(config)?, config.source_digests())?;
+
+    let cache = OutcomeCache::open(config)?;
+    let key = cache_key(&toolchain, version, config, fingerprint);
+
+    if let Some(cached) = cache.get(&key) {
+        let outcome = Outcome::cached(cached, toolchain, version);
+
+        output.complete_cached_step(version, outcome.is_success());
+
+        return Ok(outcome);
+    }
+
+    let outcome = check_toolchain_uncached(version, config, output)?;
+
+    if !outcome.failed_spuriously() {
+        cache.store(
+            &key,
+            outcome.toolchain(),
+            outcome.result,
+            outcome.diagnostics(),
+        )?;
+    }
+
+    Ok(outcome)
+}
+
+fn cache_key(
+    toolchain: &str,
+    version: &semver::Version,
+    config: &Config,
+    fingerprint: Fingerprint,
+) -> CacheKey {
+    CacheKey::new(
+        toolchain,
+        &config.check_command_string(),
+        config.target(),
+        config.ignore_lockfile(),
+        config.translate_lockfile(),
+        &config.environment().vars_for(Some(version)),
+        fingerprint,
+    )
+}
+
+/// Checks the given versions, running at most `config.jobs()` checks at the same time.
+///
+/// Toolchains are installed one after another, after which the checks run concurrently. Since each
+/// toolchain uses its own `CARGO_TARGET_DIR`, the builds don't clobber each other. The outcomes are
+/// returned in the same order as the given versions.
+///
+/// Since the lockfile is moved in and out of place for each check when `config.ignore_lockfile()`
+/// or `config.translate_lockfile()` is set, the checks are run one after another in that case.
+pub fn check_toolchains(
+    versions: &[&semver::Version],
+    config: &Config,
+    output: &impl Output,
+) -> TResult<Vec<Outcome>> {
+    if config.jobs() <= 1 || config.ignore_lockfile() || config.translate_lockfile() {
+        return versions
+            .iter()
+            .map(|version| {
+                output.progress(ProgressAction::Checking(version));
+                check_toolchain(version, config, output)
+            })
+            .collect();
+    }
+
+    let crate_root = crate_root_folder(config)?;
+    let target_directory = target_directory(&crate_root);
+    let cache = if config.no_cache() {
+        None
+    } else {
+        Some((
+            OutcomeCache::open(config)?,
+            Fingerprint::of_crate(&crate_root, config.source_digests())?,
+        ))
+    };
+
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; versions.len()];
+    let mut pending = Vec::new();
+
+    for (index, version) in versions.iter().enumerate() {
+        let toolchain = as_toolchain_specifier(version, config.target());
+
+        if let Some((cache, fingerprint)) = &cache {
+            if let Some(cached) = cache.get(&cache_key(&toolchain, version, config, *fingerprint)) {
+                let outcome = Outcome::cached(cached, toolchain, version);
+                output.complete_cached_step(version, outcome.is_success());
+                outcomes[index] = Some(outcome);
+                continue;
+            }
+        }
+
+        download_if_required(version, &toolchain, config, output)?;
+        pending.push((index, toolchain));
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut pending = pending.into_iter().collect::<VecDeque<_>>();
+    let mut attempts = vec![0; versions.len()];
+    let mut free_slots = (0..config.jobs()).rev().collect::<Vec<_>>();
+    let mut running = 0;
+
+    loop {
+        while !free_slots.is_empty() {
+            let (index, toolchain) = match pending.pop_front() {
+                Some(check) => check,
+                None => break,
+            };
+            let slot = free_slots.pop().unwrap();
+            let target_dir = toolchain_target_dir(&target_directory, versions[index], config);
+
+            output.progress(ProgressAction::Checking(versions[index]));
+            spawn_check(
+                CheckJob {
+                    provider: Arc::clone(config.toolchain_provider()),
+                    slot,
+                    index,
+                    toolchain,
+                    dir: config.crate_path().map(Path::to_path_buf),
+                    check: with_json_messages(&config.effective_check_command(), versions[index]),
+                    env: env_for(versions[index], config)
+                        .into_iter()
+                        .map(|(name, value)| (name.to_string(), value.to_os_string()))
+                        .collect(),
+                    target_dir,
+                    clean_target_dir: config.clean_target_dirs(),
+                    timeout: config.check_timeout(),
+                },
+                sender.clone(),
+            );
+            running += 1;
+        }
+
+        if running == 0 {
+            break;
+        }
+
+        let finished = receiver
+            .recv()
+            .expect("A check thread exited without reporting its outcome");
+        running -= 1;
+        free_slots.push(finished.slot);
+
+        let version = versions[finished.index];
+
+        let outcome = match finished.result? {
+            Some(completed) => {
+                let attempt = &mut attempts[finished.index];
+
+                // the retry is checked before the versions which haven't been checked yet
+                if let Some(failure) = completed.retried_failure(*attempt, config) {
+                    *attempt += 1;
+                    output.retry_check(version, failure, *attempt, config.retries());
+                    pending.push_front((finished.index, finished.toolchain));
+                    continue;
+                }
+
+                completed.into_outcome(version, finished.toolchain, output)
+            }
+            None => timed_out(version, finished.toolchain, config, output)?,
+        };
+
+        if let Some((cache, fingerprint)) = &cache {
+            if !outcome.failed_spuriously() {
+                let key = cache_key(outcome.toolchain(), version, config, *fingerprint);
+                cache.store(
+                    &key,
+                    outcome.toolchain(),
+                    outcome.result,
+                    outcome.diagnostics(),
+                )?;
+            }
+        }
+
+        outcomes[finished.index] = Some(outcome);
+    }
+
+    Ok(outcomes
+        .into_iter()
+        .map(|outcome| outcome.expect("Each version should have been checked"))
+        .collect())
+}
+
+/// The target directory used by the checks of the given version, e.g. `target/cargo-msrv/1.56.0`,
+/// or `None` if the user set `CARGO_TARGET_DIR` for the checks of this version, which then takes
+/// precedence.
+///
+/// Each toolchain has its own folder within the target directory of the workspace, so switching
+/// toolchains doesn't invalidate the builds of other toolchains, nor the regular builds of the crate.
+fn toolchain_target_dir(
+    target_directory: &Path,
+    version: &semver::Version,
+    config: &Config,
+) -> Option<PathBuf> {
+    if env_for(version, config)
+        .iter()
+        .any(|(name, _)| *name == "CARGO_TARGET_DIR")
+    {
+        return None;
+    }
+
+    Some(
+        target_directory
+            .join("cargo-msrv")
+            .join(toolchain_channel(version)),
+    )
+}
+
+// The target directory of the workspace, or the `target` folder of the crate if it can't be read
+fn target_directory(crate_root: &Path) -> PathBuf {
+    workspace::target_directory(crate_root).unwrap_or_else(|err| {
+        tracing::warn!("Unable to determine the target directory: {}", err);
+        crate_root.join("target")
+    })
+}
+
+// Failing to remove a target directory doesn't affect the outcome of the check, e.g. when a process
+// which survived a timed out check still holds on to it
+fn remove_target_dir(target_dir: &Path) {
+    if let Err(err) = std::fs::remove_dir_all(target_dir) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(
+                "Unable to remove target directory '{}': {}",
+                target_dir.display(),
+                err
+            );
+        }
+    }
+}
+
+struct CheckJob {
+    provider: Arc<dyn ToolchainProvider>,
+    slot: usize,
+    index: usize,
+    toolchain: String,
+    dir: Option<PathBuf>,
+    check: Vec<String>,
+    env: Vec<(String, OsString)>,
+    // `None` if the user set `CARGO_TARGET_DIR`
+    target_dir: Option<PathBuf>,
+    clean_target_dir: bool,
+    timeout: Option<Duration>,
+}
+
+struct FinishedCheck {
+    slot: usize,
+    index: usize,
+    toolchain: String,
+    // `None` if the check timed out
+    result: TResult<Option<Completed>>,
+}
+
+fn spawn_check(job: CheckJob, sender: mpsc::Sender<FinishedCheck>) {
+    std::thread::spawn(move || {
+        let mut envs = job
+            .env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_os_str()))
+            .collect::<Vec<_>>();
+        if let Some(target_dir) = &job.target_dir {
+            envs.push(("CARGO_TARGET_DIR", target_dir.as_os_str()));
+        }
+
+        let result = job
+            .provider
+            .run(&job.toolchain, &job.check, job.dir.as_deref(), &envs)
+            .map_err(|_| CargoMSRVError::UnableToRunCheck)
+            .and_then(|child| wait_with_timeout(child, job.timeout))
+            .map(|output| output.as_ref().map(Completed::of));
+
+        if let Some(target_dir) = job.target_dir.as_ref().filter(|_| job.clean_target_dir) {
+            remove_target_dir(target_dir);
+        }
+
+        let _ = sender.send(FinishedCheck {
+            slot: job.slot,
+            index: job.index,
+            toolchain: job.toolchain,
+            result,
+        });
+    });
+}
+
+fn check_toolchain_uncached(
+    version: &semver::Version,
+    config: &Config,
+    output: &impl Output,
 ) -> TResult<Outcome> {
     // temporarily move the lockfile if the user opted to ignore it, and it exists
     let cargo_lock = crate_root_folder(config).map(|p| p.join(CARGO_LOCK))?;
+
+    if config.translate_lockfile() && cargo_lock.is_file() {
+        return check_with_translated_lockfile(version, &cargo_lock, config, output);
+    }
+
     let handle_wrap = if config.ignore_lockfile() && cargo_lock.is_file() {
         let handle = LockfileHandler::new(cargo_lock).move_lockfile()?;
 
@@ -64,8 +409,34 @@ pub fn check_toolchain<'a>(
     Ok(status)
 }
 
+// Checks a toolchain against a copy of the lockfile, which is translated into the most recent format
+// the toolchain can read, if the lockfile uses a more recent format
+fn check_with_translated_lockfile(
+    version: &semver::Version,
+    cargo_lock: &Path,
+    config: &Config,
+    output: &impl Output,
+) -> TResult<Outcome> {
+    let contents = std::fs::read_to_string(cargo_lock)?;
+    let translated = translate_lockfile(&contents, LockfileVersion::newest_readable_by(version))?;
+
+    if translated == contents {
+        return examine_toolchain(version, config, output);
+    }
+
+    let handle = LockfileHandler::new(cargo_lock)
+        .move_lockfile()?
+        .write_lockfile(&translated)?;
+
+    // restore the original lockfile, also if the check could not be run
+    let outcome = examine_toolchain(version, config, output);
+    handle.restore_lockfile()?;
+
+    outcome
+}
+
 pub fn as_toolchain_specifier(version: &semver::Version, target: &str) -> String {
-    format!("{}-{}", version, target)
+    format!("{}-{}", toolchain_channel(version), target)
 }
 
 fn examine_toolchain(
@@ -75,7 +446,7 @@ fn examine_toolchain(
 ) -> TResult<Outcome> {
     let toolchain_specifier = as_toolchain_specifier(version, config.target());
 
-    download_if_required(version, &toolchain_specifier, output)?;
+    download_if_required(version, &toolchain_specifier, config, output)?;
 
     if config.ignore_lockfile() {
         remove_lockfile(config)?;
@@ -84,33 +455,39 @@ fn examine_toolchain(
     try_building(
         version,
         &toolchain_specifier,
-        config.crate_path(),
-        config.check_command(),
+        config,
+        &with_json_messages(&config.effective_check_command(), version),
         output,
     )
 }
 
+// Every check installs its toolchain through this function, which leaves the toolchains alone when
+// they shouldn't be installed
 #[tracing::instrument]
 fn download_if_required(
     version: &semver::Version,
     toolchain_specifier: &str,
+    config: &Config,
     output: &impl Output,
 ) -> TResult<()> {
-    let toolchain = toolchain_specifier.to_owned();
-    output.progress(ProgressAction::Installing(version));
+    if !config.installs_toolchains() {
+        return Ok(());
+    }
 
-    tracing::info!("Installing toolchain {}", toolchain);
+    output.progress(ProgressAction::Installing(version));
 
-    let status = command(&["install", "--profile", "minimal", &toolchain], None)
-        .and_then(|mut c| c.wait().map_err(CargoMSRVError::Io))?;
+    let installed = config
+        .toolchain_provider()
+        .install(toolchain_specifier, config.cross_target());
 
-    if !status.success() {
-        return Err(CargoMSRVError::RustupInstallFailed(
-            toolchain_specifier.to_string(),
-        ));
+    // not every day has a nightly, and not every nightly has each component, so the search skips
+    // the nightlies which can't be installed
+    match installed {
+        Err(_) if NightlyDate::from_version(version).is_some() => Err(
+            CargoMSRVError::NightlyUnavailable(toolchain_specifier.to_string()),
+        ),
+        installed => installed,
     }
-
-    Ok(())
 }
 
 fn remove_lockfile(config: &Config) -> TResult<()> {
@@ -126,34 +503,165 @@ fn remove_lockfile(config: &Config) -> TResult<()> {
 fn try_building(
     version: &semver::Version,
     toolchain_specifier: &str,
-    dir: Option<&Path>,
-    check: &[&str],
+    config: &Config,
+    check: &[String],
     output: &impl Output,
 ) -> TResult<Outcome> {
-    let mut cmd: Vec<&str> = vec!["run", toolchain_specifier];
-    cmd.extend_from_slice(check);
+    let target_directory = target_directory(&crate_root_folder(config)?);
+    let target_dir = toolchain_target_dir(&target_directory, version, config);
+    let mut envs = env_for(version, config);
+    if let Some(target_dir) = &target_dir {
+        envs.push(("CARGO_TARGET_DIR", target_dir.as_os_str()));
+    }
+
+    let mut attempt = 0;
+
+    loop {
+        let child = config
+            .toolchain_provider()
+            .run(toolchain_specifier, check, config.crate_path(), &envs)
+            .map_err(|_| CargoMSRVError::UnableToRunCheck)?;
+        output.progress(ProgressAction::Checking(version));
+
+        let check_output = wait_with_timeout(child, config.check_timeout())?;
+
+        if let Some(target_dir) = target_dir.as_ref().filter(|_| config.clean_target_dirs()) {
+            remove_target_dir(target_dir);
+        }
+
+        let completed = match check_output {
+            Some(check_output) => Completed::of(&check_output),
+            None => return timed_out(version, toolchain_specifier.to_owned(), config, output),
+        };
+
+        if let Some(failure) = completed.retried_failure(attempt, config) {
+            attempt += 1;
+            output.retry_check(version, failure, attempt, config.retries());
+            continue;
+        }
+
+        return Ok(completed.into_outcome(version, toolchain_specifier.to_owned(), output));
+    }
+}
+
+/// Runs a cargo command with the toolchain of the given version, like the checks: in the folder of
+/// the crate, with the environment variables which are set for this version.
+pub(crate) fn run_cargo(
+    version: &semver::Version,
+    config: &Config,
+    args: &[String],
+    output: &impl Output,
+) -> TResult<std::process::Output> {
+    let toolchain_specifier = as_toolchain_specifier(version, config.target());
+
+    // the toolchain isn't installed yet if the outcome of its check was cached
+    download_if_required(version, &toolchain_specifier, config, output)?;
 
-    let mut child = command(&cmd, dir).map_err(|_| CargoMSRVError::UnableToRunCheck)?;
-    output.progress(ProgressAction::Checking(version));
+    let mut command = vec!["cargo".to_string()];
+    command.extend_from_slice(args);
 
-    let status = child.wait()?;
+    let child = config.toolchain_provider().run(
+        &toolchain_specifier,
+        &command,
+        config.crate_path(),
+        &env_for(version, config),
+    )?;
+
+    child.wait_with_output().map_err(From::from)
+}
+
+// The environment variables which are set for the checks of the given version
+fn env_for<'c>(version: &semver::Version, config: &'c Config) -> Vec<(&'c str, &'c OsStr)> {
+    config
+        .environment()
+        .vars_for(Some(version))
+        .into_iter()
+        .map(|(name, value)| (name, OsStr::new(value)))
+        .collect()
+}
+
+// A check which ran to completion
+struct Completed {
+    diagnostics: Diagnostics,
+    // `None` if the check passed
+    failure: Option<Failure>,
+}
 
-    output.complete_step(version, status.success());
+impl Completed {
+    fn of(output: &std::process::Output) -> Self {
+        let diagnostics = diagnostics_of(output);
+        let failure = if output.status.success() {
+            None
+        } else {
+            Some(Failure::of(output, &diagnostics))
+        };
 
-    let toolchain = toolchain_specifier.to_owned();
-    let version = version.to_owned();
+        Self {
+            diagnostics,
+            failure,
+        }
+    }
 
-    if !status.success() {
-        Ok(Outcome {
-            result: Status::Failure,
+    // The failure of the check, if it should be run once more after the given number of retries
+    fn retried_failure(&self, retries: u32, config: &Config) -> Option<Failure> {
+        self.failure
+            .filter(|failure| failure.is_spurious() && retries < config.retries())
+    }
+
+    fn into_outcome(
+        self,
+        version: &semver::Version,
+        toolchain: String,
+        output: &impl Output,
+    ) -> Outcome {
+        let success = self.failure.is_none();
+        output.complete_step(version, success);
+
+        Outcome {
+            result: if success {
+                Status::Success
+            } else {
+                Status::Failure
+            },
             toolchain,
-            version,
-        })
-    } else {
-        Ok(Outcome {
-            result: Status::Success,
+            version: version.to_owned(),
+            diagnostics: self.diagnostics,
+            failure: self.failure,
+        }
+    }
+}
+
+// A check which didn't finish within the check timeout is considered to have failed, or aborts the
+// search, as chosen by the user
+fn timed_out(
+    version: &semver::Version,
+    toolchain: String,
+    config: &Config,
+    output: &impl Output,
+) -> TResult<Outcome> {
+    let timeout = config.check_timeout().unwrap_or_default();
+    output.complete_timed_out_step(version, timeout);
+
+    match config.timeout_policy() {
+        TimeoutPolicy::Fail => Ok(Outcome {
+            result: Status::TimedOut,
             toolchain,
-            version,
-        })
+            version: version.to_owned(),
+            diagnostics: Diagnostics::default(),
+            failure: None,
+        }),
+        TimeoutPolicy::Abort => Err(CargoMSRVError::CheckTimedOut { toolchain, timeout }),
     }
 }
+
+// Only the output of failed checks is of interest
+fn diagnostics_of(output: &std::process::Output) -> Diagnostics {
+    if output.status.success() {
+        return Diagnostics::default();
+    }
+
+    Diagnostics::parse(
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(&output.stderr),
+    )
+}
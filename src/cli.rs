@@ -1,5 +1,7 @@
-use crate::fetch::is_target_available;
-use clap::{App, AppSettings, Arg};
+use crate::config::{parse_duration, parse_jobs, parse_retries, ReleaseSource};
+use crate::environment::EnvVar;
+use clap::{App, AppSettings, Arg, SubCommand};
+use std::convert::TryFrom;
 
 pub mod id {
     pub const ARG_SEEK_PATH: &str = "seek_path";
@@ -9,13 +11,111 @@ pub mod id {
     pub const ARG_MIN: &str = "min";
     pub const ARG_MAX: &str = "max";
     pub const ARG_BISECT: &str = "bisect";
+    pub const ARG_GALLOPING: &str = "galloping";
     pub const ARG_TOOLCHAIN_FILE: &str = "toolchain_file";
+    pub const ARG_WRITE_MSRV: &str = "write_msrv";
     pub const ARG_IGNORE_LOCKFILE: &str = "lockfile";
+    pub const ARG_TRANSLATE_LOCKFILE: &str = "translate_lockfile";
+    pub const ARG_MINIMAL_VERSIONS: &str = "minimal_versions";
+    pub const ARG_DIRECT_MINIMAL_VERSIONS: &str = "direct_minimal_versions";
     pub const ARG_OUTPUT_FORMAT: &str = "output_format";
     pub const ARG_VERIFY: &str = "verify_msrv";
+    pub const ARG_STRICT: &str = "strict";
+    pub const ARG_BLOCKING_DEPENDENCIES: &str = "blocking_dependencies";
+    pub const ARG_GENERATE_LOCKFILE: &str = "generate_lockfile";
     pub const ARG_RELEASE_SOURCE: &str = "release_source";
     pub const ARG_NO_LOG: &str = "no_log";
     pub const ARG_NO_READ_MIN_EDITION: &str = "no_read_min_edition";
+    pub const ARG_NO_CACHE: &str = "no_cache";
+    pub const ARG_JOBS: &str = "jobs";
+    pub const ARG_CLEAN_TARGET_DIRS: &str = "clean_target_dirs";
+    pub const ARG_CHECK_TIMEOUT: &str = "check_timeout";
+    pub const ARG_ON_CHECK_TIMEOUT: &str = "on_check_timeout";
+    pub const ARG_RETRIES: &str = "retries";
+    pub const ARG_ENV: &str = "env";
+    pub const ARG_ENV_FILE: &str = "env_file";
+    pub const ARG_TOOLCHAIN_DIR: &str = "toolchain_dir";
+    pub const ARG_NIGHTLY: &str = "nightly";
+    pub const ARG_WORKSPACE: &str = "workspace";
+    pub const ARG_FEATURE_SET: &str = "feature_set";
+    pub const ARG_EACH_FEATURE: &str = "each_feature";
+    pub const ARG_FEATURE_POWERSET: &str = "feature_powerset";
+
+    pub const ARG_SET_VERSION: &str = "set_version";
+
+    pub const SUB_COMMAND_LIST: &str = "list";
+    pub const SUB_COMMAND_SET: &str = "set";
+}
+
+/// The options which can't be used together with another option. These also apply when the
+/// options are given in environment variables or in a configuration file, so they're checked once
+/// all options are known.
+pub(crate) const CONFLICTS: &[(&str, &[&str])] = &[
+    (id::ARG_GALLOPING, &[id::ARG_BISECT]),
+    (id::ARG_TRANSLATE_LOCKFILE, &[id::ARG_IGNORE_LOCKFILE]),
+    (
+        id::ARG_MINIMAL_VERSIONS,
+        &[
+            id::ARG_VERIFY,
+            id::ARG_GENERATE_LOCKFILE,
+            id::ARG_IGNORE_LOCKFILE,
+        ],
+    ),
+    (
+        id::ARG_DIRECT_MINIMAL_VERSIONS,
+        &[
+            id::ARG_VERIFY,
+            id::ARG_GENERATE_LOCKFILE,
+            id::ARG_IGNORE_LOCKFILE,
+        ],
+    ),
+    (id::ARG_BLOCKING_DEPENDENCIES, &[id::ARG_VERIFY]),
+    (
+        id::ARG_GENERATE_LOCKFILE,
+        &[
+            id::ARG_VERIFY,
+            id::ARG_BLOCKING_DEPENDENCIES,
+            id::ARG_IGNORE_LOCKFILE,
+            id::ARG_TRANSLATE_LOCKFILE,
+            id::ARG_NIGHTLY,
+        ],
+    ),
+    (
+        id::ARG_JOBS,
+        &[id::ARG_IGNORE_LOCKFILE, id::ARG_TRANSLATE_LOCKFILE],
+    ),
+    (id::ARG_NIGHTLY, &[id::ARG_VERIFY, id::ARG_RELEASE_SOURCE]),
+    // the nightly MSRV can't be written as the `rust-version` of a stable release
+    (id::ARG_WRITE_MSRV, &[id::ARG_NIGHTLY]),
+    (id::ARG_WORKSPACE, &[id::ARG_VERIFY]),
+    (
+        id::ARG_FEATURE_SET,
+        &[
+            id::ARG_EACH_FEATURE,
+            id::ARG_FEATURE_POWERSET,
+            id::ARG_VERIFY,
+        ],
+    ),
+    (
+        id::ARG_EACH_FEATURE,
+        &[id::ARG_FEATURE_POWERSET, id::ARG_VERIFY],
+    ),
+    (id::ARG_FEATURE_POWERSET, &[id::ARG_VERIFY]),
+];
+
+/// The options which can only be used together with another option. Since the other option may
+/// be given in an environment variable or in a configuration file, these aren't checked by clap.
+pub(crate) const REQUIREMENTS: &[(&str, &str)] = &[
+    (id::ARG_STRICT, id::ARG_VERIFY),
+    (id::ARG_ON_CHECK_TIMEOUT, id::ARG_CHECK_TIMEOUT),
+];
+
+fn conflicts(id: &str) -> &'static [&'static str] {
+    CONFLICTS
+        .iter()
+        .find(|(option, _)| *option == id)
+        .map(|(_, conflicts)| *conflicts)
+        .unwrap_or(&[])
 }
 
 pub fn cli() -> App<'static, 'static> {
@@ -27,6 +127,7 @@ pub fn cli() -> App<'static, 'static> {
         .global_setting(AppSettings::ColorAuto)
         .global_setting(AppSettings::DontCollapseArgsInUsage)
         .global_setting(AppSettings::UnifiedHelpMessage)
+        .setting(AppSettings::VersionlessSubcommands)
         .max_term_width(120)
         .usage("cargo msrv [OPTIONS] or cargo-msrv [OPTIONS]")
         .about("Helps with finding the Minimal Supported Rust Version (MSRV)")
@@ -40,32 +141,23 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
                 .long("path")
                 .help("Path to the cargo project directory")
                 .takes_value(true)
+                .global(true)
                 .value_name("DIR")
-                .validator(|value| {
-                    std::fs::metadata(&value)
-                        .map_err(|_| "Path doesn't exist.".to_string())
-                        .and_then(|m| {
-                            if m.is_dir() {
-                                Ok(())
-                            } else {
-                                Err("Not a directory.".to_string())
-                            }
-                        })
-                }),
+                .validator(validate_path),
         )
         .arg(
             Arg::with_name(id::ARG_SEEK_CUSTOM_TARGET)
                 .long("target")
                 .help("Check against a custom target (instead of the rustup default)")
+                .long_help("Check against a custom target (instead of the rustup default). \
+                The toolchains of the host are used, the standard library of the target is added with rustup, \
+                and `--target <TARGET>` is passed to the check command. \
+                May be given multiple times, in which case the MSRV is determined separately for each target. \
+                The MSRV of each target is reported, together with the overall MSRV, which is the highest of them.")
                 .takes_value(true)
                 .value_name("TARGET")
-                .validator(|value| {
-                    is_target_available(&value).map_err(|_| {
-                        "The provided target is not available. Use `rustup target list` to \
-                         review the available targets."
-                            .to_string()
-                    })
-                }),
+                .multiple(true)
+                .number_of_values(1),
         )
         .arg(Arg::with_name(id::ARG_INCLUDE_ALL_PATCH_RELEASES)
             .long("include-all-patch-releases")
@@ -77,7 +169,8 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
             .visible_alias("minimum")
             .help("Earliest version to take into account")
             .long_help("Earliest (least recent) version to take into account. \
-             Version must match a valid Rust toolchain, and be semver compatible. Edition aliases may also be used.")
+             Version must match a valid Rust toolchain, and be semver compatible. Edition aliases may also be used. \
+             With --nightly, the date of a nightly (YYYY-MM-DD) is expected instead.")
             .takes_value(true)
         )
         .arg(Arg::with_name(id::ARG_MAX)
@@ -85,7 +178,8 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
             .visible_alias("maximum")
             .help("Latest version to take into account")
             .long_help("Latest (most recent) version to take into account.\
-             Version must match a valid Rust toolchain, and be semver compatible.")
+             Version must match a valid Rust toolchain, and be semver compatible. \
+             With --nightly, the date of a nightly (YYYY-MM-DD) is expected instead.")
             .takes_value(true)
         )
         .arg(Arg::with_name(id::ARG_BISECT)
@@ -93,6 +187,17 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
             .help("Use a binary search to find the MSRV instead of a linear search")
             .takes_value(false)
         )
+        .arg(Arg::with_name(id::ARG_GALLOPING)
+            .long("galloping")
+            .help("Start the search at the MSRV declared in the Cargo.toml manifest")
+            .long_help("Start the search at the MSRV declared in the Cargo.toml manifest (`package.rust-version` or \
+            `package.metadata.msrv`), and expand it exponentially in the right direction before narrowing it down \
+            with a binary search. If the MSRV didn't change, or changed by a single release, this takes only two or \
+            three checks. Without a declared MSRV, a binary search is used. The checks are not run concurrently \
+            in this mode.")
+            .takes_value(false)
+            .conflicts_with_all(conflicts(id::ARG_GALLOPING))
+        )
         .arg(Arg::with_name(id::ARG_TOOLCHAIN_FILE)
             .long("toolchain-file")
             .help("Output a rust-toolchain file with the MSRV as toolchain")
@@ -100,17 +205,52 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
             The toolchain file will pin the Rust version for this crate. \
             See https://rust-lang.github.io/rustup/overrides.html#the-toolchain-file for more.")
         )
+        .arg(Arg::with_name(id::ARG_WRITE_MSRV)
+            .long("write-msrv")
+            .help("Write the MSRV to the Cargo.toml manifest")
+            .long_help("Write the MSRV to the Cargo.toml manifest, like the `set` subcommand does. \
+            The comments and formatting of the manifest are preserved.")
+            .conflicts_with_all(conflicts(id::ARG_WRITE_MSRV))
+        )
         .arg(Arg::with_name(id::ARG_IGNORE_LOCKFILE)
             .long("ignore-lockfile")
             .help("Temporarily removes the lockfile, so it will not interfere with the building process")
             .long_help("Temporarily removes the lockfile, so it will not interfere with the building process. \
             This is important when testing against Rust versions prior to 1.38.0, for which Cargo does not recognize the new v2 lockfile.")
         )
+        .arg(Arg::with_name(id::ARG_TRANSLATE_LOCKFILE)
+            .long("translate-lockfile")
+            .help("Temporarily translates the lockfile into a format which the checked toolchain can read")
+            .long_help("Temporarily translates the lockfile into the most recent format which the checked toolchain can read, \
+            instead of removing it like --ignore-lockfile does, so the locked versions of the dependencies are still used. \
+            Cargo reads v2 lockfiles since Rust 1.38.0, v3 lockfiles since Rust 1.47.0 and v4 lockfiles since Rust 1.78.0. \
+            The original lockfile is restored after each check.")
+            .conflicts_with_all(conflicts(id::ARG_TRANSLATE_LOCKFILE))
+        )
+        .arg(Arg::with_name(id::ARG_MINIMAL_VERSIONS)
+            .long("minimal-versions")
+            .help("Also determine the MSRV with the minimal versions of the dependencies")
+            .long_help("Also determine the MSRV with the minimal versions of the dependencies, i.e. the lowest versions \
+            which the version requirements allow. After the MSRV is determined with the lockfile, a lockfile which resolves \
+            the minimal versions is generated with `cargo +nightly update -Z minimal-versions`, and the MSRV is determined \
+            once more. This lockfile is translated into a format each checked toolchain can read (see --translate-lockfile), \
+            and the original lockfile is restored afterwards. Requires a nightly toolchain.")
+            .conflicts_with_all(conflicts(id::ARG_MINIMAL_VERSIONS))
+        )
+        .arg(Arg::with_name(id::ARG_DIRECT_MINIMAL_VERSIONS)
+            .long("direct-minimal-versions")
+            .help("Like --minimal-versions, but only resolve the direct dependencies to their minimal versions")
+            .long_help("Like --minimal-versions, but only the direct dependencies are resolved to their minimal versions, \
+            with `cargo +nightly update -Z direct-minimal-versions`, while their dependencies are resolved to the most recent \
+            compatible versions.")
+            .conflicts_with_all(conflicts(id::ARG_DIRECT_MINIMAL_VERSIONS))
+        )
         .arg(Arg::with_name(id::ARG_OUTPUT_FORMAT)
             .long("output-format")
             .help("Output status messages in machine-readable format")
             .takes_value(true)
             .possible_values(&["json"])
+            .global(true)
             .long_help("Output status messages in machine-readable format. \
         Machine-readable status updates will be printed in the requested format to stdout.")
         )
@@ -123,17 +263,55 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
             how we determine whether a Rust toolchain version is compatible for your crate or not.")
             .takes_value(false)
         )
+        .arg(Arg::with_name(id::ARG_STRICT)
+            .long("strict")
+            .help("When verifying, also check that the MSRV can't be lowered")
+            .long_help("When verifying, also check that the MSRV can't be lowered, by checking the release just below \
+            the MSRV declared in the Cargo.toml manifest. If this release is compatible too, the verification fails.")
+            .takes_value(false)
+        )
+        .arg(Arg::with_name(id::ARG_BLOCKING_DEPENDENCIES)
+            .long("blocking-dependencies")
+            .help("After determining the MSRV, list the dependencies which fail to compile with the release below it")
+            .long_help("After determining the MSRV, list the dependencies which fail to compile with the release below it, \
+            in the order in which they failed, together with the MSRV they declare (if any). If the release below the MSRV \
+            wasn't checked during the search, it will be checked once more. \
+            When no dependency fails to compile, the crate itself requires the MSRV.")
+            .takes_value(false)
+            .conflicts_with_all(conflicts(id::ARG_BLOCKING_DEPENDENCIES))
+        )
+        .arg(Arg::with_name(id::ARG_GENERATE_LOCKFILE)
+            .long("generate-lockfile")
+            .help("Generate a lockfile with which the crate builds on the given Rust version, by downgrading dependencies")
+            .long_help("Generate a lockfile with which the crate builds on the given Rust version, by downgrading dependencies. \
+            Each time the check fails, the first dependency which failed to compile is downgraded with `cargo update --precise`, \
+            until the check passes. The earlier versions are taken from cargo's local cache of the registry index, preferring \
+            versions which declare a compatible rust-version. The resulting lockfile is written to Cargo.lock.msrv, \
+            after which the original Cargo.lock is restored.")
+            .takes_value(true)
+            .value_name("RUST_VERSION")
+            .conflicts_with_all(conflicts(id::ARG_GENERATE_LOCKFILE))
+        )
         .arg(Arg::with_name(id::ARG_RELEASE_SOURCE)
             .long("release-source")
             .help("Select the rust-releases source to use as the release index")
+            .long_help("Select the rust-releases source to use as the release index: 'rust-changelog', 'rust-dist', \
+            'installed' or 'file:<path>'. Both 'rust-changelog' and 'rust-dist' fetch the releases over the network. \
+            With 'installed', only the stable releases of which a toolchain is installed with rustup (e.g. 1.56.0-<target>), \
+            or is in the --toolchain-dir, are checked, and no toolchains are installed. This is the default with --toolchain-dir. \
+            With 'file:<path>', the releases are read from a local file instead: a saved copy of the Rust changelog (RELEASES.md), \
+            a saved listing of the Rust distribution server, or a list of versions in a file with the 'json' extension \
+            ([\"1.56.0\", ...] or {\"versions\": [...]}) or the 'toml' extension (versions = [\"1.56.0\", ...]).")
             .takes_value(true)
-            .possible_values(&["rust-changelog", "rust-dist"])
+            .value_name("SOURCE")
+            .validator(|value| ReleaseSource::try_from(value.as_str()).map(|_| ()).map_err(|err| err.to_string()))
             .default_value("rust-changelog")
         )
         .arg(Arg::with_name(id::ARG_NO_LOG)
             .long("no-log")
             .help("Disable logging")
             .takes_value(false)
+            .global(true)
         )
         .arg(Arg::with_name(id::ARG_NO_READ_MIN_EDITION)
             .long("no-read-min-edition")
@@ -141,6 +319,150 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
             be used to reduce search space.")
             .takes_value(false)
         )
+        .arg(Arg::with_name(id::ARG_JOBS)
+            .long("jobs")
+            .short("j")
+            .help("Number of toolchain checks to run at the same time")
+            .long_help("Number of toolchain checks to run at the same time. \
+            Toolchains are installed one after another, after which up to N checks run concurrently. \
+            The linear search checks the next N versions at once, while the binary search splits the remaining \
+            versions into N + 1 parts. Cannot be combined with --ignore-lockfile or --translate-lockfile.")
+            .takes_value(true)
+            .value_name("N")
+            .conflicts_with_all(conflicts(id::ARG_JOBS))
+            .validator(|value| parse_jobs(&value).map(|_| ()).map_err(|err| err.to_string()))
+        )
+        .arg(Arg::with_name(id::ARG_CLEAN_TARGET_DIRS)
+            .long("clean-target-dirs")
+            .help("Remove the target directory of each toolchain after its check")
+            .long_help("Remove the target directory of each toolchain after its check, to save disk space. \
+            Each toolchain builds the crate in its own target directory (target/cargo-msrv/<toolchain>), so switching \
+            toolchains doesn't invalidate the builds of other toolchains, nor the regular target directory. \
+            A CARGO_TARGET_DIR set with --env is used as is, and never removed. By default, these directories are kept, so checks run again later can reuse the builds.")
+            .takes_value(false)
+        )
+        .arg(Arg::with_name(id::ARG_CHECK_TIMEOUT)
+            .long("check-timeout")
+            .help("Stop a check when it takes longer than the given duration")
+            .long_help("Stop a check when it takes longer than the given duration, e.g. '90s', '5m' or '1h' \
+            (seconds, if no unit is given). The check command, and the processes it started, are killed. \
+            On Unix, the check runs in its own process group, which is killed as a whole. \
+            What happens next is chosen with --on-check-timeout. Timed out checks are not cached.")
+            .takes_value(true)
+            .value_name("DURATION")
+            .validator(|value| parse_duration(&value).map(|_| ()).map_err(|err| err.to_string()))
+        )
+        .arg(Arg::with_name(id::ARG_ON_CHECK_TIMEOUT)
+            .long("on-check-timeout")
+            .help("What to do when a check times out")
+            .long_help("What to do when a check times out: 'fail' considers the toolchain to be incompatible, \
+            and continues the search, while 'abort' stops the search with an error. Defaults to 'fail'.")
+            .takes_value(true)
+            .possible_values(&["fail", "abort"])
+        )
+        .arg(Arg::with_name(id::ARG_RETRIES)
+            .long("retries")
+            .help("Number of times a check is retried when it fails for a reason unrelated to the Rust version")
+            .long_help("Number of times a check is retried when it fails for a reason unrelated to the Rust version, \
+            e.g. when the check was killed by a signal, or ran out of memory or disk space. Failures with compiler \
+            errors are never retried. Defaults to 2; 0 disables retries.")
+            .takes_value(true)
+            .value_name("N")
+            .validator(|value| parse_retries(&value).map(|_| ()).map_err(|err| err.to_string()))
+        )
+        .arg(Arg::with_name(id::ARG_ENV)
+            .long("env")
+            .help("Set an environment variable for each check, e.g. RUSTFLAGS=-Dwarnings")
+            .long_help("Set an environment variable for each check, e.g. RUSTFLAGS=-Dwarnings. \
+            May be given multiple times. When the variable is preceded by a Rust version requirement in brackets, \
+            e.g. '[<1.50]RUSTFLAGS=-Zfoo', it's only set for the versions which match the requirement. \
+            A variable which is given more than once is set to the value given last.")
+            .takes_value(true)
+            .value_name("[VERSIONS]NAME=VALUE")
+            .multiple(true)
+            .number_of_values(1)
+            .validator(|value| EnvVar::parse(&value).map(|_| ()).map_err(|err| err.to_string()))
+        )
+        .arg(Arg::with_name(id::ARG_ENV_FILE)
+            .long("env-file")
+            .help("Set the environment variables listed in the given file for each check")
+            .long_help("Set the environment variables listed in the given file for each check. \
+            The file lists one variable per line, in the same format as --env. Empty lines, and lines starting with '#', \
+            are skipped. Variables given with --env take precedence over those in the file.")
+            .takes_value(true)
+            .value_name("FILE")
+        )
+        .arg(Arg::with_name(id::ARG_TOOLCHAIN_DIR)
+            .long("toolchain-dir")
+            .help("Run the toolchains from the given directory, instead of installing them with rustup")
+            .long_help("Run the toolchains from the given directory, instead of installing them with rustup. \
+            The directory contains a folder for each toolchain, named after the toolchain, e.g. '1.56.0-x86_64-unknown-linux-gnu', \
+            with its binaries in 'bin', and the standard library of each target in 'lib/rustlib/<TARGET>'. \
+            The releases of which a toolchain is in the directory are checked, unless another release source is given.")
+            .takes_value(true)
+            .value_name("DIR")
+            .validator(validate_path)
+        )
+        .arg(Arg::with_name(id::ARG_NIGHTLY)
+            .long("nightly")
+            .help("Find the earliest nightly toolchain with which the crate builds")
+            .long_help("Find the earliest nightly toolchain with which the crate builds, instead of the earliest stable release. \
+            Useful for crates which depend on unstable features. The nightlies are searched with a binary search, \
+            and --min and --max take the date of a nightly (YYYY-MM-DD) in this mode. \
+            Days on which no nightly was published, or of which the nightly can't be installed, are skipped. \
+            By default, the nightlies from the release of Rust 1.0.0 up to yesterday are searched.")
+            .takes_value(false)
+            .conflicts_with_all(conflicts(id::ARG_NIGHTLY))
+        )
+        .arg(Arg::with_name(id::ARG_WORKSPACE)
+            .long("workspace")
+            .help("Determine the MSRV of each workspace member separately")
+            .long_help("Determine the MSRV of each workspace member separately. \
+            Each member is checked on its own (with `-p <member>` instead of `--all` or `--workspace`), after the members it depends on. \
+            The MSRV of a member is never lower than the MSRV of the members it depends on. \
+            This is the default when the Cargo.toml manifest is a virtual workspace manifest.")
+            .takes_value(false)
+            .conflicts_with_all(conflicts(id::ARG_WORKSPACE))
+        )
+        .arg(Arg::with_name(id::ARG_FEATURE_SET)
+            .long("feature-set")
+            .help("Determine the MSRV separately for the given comma separated set of features")
+            .long_help("Determine the MSRV separately for the given comma separated set of features. \
+            May be given multiple times. Each feature set is checked with `--no-default-features --features <FEATURES>`, \
+            so add the `default` feature to a set to include the default features. \
+            The MSRV of each feature set is reported, together with the overall MSRV, which is the highest of them.")
+            .takes_value(true)
+            .value_name("FEATURES")
+            .multiple(true)
+            .number_of_values(1)
+            .conflicts_with_all(conflicts(id::ARG_FEATURE_SET))
+        )
+        .arg(Arg::with_name(id::ARG_EACH_FEATURE)
+            .long("each-feature")
+            .help("Determine the MSRV separately without features, and for each feature on its own")
+            .long_help("Determine the MSRV separately without features, and for each feature on its own. \
+            The features are taken from the [features] table of the Cargo.toml manifest.")
+            .takes_value(false)
+            .conflicts_with_all(conflicts(id::ARG_EACH_FEATURE))
+        )
+        .arg(Arg::with_name(id::ARG_FEATURE_POWERSET)
+            .long("feature-powerset")
+            .help("Determine the MSRV separately for every combination of features")
+            .long_help("Determine the MSRV separately for every combination of features. \
+            The features are taken from the [features] table of the Cargo.toml manifest, except for the `default` feature. \
+            The number of combinations grows exponentially, so at most 12 features are supported.")
+            .takes_value(false)
+            .conflicts_with_all(conflicts(id::ARG_FEATURE_POWERSET))
+        )
+        .arg(Arg::with_name(id::ARG_NO_CACHE)
+            .long("no-cache")
+            .help("Don't use or update the cache of check outcomes")
+            .long_help("Don't use or update the cache of check outcomes. \
+            By default, the outcome of each check is stored on disk, keyed by the toolchain, the check command, \
+            the target, and a fingerprint of the Cargo.toml, Cargo.lock and source files of the crate. \
+            When none of these changed since a previous run, the stored outcome is used instead of running the check again.")
+            .takes_value(false)
+        )
         .arg(
             Arg::with_name(id::ARG_CUSTOM_CHECK)
                 .value_name("COMMAND")
@@ -152,4 +474,37 @@ rustup like so: `rustup run <toolchain> <COMMAND...>`. You'll only need to provi
                 .last(true)
 
         )
+        .subcommand(SubCommand::with_name(id::SUB_COMMAND_LIST)
+            .about("List the MSRVs declared by the dependencies")
+            .long_about("List the MSRVs declared by the dependencies, as resolved in the Cargo.lock lockfile, \
+            ordered from the highest to the lowest MSRV. The MSRV of a dependency is read from the `package.rust-version` \
+            or `package.metadata.msrv` key in its Cargo.toml manifest, which is taken from the vendored sources of the \
+            crate, or from cargo's registry cache. Nothing is downloaded or built, so the MSRV of a dependency which \
+            cargo hasn't downloaded yet is unknown.")
+        )
+        .subcommand(SubCommand::with_name(id::SUB_COMMAND_SET)
+            .about("Write the given MSRV to the Cargo.toml manifest")
+            .long_about("Write the given MSRV to the Cargo.toml manifest, while preserving its comments and formatting. \
+            A `package.rust-version` which is already declared is updated. Otherwise, `package.rust-version` is added, \
+            unless the MSRV is below Rust 1.56, which doesn't understand it, in which case `package.metadata.msrv` \
+            is written instead. When the crate inherits its rust-version from the workspace \
+            (`rust-version.workspace = true`), the `workspace.package.rust-version` of the workspace root is written.")
+            .arg(Arg::with_name(id::ARG_SET_VERSION)
+                .value_name("VERSION")
+                .help("The MSRV, as a two or three component Rust version, e.g. 1.56 or 1.56.1")
+                .required(true)
+            )
+        )
+}
+
+pub(crate) fn validate_path(value: String) -> Result<(), String> {
+    std::fs::metadata(&value)
+        .map_err(|_| "Path doesn't exist.".to_string())
+        .and_then(|m| {
+            if m.is_dir() {
+                Ok(())
+            } else {
+                Err("Not a directory.".to_string())
+            }
+        })
 }
@@ -1,27 +1,175 @@
-use crate::errors::TResult;
+use crate::errors::{CargoMSRVError, TResult};
 use std::ffi::OsStr;
+use std::io::Read;
 use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-pub fn command_with_output<I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
+/// How often a child process is polled, to see if it has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn command_with_output<P: AsRef<OsStr>, I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
+    program: P,
     commands: I,
 ) -> TResult<Child> {
-    command_impl(commands, None)
+    command_impl(program, commands, None)
         .pipe_output()
         .spawn()
         .map_err(From::from)
 }
 
-pub fn command<I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
+pub fn command<P: AsRef<OsStr>, I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
+    program: P,
     commands: I,
     dir: Option<&Path>,
 ) -> TResult<Child> {
-    command_impl(commands, dir)
+    command_impl(program, commands, dir)
         .pipe_output()
         .spawn()
         .map_err(From::from)
 }
 
+/// Runs the command in its own process group, so it can be killed together with the processes it
+/// starts, see [`wait_with_timeout`].
+pub fn command_with_env<P, I, V, E, K, W>(
+    program: P,
+    commands: I,
+    dir: Option<&Path>,
+    envs: E,
+) -> TResult<Child>
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = V>,
+    V: AsRef<OsStr>,
+    E: IntoIterator<Item = (K, W)>,
+    K: AsRef<OsStr>,
+    W: AsRef<OsStr>,
+{
+    command_impl(program, commands, dir)
+        .envs(envs)
+        .process_group()
+        .pipe_output()
+        .spawn()
+        .map_err(From::from)
+}
+
+/// Waits until the child exits, and collects its output, like [`Child::wait_with_output`].
+///
+/// If the child doesn't exit within the timeout, it's killed together with the processes it
+/// started, and `None` is returned. The processes it started are only killed if the child leads
+/// its own process group, like the commands started with [`command_with_env`]; otherwise an error
+/// is returned.
+pub fn wait_with_timeout(mut child: Child, timeout: Option<Duration>) -> TResult<Option<Output>> {
+    // a timeout too large to represent never expires
+    let deadline = match timeout.and_then(|timeout| Instant::now().checked_add(timeout)) {
+        Some(deadline) => deadline,
+        None => return child.wait_with_output().map(Some).map_err(From::from),
+    };
+
+    // the pipes are read while waiting, so the child doesn't block on a full pipe
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if Instant::now() >= deadline {
+            let killed = kill_process_tree(&mut child);
+            let _ = child.wait();
+            killed?;
+
+            // the pipes are closed once every process of the tree is killed
+            collect(stdout);
+            collect(stderr);
+
+            return Ok(None);
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    Ok(Some(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    }))
+}
+
+fn collect(reader: Option<JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default()
+}
+
+fn read_in_background<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+// Killing just the child isn't enough: `rustup run` starts cargo, which starts rustc, build scripts
+// and so on, which would keep running, and hold on to the pipes.
+#[cfg(windows)]
+fn kill_process_tree(child: &mut Child) -> TResult<()> {
+    let pid = child.id().to_string();
+    let killed = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid].iter())
+        .output();
+    let _ = child.kill();
+
+    killed.map(|_| ()).map_err(|err| {
+        CargoMSRVError::UnableToStopCheck(format!("unable to run `taskkill`: {}", err))
+    })
+}
+
+// The child leads its own process group, see `command_with_env`, which contains the processes it
+// started as well, so they're killed at once
+#[cfg(unix)]
+fn kill_process_tree(child: &mut Child) -> TResult<()> {
+    let killed = match unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } {
+        0 => Ok(()),
+        _ => Err(CargoMSRVError::UnableToStopCheck(format!(
+            "unable to kill process group {}: {}",
+            child.id(),
+            std::io::Error::last_os_error()
+        ))),
+    };
+    let _ = child.kill();
+
+    killed
+}
+
+trait ProcessGroup {
+    fn process_group(&mut self) -> &mut Command;
+}
+
+impl ProcessGroup for Command {
+    // The child becomes the leader of a new process group, which its children join
+    #[cfg(unix)]
+    fn process_group(&mut self) -> &mut Command {
+        use std::os::unix::process::CommandExt;
+
+        // setpgid is async-signal-safe, so it may be called between fork and exec
+        unsafe {
+            self.pre_exec(|| match libc::setpgid(0, 0) {
+                0 => Ok(()),
+                _ => Err(std::io::Error::last_os_error()),
+            })
+        }
+    }
+
+    // taskkill finds the processes started by the child instead
+    #[cfg(windows)]
+    fn process_group(&mut self) -> &mut Command {
+        self
+    }
+}
+
 trait PipeCliOutput {
     fn pipe_output(&mut self) -> &mut Command;
 }
@@ -33,11 +181,12 @@ impl PipeCliOutput for Command {
     }
 }
 
-fn command_impl<I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
+fn command_impl<P: AsRef<OsStr>, I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
+    program: P,
     commands: I,
     current_dir: Option<&Path>,
 ) -> Command {
-    let mut cmd = Command::new("rustup");
+    let mut cmd = Command::new(program);
     let _ = cmd.args(commands);
 
     if let Some(dir) = current_dir {
@@ -46,3 +195,60 @@ fn command_impl<I: IntoIterator<Item = V>, V: AsRef<OsStr>>(
 
     cmd
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finishes_within_timeout() {
+        let child = Command::new("cargo")
+            .arg("--version")
+            .pipe_output()
+            .spawn()
+            .unwrap();
+
+        let output = wait_with_timeout(child, Some(Duration::from_secs(60)))
+            .unwrap()
+            .expect("cargo --version should finish within the timeout");
+
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("cargo"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn killed_after_timeout() {
+        let child = Command::new("sh")
+            .args(["-c", "sleep 30"].iter())
+            .process_group()
+            .pipe_output()
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        let output = wait_with_timeout(child, Some(Duration::from_millis(200))).unwrap();
+
+        assert!(output.is_none());
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+
+    // the orphaned grandchild holds on to the pipes, so the readers only finish once it's killed
+    // as well
+    #[cfg(not(windows))]
+    #[test]
+    fn descendants_killed_after_timeout() {
+        let child = Command::new("sh")
+            .args(["-c", "(sleep 30 &); sleep 30"].iter())
+            .process_group()
+            .pipe_output()
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        let output = wait_with_timeout(child, Some(Duration::from_millis(200))).unwrap();
+
+        assert!(output.is_none());
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -1,8 +1,20 @@
+use crate::cache::SourceDigests;
+use crate::cli::{id, CONFLICTS, REQUIREMENTS};
+use crate::config_env::{ConfigEnv, ENV_OPTIONS};
+use crate::config_file::{ConfigFile, FileValue, OPTIONS};
+use crate::environment::{EnvVar, Environment};
 use crate::errors::{CargoMSRVError, TResult};
+use crate::features::{FeatureCombinations, FeatureSet};
+use crate::manifest::BareVersion;
+use crate::nightly::NightlyDate;
+use crate::toolchain::{Rustup, ToolchainDirectory, ToolchainProvider};
 use clap::ArgMatches;
 use rust_releases::semver;
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
@@ -22,12 +34,26 @@ impl Default for OutputFormat {
     }
 }
 
-/// Gets a [`Config`] from the given matches, but sets output_format to None
+impl TryFrom<&str> for OutputFormat {
+    type Error = CargoMSRVError;
+
+    fn try_from(format: &str) -> Result<Self, Self::Error> {
+        match format {
+            "json" => Ok(Self::Json),
+            _ => Err(CargoMSRVError::InvalidOutputFormat(format.to_string())),
+        }
+    }
+}
+
+/// Gets a [`Config`] from the given matches, but sets output_format to None, and stores the
+/// outcomes of checks in a temporary folder instead of the user's cache folder
 ///
 /// This is meant to be used for testing
 pub fn test_config_from_matches<'a>(matches: &'a ArgMatches<'a>) -> TResult<Config<'a>> {
     let mut config = Config::try_from(matches)?;
     config.output_format = OutputFormat::None;
+    config.cache_folder =
+        Some(std::env::temp_dir().join(format!("cargo-msrv-test-outcomes-{}", std::process::id())));
     Ok(config)
 }
 
@@ -37,6 +63,12 @@ pub enum ModeIntent {
     DetermineMSRV,
     // Verifies the given MSRV
     VerifyMSRV,
+    // Generates a lockfile with which the crate builds on the given Rust version
+    GenerateLockfile,
+    // Lists the MSRVs declared by the dependencies of a project
+    ListDependencies,
+    // Writes the given MSRV to the manifest of a project
+    SetMSRV,
 }
 
 impl From<ModeIntent> for &'static str {
@@ -44,16 +76,26 @@ impl From<ModeIntent> for &'static str {
         match action {
             ModeIntent::DetermineMSRV => "determine-msrv",
             ModeIntent::VerifyMSRV => "verify-msrv",
+            ModeIntent::GenerateLockfile => "generate-lockfile",
+            ModeIntent::ListDependencies => "list-dependencies",
+            ModeIntent::SetMSRV => "set-msrv",
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ReleaseSource {
     RustChangelog,
     RustDist,
+    /// A local file which lists the releases, given as `file:<path>`
+    File(PathBuf),
+    /// The stable releases of which a toolchain is installed with rustup, which are never installed
+    /// by cargo-msrv
+    Installed,
 }
 
+pub(crate) const RELEASE_SOURCE_FILE_PREFIX: &str = "file:";
+
 impl TryFrom<&str> for ReleaseSource {
     type Error = CargoMSRVError;
 
@@ -61,27 +103,109 @@ impl TryFrom<&str> for ReleaseSource {
         match source {
             "rust-changelog" => Ok(Self::RustChangelog),
             "rust-dist" => Ok(Self::RustDist),
-            s => Err(CargoMSRVError::RustReleasesSourceParseError(s.to_string())),
+            "installed" => Ok(Self::Installed),
+            s => match s.strip_prefix(RELEASE_SOURCE_FILE_PREFIX) {
+                Some(path) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+                _ => Err(CargoMSRVError::RustReleasesSourceParseError(s.to_string())),
+            },
+        }
+    }
+}
+
+/// The dependencies which are resolved to the minimal versions their dependents accept, when the
+/// MSRV is also determined with the minimal versions of the dependencies.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MinimalVersions {
+    /// All dependencies, with `cargo update -Z minimal-versions`
+    All,
+    /// Only the direct dependencies, with `cargo update -Z direct-minimal-versions`
+    Direct,
+}
+
+impl MinimalVersions {
+    /// The unstable cargo flag with which the lockfile is generated
+    pub fn unstable_flag(self) -> &'static str {
+        match self {
+            Self::All => "minimal-versions",
+            Self::Direct => "direct-minimal-versions",
+        }
+    }
+}
+
+/// What to do when a check doesn't finish within the check timeout.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeoutPolicy {
+    /// The toolchain is considered to be incompatible, and the search continues
+    Fail,
+    /// The search is aborted with an error
+    Abort,
+}
+
+impl TryFrom<&str> for TimeoutPolicy {
+    type Error = CargoMSRVError;
+
+    fn try_from(policy: &str) -> Result<Self, Self::Error> {
+        match policy {
+            "fail" => Ok(Self::Fail),
+            "abort" => Ok(Self::Abort),
+            _ => Err(CargoMSRVError::InvalidTimeoutPolicy(policy.to_string())),
         }
     }
 }
 
+/// The strategy used to search for the MSRV among the included releases.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SearchMethod {
+    /// Check the releases one by one, from the most recent to the least recent release
+    Linear,
+    /// Use a binary search over all releases
+    Bisect,
+    /// Start at the given release, usually the MSRV declared in the Cargo.toml manifest, and expand
+    /// the search exponentially in the right direction, before narrowing it down with a binary search.
+    /// Without a release to start at, a binary search over all releases is used.
+    Galloping { seed: Option<semver::Version> },
+}
+
 #[derive(Debug, Clone)]
 pub struct Config<'a> {
     mode_intent: ModeIntent,
     target: String,
-    check_command: Vec<&'a str>,
+    check_command: Vec<Cow<'a, str>>,
     crate_path: Option<PathBuf>,
     include_all_patch_releases: bool,
     minimum_version: Option<semver::Version>,
     maximum_version: Option<semver::Version>,
-    bisect: bool,
+    search_method: SearchMethod,
     output_toolchain_file: bool,
+    write_msrv: bool,
     ignore_lockfile: bool,
+    translate_lockfile: bool,
+    minimal_versions: Option<MinimalVersions>,
+    toolchain_provider: Arc<dyn ToolchainProvider>,
+    check_timeout: Option<Duration>,
+    timeout_policy: TimeoutPolicy,
+    retries: u32,
+    environment: Environment,
     output_format: OutputFormat,
     release_source: ReleaseSource,
     no_tracing: bool,
     no_read_min_edition: Option<semver::Version>,
+    no_cache: bool,
+    cache_folder: Option<PathBuf>,
+    source_digests: SourceDigests,
+    jobs: usize,
+    clean_target_dirs: bool,
+    feature_combinations: Option<FeatureCombinations>,
+    features: Option<FeatureSet>,
+    workspace: bool,
+    package: Option<String>,
+    cross_targets: Vec<String>,
+    cross_target: Option<String>,
+    nightly: bool,
+    strict: bool,
+    blocking_dependencies: bool,
+    lockfile_rust_version: Option<BareVersion>,
+    manifest_rust_version: Option<BareVersion>,
 }
 
 impl<'a> Config<'a> {
@@ -89,18 +213,42 @@ impl<'a> Config<'a> {
         Self {
             mode_intent,
             target,
-            check_command: vec!["cargo", "check", "--all"],
+            check_command: vec!["cargo".into(), "check".into(), "--all".into()],
             crate_path: None,
             include_all_patch_releases: false,
             minimum_version: None,
             maximum_version: None,
-            bisect: false,
+            search_method: SearchMethod::Linear,
             output_toolchain_file: false,
+            write_msrv: false,
             ignore_lockfile: false,
+            translate_lockfile: false,
+            minimal_versions: None,
+            toolchain_provider: Arc::new(Rustup),
+            check_timeout: None,
+            timeout_policy: TimeoutPolicy::Fail,
+            retries: 2,
+            environment: Environment::default(),
             output_format: OutputFormat::Human,
             release_source: ReleaseSource::RustChangelog,
             no_tracing: false,
             no_read_min_edition: None,
+            no_cache: false,
+            cache_folder: None,
+            source_digests: SourceDigests::default(),
+            jobs: 1,
+            clean_target_dirs: false,
+            feature_combinations: None,
+            features: None,
+            workspace: false,
+            package: None,
+            cross_targets: Vec::new(),
+            cross_target: None,
+            nightly: false,
+            strict: false,
+            blocking_dependencies: false,
+            lockfile_rust_version: None,
+            manifest_rust_version: None,
         }
     }
 
@@ -112,12 +260,38 @@ impl<'a> Config<'a> {
         &self.target
     }
 
-    pub fn check_command(&self) -> &Vec<&'a str> {
-        &self.check_command
+    pub fn check_command(&self) -> Vec<&str> {
+        self.check_command.iter().map(AsRef::as_ref).collect()
+    }
+
+    /// The check command, extended with the arguments which follow from the other options,
+    /// such as the feature set to check.
+    pub fn effective_check_command(&self) -> Vec<String> {
+        let mut command = self
+            .check_command
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+
+        // only check the selected workspace member, instead of the whole workspace
+        if let Some(package) = &self.package {
+            remove_workspace_args(&mut command);
+            insert_cargo_args(&mut command, vec!["-p".to_string(), package.clone()]);
+        }
+
+        if let Some(features) = &self.features {
+            insert_cargo_args(&mut command, features.cargo_args());
+        }
+
+        if let Some(target) = &self.cross_target {
+            insert_cargo_args(&mut command, vec!["--target".to_string(), target.clone()]);
+        }
+
+        command
     }
 
     pub fn check_command_string(&self) -> String {
-        self.check_command.join(" ")
+        self.effective_check_command().join(" ")
     }
 
     pub fn crate_path(&self) -> Option<&Path> {
@@ -136,24 +310,67 @@ impl<'a> Config<'a> {
         self.maximum_version.as_ref()
     }
 
-    pub fn bisect(&self) -> bool {
-        self.bisect
+    pub fn search_method(&self) -> &SearchMethod {
+        &self.search_method
     }
 
     pub fn output_toolchain_file(&self) -> bool {
         self.output_toolchain_file
     }
 
+    pub fn write_msrv(&self) -> bool {
+        self.write_msrv
+    }
+
     pub fn ignore_lockfile(&self) -> bool {
         self.ignore_lockfile
     }
 
+    pub fn translate_lockfile(&self) -> bool {
+        self.translate_lockfile
+    }
+
+    pub fn minimal_versions(&self) -> Option<MinimalVersions> {
+        self.minimal_versions
+    }
+
+    /// Installs and runs the checked toolchains; rustup by default
+    pub fn toolchain_provider(&self) -> &Arc<dyn ToolchainProvider> {
+        &self.toolchain_provider
+    }
+
+    /// The maximum duration of a single check; unlimited if `None`
+    pub fn check_timeout(&self) -> Option<Duration> {
+        self.check_timeout
+    }
+
+    pub fn timeout_policy(&self) -> TimeoutPolicy {
+        self.timeout_policy
+    }
+
+    /// The number of times a check is run once more, when it failed for a reason unrelated to the
+    /// Rust version, e.g. because it was killed, or ran out of memory or disk space.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// The environment variables which are set for each check.
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
     pub fn output_format(&self) -> OutputFormat {
         self.output_format
     }
 
-    pub fn release_source(&self) -> ReleaseSource {
-        self.release_source
+    pub fn release_source(&self) -> &ReleaseSource {
+        &self.release_source
+    }
+
+    /// Whether toolchains are installed before they're checked. The toolchains of the installed
+    /// releases are already installed, and shouldn't be modified.
+    pub fn installs_toolchains(&self) -> bool {
+        self.release_source != ReleaseSource::Installed
     }
 
     pub fn no_tracing(&self) -> bool {
@@ -163,6 +380,80 @@ impl<'a> Config<'a> {
     pub fn no_read_min_version(&self) -> Option<&semver::Version> {
         self.no_read_min_edition.as_ref()
     }
+
+    pub fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+
+    /// The folder in which the outcomes of checks are cached; the user's cache folder if `None`
+    pub fn cache_folder(&self) -> Option<&Path> {
+        self.cache_folder.as_deref()
+    }
+
+    /// The digests of the checked source trees, shared by the configs derived from this one
+    pub fn source_digests(&self) -> &SourceDigests {
+        &self.source_digests
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Whether the target directory of each toolchain is removed after its check.
+    pub fn clean_target_dirs(&self) -> bool {
+        self.clean_target_dirs
+    }
+
+    pub fn feature_combinations(&self) -> Option<&FeatureCombinations> {
+        self.feature_combinations.as_ref()
+    }
+
+    pub fn features(&self) -> Option<&FeatureSet> {
+        self.features.as_ref()
+    }
+
+    pub fn workspace(&self) -> bool {
+        self.workspace
+    }
+
+    pub fn package(&self) -> Option<&str> {
+        self.package.as_deref()
+    }
+
+    /// The targets for which the MSRV is determined separately, by cross compiling from the host.
+    pub fn cross_targets(&self) -> &[String] {
+        &self.cross_targets
+    }
+
+    /// The target to cross compile to, using the toolchain of the host.
+    pub fn cross_target(&self) -> Option<&str> {
+        self.cross_target.as_deref()
+    }
+
+    /// Whether to search for the earliest nightly, instead of the earliest stable release.
+    pub fn nightly(&self) -> bool {
+        self.nightly
+    }
+
+    /// Whether verifying also checks that the release below the declared MSRV is incompatible.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Whether to list the dependencies which fail to compile with the release below the MSRV.
+    pub fn blocking_dependencies(&self) -> bool {
+        self.blocking_dependencies
+    }
+
+    /// The Rust version for which a lockfile is generated, in the `GenerateLockfile` mode.
+    pub fn lockfile_rust_version(&self) -> Option<&BareVersion> {
+        self.lockfile_rust_version.as_ref()
+    }
+
+    /// The Rust version which is written to the manifest, in the `SetMSRV` mode.
+    pub fn manifest_rust_version(&self) -> Option<&BareVersion> {
+        self.manifest_rust_version.as_ref()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -177,6 +468,12 @@ impl<'a> ConfigBuilder<'a> {
         }
     }
 
+    /// Starts from an existing configuration, e.g. to derive the configuration of a single
+    /// feature set.
+    pub fn from_config(config: Config<'a>) -> Self {
+        Self { inner: config }
+    }
+
     pub fn mode_intent(mut self, mode_intent: ModeIntent) -> Self {
         self.inner.mode_intent = mode_intent;
         self
@@ -187,8 +484,8 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
-    pub fn check_command(mut self, cmd: Vec<&'a str>) -> Self {
-        self.inner.check_command = cmd;
+    pub fn check_command<S: Into<Cow<'a, str>>>(mut self, cmd: Vec<S>) -> Self {
+        self.inner.check_command = cmd.into_iter().map(Into::into).collect();
         self
     }
 
@@ -212,8 +509,8 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
-    pub fn bisect(mut self, answer: bool) -> Self {
-        self.inner.bisect = answer;
+    pub fn search_method(mut self, method: SearchMethod) -> Self {
+        self.inner.search_method = method;
         self
     }
 
@@ -222,11 +519,51 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
+    pub fn write_msrv(mut self, choice: bool) -> Self {
+        self.inner.write_msrv = choice;
+        self
+    }
+
     pub fn ignore_lockfile(mut self, choice: bool) -> Self {
         self.inner.ignore_lockfile = choice;
         self
     }
 
+    pub fn translate_lockfile(mut self, choice: bool) -> Self {
+        self.inner.translate_lockfile = choice;
+        self
+    }
+
+    pub fn minimal_versions(mut self, minimal_versions: Option<MinimalVersions>) -> Self {
+        self.inner.minimal_versions = minimal_versions;
+        self
+    }
+
+    pub fn toolchain_provider(mut self, provider: Arc<dyn ToolchainProvider>) -> Self {
+        self.inner.toolchain_provider = provider;
+        self
+    }
+
+    pub fn check_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.inner.check_timeout = timeout;
+        self
+    }
+
+    pub fn timeout_policy(mut self, policy: TimeoutPolicy) -> Self {
+        self.inner.timeout_policy = policy;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.inner.retries = retries;
+        self
+    }
+
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.inner.environment = environment;
+        self
+    }
+
     pub fn output_format(mut self, output_format: OutputFormat) -> Self {
         self.inner.output_format = output_format;
         self
@@ -247,6 +584,81 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
+    pub fn no_cache(mut self, choice: bool) -> Self {
+        self.inner.no_cache = choice;
+        self
+    }
+
+    pub fn cache_folder<P: AsRef<Path>>(mut self, folder: P) -> Self {
+        self.inner.cache_folder = Some(folder.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.inner.jobs = jobs;
+        self
+    }
+
+    pub fn clean_target_dirs(mut self, choice: bool) -> Self {
+        self.inner.clean_target_dirs = choice;
+        self
+    }
+
+    pub fn feature_combinations(mut self, combinations: FeatureCombinations) -> Self {
+        self.inner.feature_combinations = Some(combinations);
+        self
+    }
+
+    pub fn features(mut self, features: FeatureSet) -> Self {
+        self.inner.features = Some(features);
+        self
+    }
+
+    pub fn workspace(mut self, choice: bool) -> Self {
+        self.inner.workspace = choice;
+        self
+    }
+
+    pub fn package(mut self, package: String) -> Self {
+        self.inner.package = Some(package);
+        self
+    }
+
+    pub fn cross_targets(mut self, targets: Vec<String>) -> Self {
+        self.inner.cross_targets = targets;
+        self
+    }
+
+    pub fn cross_target(mut self, target: String) -> Self {
+        self.inner.cross_target = Some(target);
+        self
+    }
+
+    pub fn nightly(mut self, choice: bool) -> Self {
+        self.inner.nightly = choice;
+        self
+    }
+
+    pub fn strict(mut self, choice: bool) -> Self {
+        self.inner.strict = choice;
+        self
+    }
+
+    pub fn blocking_dependencies(mut self, choice: bool) -> Self {
+        self.inner.blocking_dependencies = choice;
+        self
+    }
+
+    pub fn lockfile_rust_version(mut self, version: BareVersion) -> Self {
+        self.inner.lockfile_rust_version = Some(version);
+        self
+    }
+
+    pub fn manifest_rust_version(mut self, version: BareVersion) -> Self {
+        self.inner.manifest_rust_version = Some(version);
+        self
+    }
+
     pub fn build(self) -> Config<'a> {
         self.inner
     }
@@ -256,45 +668,136 @@ impl<'config> TryFrom<&'config ArgMatches<'config>> for Config<'config> {
     type Error = CargoMSRVError;
 
     fn try_from(matches: &'config ArgMatches<'config>) -> Result<Self, Self::Error> {
-        use crate::cli::id;
-        use crate::fetch::default_target;
+        Self::from_matches_and_env(matches, ConfigEnv::from_env()?)
+    }
+}
+
+impl<'config> Config<'config> {
+    // The options which aren't given on the command line are taken from the given environment
+    // variables, and then from the configuration file of the crate
+    fn from_matches_and_env(
+        matches: &'config ArgMatches<'config>,
+        env: ConfigEnv,
+    ) -> TResult<Self> {
+        use crate::cli::validate_path;
+        use crate::fetch::{default_target, is_target_available};
+
+        // the options of a subcommand are given after it, while global options, such as the path,
+        // may also be given before it
+        let (matches, subcommand) = match matches.subcommand() {
+            (name, Some(subcommand)) => (subcommand, Some(name)),
+            _ => (matches, None),
+        };
 
-        let action_intent = if matches.is_present(id::ARG_VERIFY) {
-            ModeIntent::VerifyMSRV
+        // options which aren't given on the command line may be given in environment variables,
+        // and then in a configuration file, which is found once the path to the crate is known
+        let mut options = Options {
+            matches,
+            env,
+            file: None,
+        };
+
+        let action_intent = match subcommand {
+            Some(id::SUB_COMMAND_LIST) => ModeIntent::ListDependencies,
+            Some(id::SUB_COMMAND_SET) => ModeIntent::SetMSRV,
+            _ => match options.selected(MODES) {
+                Some(id::ARG_VERIFY) => ModeIntent::VerifyMSRV,
+                Some(_) => ModeIntent::GenerateLockfile,
+                None => ModeIntent::DetermineMSRV,
+            },
+        };
+
+        // set the cargo workspace path
+        let crate_path = options.value(id::ARG_SEEK_PATH);
+        if let Some(path) = &crate_path {
+            path.validate(validate_path)?;
+        }
+        let crate_path = crate_path.map(|path| PathBuf::from(path.values[0].as_ref()));
+
+        let crate_folder = if let Some(ref path) = crate_path {
+            Ok(path.to_path_buf())
         } else {
-            ModeIntent::DetermineMSRV
+            std::env::current_dir().map_err(CargoMSRVError::Io)
+        }?;
+
+        options.file = ConfigFile::find(&crate_folder)?;
+
+        // the options of the subcommands are given on the command line, and checked by clap
+        if subcommand.is_none() {
+            options.check_relations()?;
+        }
+
+        // the toolchains are run from the given directory, instead of being installed with rustup
+        let toolchain_dir = options.value(id::ARG_TOOLCHAIN_DIR);
+        let toolchain_provider: Arc<dyn ToolchainProvider> = match &toolchain_dir {
+            Some(dir) => {
+                dir.validate(validate_path)?;
+                Arc::new(ToolchainDirectory::new(dir.values[0].as_ref()))
+            }
+            None => Arc::new(Rustup),
         };
 
         // FIXME: if set, we don't need to do this; in case we can't find it, it may fail here, but atm can't be manually supplied at all
-        let target = default_target()?;
+        let target = default_target(toolchain_provider.as_ref())?;
 
-        let mut builder = ConfigBuilder::new(action_intent, &target);
+        let mut builder = ConfigBuilder::new(action_intent, &target)
+            .toolchain_provider(Arc::clone(&toolchain_provider))
+            .crate_path(crate_path);
 
-        // set the command which will be used to check if a project can build
-        let check_cmd = matches.values_of(id::ARG_CUSTOM_CHECK);
-        if let Some(cmd) = check_cmd {
-            builder = builder.check_command(cmd.collect());
+        // the Rust version for which a lockfile is generated
+        if let Some(version) = options.value(id::ARG_GENERATE_LOCKFILE) {
+            builder = builder
+                .lockfile_rust_version(version.parse(|version| BareVersion::try_from(version))?);
         }
 
-        // set the cargo workspace path
-        let crate_path = matches.value_of(id::ARG_SEEK_PATH);
-        builder = builder.crate_path(crate_path);
+        // the Rust version which is written to the manifest
+        if let Some(version) = options.value(id::ARG_SET_VERSION) {
+            builder = builder
+                .manifest_rust_version(version.parse(|version| BareVersion::try_from(version))?);
+        }
 
-        // set a custom target
-        let custom_target = matches.value_of(id::ARG_SEEK_CUSTOM_TARGET);
-        if let Some(target) = custom_target {
-            builder = builder.target(target);
+        // set the command which will be used to check if a project can build
+        if let Some(cmd) = options.value(id::ARG_CUSTOM_CHECK) {
+            builder = builder.check_command(cmd.values);
         }
 
-        match matches.value_of(id::ARG_MIN) {
-            Some(min) => builder = builder.minimum_version(parse_version(min)?),
-            None if matches.is_present(id::ARG_NO_READ_MIN_EDITION) => {}
+        // set the targets, which are cross compiled with the toolchains of the host, and checked
+        // separately
+        let custom_targets = options.value(id::ARG_SEEK_CUSTOM_TARGET);
+        if let Some(targets) = &custom_targets {
+            for target in &targets.values {
+                is_target_available(toolchain_provider.as_ref(), target)
+                    .map_err(|err| targets.origin.invalid(err))?;
+            }
+        }
+        let custom_targets: Vec<String> = custom_targets
+            .map(|targets| targets.values.into_iter().map(String::from).collect())
+            .unwrap_or_default();
+        // a single target is also cross compiled by the actions which don't check each target,
+        // such as verifying the MSRV
+        if let [target] = custom_targets.as_slice() {
+            builder = builder.cross_target(target.clone());
+        }
+        builder = builder.cross_targets(custom_targets);
+
+        // in nightly mode, the minimum and maximum are dates, and the edition isn't used
+        let nightly = options.flag(id::ARG_NIGHTLY);
+        builder = builder.nightly(nightly);
+
+        match options.value(id::ARG_MIN) {
+            Some(min) if nightly => builder = builder.minimum_version(min.parse(parse_nightly)?),
+            Some(min) => {
+                builder = builder
+                    .minimum_version(min.parse(|min| parse_version(min).map_err(From::from))?)
+            }
+            // the dependencies are listed, and the MSRV is set, without checking any release
+            None if nightly
+                || options.flag(id::ARG_NO_READ_MIN_EDITION)
+                || matches!(
+                    action_intent,
+                    ModeIntent::ListDependencies | ModeIntent::SetMSRV
+                ) => {}
             None => {
-                let crate_folder = if let Some(ref path) = builder.inner.crate_path {
-                    Ok(path.to_path_buf())
-                } else {
-                    std::env::current_dir().map_err(CargoMSRVError::Io)
-                }?;
                 let cargo_toml = crate_folder.join("Cargo.toml");
 
                 let contents = std::fs::read_to_string(&cargo_toml).map_err(CargoMSRVError::Io)?;
@@ -311,41 +814,426 @@ impl<'config> TryFrom<&'config ArgMatches<'config>> for Config<'config> {
             }
         }
 
-        if let Some(max) = matches.value_of(id::ARG_MAX) {
-            builder = builder.maximum_version(rust_releases::semver::Version::parse(max)?)
+        match options.value(id::ARG_MAX) {
+            Some(max) if nightly => builder = builder.maximum_version(max.parse(parse_nightly)?),
+            Some(max) => {
+                builder = builder.maximum_version(
+                    max.parse(|max| semver::Version::parse(max).map_err(From::from))?,
+                )
+            }
+            None => {}
         }
 
-        builder = builder.bisect(matches.is_present(id::ARG_BISECT));
+        match options.selected(SEARCH_METHODS) {
+            Some(id::ARG_BISECT) => builder = builder.search_method(SearchMethod::Bisect),
+            Some(_) => builder = builder.search_method(SearchMethod::Galloping { seed: None }),
+            // with thousands of nightlies, a linear search is not an option
+            None if nightly => builder = builder.search_method(SearchMethod::Bisect),
+            None => {}
+        }
 
-        builder = builder
-            .include_all_patch_releases(matches.is_present(id::ARG_INCLUDE_ALL_PATCH_RELEASES));
+        builder =
+            builder.include_all_patch_releases(options.flag(id::ARG_INCLUDE_ALL_PATCH_RELEASES));
 
-        builder = builder.output_toolchain_file(matches.is_present(id::ARG_TOOLCHAIN_FILE));
+        builder = builder.output_toolchain_file(options.flag(id::ARG_TOOLCHAIN_FILE));
 
-        builder = builder.ignore_lockfile(matches.is_present(id::ARG_IGNORE_LOCKFILE));
+        builder = builder.write_msrv(options.flag(id::ARG_WRITE_MSRV));
 
-        let output_format = matches.value_of(id::ARG_OUTPUT_FORMAT);
-        if let Some(output_format) = output_format {
-            let output_format = match output_format {
-                "json" => OutputFormat::Json,
-                _ => unreachable!(),
-            };
+        builder = builder.strict(options.flag(id::ARG_STRICT));
+
+        builder = builder.blocking_dependencies(options.flag(id::ARG_BLOCKING_DEPENDENCIES));
+
+        builder = builder.ignore_lockfile(options.flag(id::ARG_IGNORE_LOCKFILE));
+
+        builder = builder.translate_lockfile(options.flag(id::ARG_TRANSLATE_LOCKFILE));
+
+        match options.selected(MINIMAL_VERSIONS) {
+            Some(id::ARG_DIRECT_MINIMAL_VERSIONS) => {
+                builder = builder.minimal_versions(Some(MinimalVersions::Direct))
+            }
+            Some(_) => builder = builder.minimal_versions(Some(MinimalVersions::All)),
+            None => {}
+        }
+
+        if let Some(output_format) = options.value(id::ARG_OUTPUT_FORMAT) {
+            builder = builder
+                .output_format(output_format.parse(|format| OutputFormat::try_from(format))?);
+        }
+
+        // only the releases of which a toolchain is in the toolchain directory can be checked
+        match options.value(id::ARG_RELEASE_SOURCE) {
+            Some(release_source) => {
+                builder = builder
+                    .release_source(release_source.parse(|source| ReleaseSource::try_from(source))?)
+            }
+            None if toolchain_dir.is_some() => {
+                builder = builder.release_source(ReleaseSource::Installed)
+            }
+            None => {}
+        }
+
+        builder = builder.no_tracing(options.flag(id::ARG_NO_LOG));
+
+        builder = builder.no_cache(options.flag(id::ARG_NO_CACHE));
+
+        if let Some(jobs) = options.value(id::ARG_JOBS) {
+            builder = builder.jobs(jobs.parse(parse_jobs)?);
+        }
 
-            builder = builder.output_format(output_format);
+        builder = builder.clean_target_dirs(options.flag(id::ARG_CLEAN_TARGET_DIRS));
+
+        if let Some(timeout) = options.value(id::ARG_CHECK_TIMEOUT) {
+            builder = builder.check_timeout(Some(timeout.parse(parse_duration)?));
+        }
+
+        if let Some(policy) = options.value(id::ARG_ON_CHECK_TIMEOUT) {
+            builder =
+                builder.timeout_policy(policy.parse(|policy| TimeoutPolicy::try_from(policy))?);
         }
 
-        let release_source = matches.value_of(id::ARG_RELEASE_SOURCE);
-        if let Some(release_source) = release_source {
-            let release_source = ReleaseSource::try_from(release_source)?;
-            builder = builder.release_source(release_source);
+        if let Some(retries) = options.value(id::ARG_RETRIES) {
+            builder = builder.retries(retries.parse(parse_retries)?);
         }
 
-        builder = builder.no_tracing(matches.is_present(id::ARG_NO_LOG));
+        // the variables given on the command line are set after, and thus override, those in the file
+        let mut environment = match options.value(id::ARG_ENV_FILE) {
+            Some(path) => path.parse(|path| Environment::from_file(Path::new(path)))?,
+            None => Environment::default(),
+        };
+
+        if let Some(vars) = options.value(id::ARG_ENV) {
+            for var in vars.parse_each(EnvVar::parse)? {
+                environment.push(var);
+            }
+        }
+
+        builder = builder.environment(environment);
+
+        builder = builder.workspace(options.flag(id::ARG_WORKSPACE));
+
+        match options.selected(FEATURE_OPTIONS) {
+            Some(id::ARG_FEATURE_SET) => {
+                if let Some(sets) = options.value(id::ARG_FEATURE_SET) {
+                    let sets = sets
+                        .values
+                        .iter()
+                        .map(|set| FeatureSet::parse(set))
+                        .collect();
+                    builder = builder.feature_combinations(FeatureCombinations::Given(sets));
+                }
+            }
+            Some(id::ARG_EACH_FEATURE) => {
+                builder = builder.feature_combinations(FeatureCombinations::EachFeature)
+            }
+            Some(_) => builder = builder.feature_combinations(FeatureCombinations::Powerset),
+            None => {}
+        }
 
         Ok(builder.build())
     }
 }
 
+// Options which exclude each other: the one given with the highest precedence is used, so they only
+// conflict when they're given in the same place
+const MODES: &[&str] = &[id::ARG_VERIFY, id::ARG_GENERATE_LOCKFILE];
+const SEARCH_METHODS: &[&str] = &[id::ARG_BISECT, id::ARG_GALLOPING];
+const MINIMAL_VERSIONS: &[&str] = &[id::ARG_DIRECT_MINIMAL_VERSIONS, id::ARG_MINIMAL_VERSIONS];
+const FEATURE_OPTIONS: &[&str] = &[
+    id::ARG_FEATURE_SET,
+    id::ARG_EACH_FEATURE,
+    id::ARG_FEATURE_POWERSET,
+];
+
+/// The options given on the command line, and those given in environment variables and in the
+/// configuration file of the crate, which are used for the options which aren't given on the
+/// command line. Options given in environment variables take precedence over those in the file.
+struct Options<'m> {
+    matches: &'m ArgMatches<'m>,
+    env: ConfigEnv,
+    file: Option<ConfigFile>,
+}
+
+impl<'m> Options<'m> {
+    fn flag(&self, id: &str) -> bool {
+        self.matches.is_present(id) || matches!(self.setting(id), Some((FileValue::Flag(true), _)))
+    }
+
+    fn value(&self, id: &str) -> Option<OptionValue<'m, '_>> {
+        // values given by default on the command line, don't override those in the environment
+        // or in the file
+        if self.matches.occurrences_of(id) == 0 {
+            if let Some((FileValue::Values(values), origin)) = self.setting(id) {
+                return Some(OptionValue {
+                    values: values.iter().cloned().map(Cow::Owned).collect(),
+                    origin,
+                });
+            }
+        }
+
+        self.matches.values_of(id).map(|values| OptionValue {
+            values: values.map(Cow::Borrowed).collect(),
+            origin: Origin::CommandLine,
+        })
+    }
+
+    /// Of options which exclude each other, the one which is given on the command line, or,
+    /// if none is, the one given in an environment variable, and then in the configuration file.
+    fn selected<'id>(&self, ids: &[&'id str]) -> Option<&'id str> {
+        let given = |value: Option<&FileValue>| match value {
+            Some(FileValue::Flag(flag)) => *flag,
+            Some(FileValue::Values(_)) => true,
+            None => false,
+        };
+
+        ids.iter()
+            .find(|id| self.matches.occurrences_of(id) > 0)
+            .or_else(|| {
+                ids.iter()
+                    .find(|id| given(self.env.get(id).map(|(_, value)| value)))
+            })
+            .or_else(|| {
+                ids.iter().find(|id| {
+                    given(
+                        self.file
+                            .as_ref()
+                            .and_then(|file| file.get(id))
+                            .map(|(_, _, value)| value),
+                    )
+                })
+            })
+            .copied()
+    }
+
+    /// Where the option is given, if it's given; a flag which is unset isn't given.
+    fn given(&self, id: &str) -> Option<Origin<'_>> {
+        if self.matches.occurrences_of(id) > 0 {
+            return Some(Origin::CommandLine);
+        }
+
+        match self.setting(id) {
+            Some((FileValue::Flag(false), _)) | None => None,
+            Some((_, origin)) => Some(origin),
+        }
+    }
+
+    /// Rejects the options which conflict, and those which require an option which isn't given,
+    /// wherever they're given, like clap does for the options given on the command line.
+    fn check_relations(&self) -> TResult<()> {
+        let alternatives = |id: &str, other: &str| {
+            [MODES, SEARCH_METHODS, MINIMAL_VERSIONS, FEATURE_OPTIONS]
+                .iter()
+                .any(|options| options.contains(&id) && options.contains(&other))
+        };
+
+        for (id, conflicts) in CONFLICTS {
+            let origin = match self.given(id) {
+                Some(origin) => origin,
+                None => continue,
+            };
+
+            for other in conflicts.iter() {
+                let other_origin = match self.given(other) {
+                    Some(other_origin) => other_origin,
+                    None => continue,
+                };
+
+                if alternatives(id, other) && origin.precedence() != other_origin.precedence() {
+                    continue;
+                }
+
+                return Err(CargoMSRVError::ConflictingOptions {
+                    option: origin.describe(id),
+                    other: other_origin.describe(other),
+                });
+            }
+        }
+
+        for (id, required) in REQUIREMENTS {
+            if let (Some(origin), None) = (self.given(id), self.given(required)) {
+                return Err(CargoMSRVError::MissingRequiredOption {
+                    option: origin.describe(id),
+                    required: format!("'--{}'", long_flag(required)),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // The value of an option which isn't given on the command line
+    fn setting(&self, id: &str) -> Option<(&FileValue, Origin<'_>)> {
+        if let Some((name, value)) = self.env.get(id) {
+            return Some((value, Origin::Environment { name }));
+        }
+
+        let file = self.file.as_ref()?;
+        file.get(id).map(|(path, key, value)| {
+            let origin = Origin::File { path, key };
+
+            (value, origin)
+        })
+    }
+}
+
+/// The value, or values, of an option.
+struct OptionValue<'m, 'o> {
+    values: Vec<Cow<'m, str>>,
+    origin: Origin<'o>,
+}
+
+impl OptionValue<'_, '_> {
+    fn parse<T, F: FnOnce(&str) -> TResult<T>>(&self, parse: F) -> TResult<T> {
+        parse(&self.values[0]).map_err(|err| self.origin.invalid(err))
+    }
+
+    fn parse_each<T, F: Fn(&str) -> TResult<T>>(&self, parse: F) -> TResult<Vec<T>> {
+        self.values
+            .iter()
+            .map(|value| parse(value).map_err(|err| self.origin.invalid(err)))
+            .collect()
+    }
+
+    // Runs the validator of the option on the values which clap hasn't validated
+    fn validate(&self, validator: fn(String) -> Result<(), String>) -> TResult<()> {
+        if let Origin::CommandLine = self.origin {
+            return Ok(());
+        }
+
+        for value in &self.values {
+            validator(value.to_string())
+                .map_err(|reason| self.origin.invalid(CargoMSRVError::GenericMessage(reason)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the value of an option is given.
+#[derive(Clone, Copy)]
+enum Origin<'o> {
+    CommandLine,
+    Environment { name: &'o str },
+    File { path: &'o Path, key: &'static str },
+}
+
+impl Origin<'_> {
+    // Options given on the command line take precedence over those in environment variables,
+    // which take precedence over those in the configuration file
+    fn precedence(self) -> u8 {
+        match self {
+            Origin::CommandLine => 2,
+            Origin::Environment { .. } => 1,
+            Origin::File { .. } => 0,
+        }
+    }
+
+    // Names an option given here, e.g. `'--nightly' (given in CARGO_MSRV_NIGHTLY)`
+    fn describe(self, id: &str) -> String {
+        let flag = format!("'--{}'", long_flag(id));
+
+        match self {
+            Origin::CommandLine => flag,
+            Origin::Environment { name } => format!("{} (given in {})", flag, name),
+            Origin::File { path, key } => {
+                format!("{} (given as '{}' in '{}')", flag, key, path.display())
+            }
+        }
+    }
+
+    // Errors of values given outside of the command line name the variable, or the file and
+    // the option
+    fn invalid(self, err: CargoMSRVError) -> CargoMSRVError {
+        match self {
+            Origin::CommandLine => err,
+            Origin::Environment { name } => CargoMSRVError::InvalidEnvOption {
+                name: name.to_string(),
+                reason: err.to_string(),
+            },
+            Origin::File { path, key } => CargoMSRVError::InvalidConfigFile {
+                path: path.to_path_buf(),
+                reason: format!("invalid value for '{}': {}", key, err),
+            },
+        }
+    }
+}
+
+/// Inserts arguments for cargo into a check command, before the arguments which cargo passes on
+/// to other tools (i.e. those after `--`).
+pub(crate) fn insert_cargo_args(command: &mut Vec<String>, args: Vec<String>) {
+    let position = command
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(command.len());
+
+    command.splice(position..position, args);
+}
+
+/// Removes the arguments which select every member of a workspace, e.g. `--all` in the default
+/// check command.
+fn remove_workspace_args(command: &mut Vec<String>) {
+    let end = command
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(command.len());
+
+    let mut index = 0;
+    command.retain(|arg| {
+        index += 1;
+        index > end || (arg != "--all" && arg != "--workspace")
+    });
+}
+
+// The long flag of an option, which is also its key in the configuration file
+fn long_flag(id: &str) -> &str {
+    OPTIONS
+        .iter()
+        .chain(ENV_OPTIONS.iter())
+        .find(|(_, option, _)| *option == id)
+        .map_or(id, |(key, _, _)| *key)
+}
+
+fn parse_nightly(date: &str) -> TResult<semver::Version> {
+    NightlyDate::parse(date).map(|date| date.to_version())
+}
+
+pub(crate) fn parse_jobs(input: &str) -> TResult<usize> {
+    match input.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(CargoMSRVError::InvalidJobs(input.to_string())),
+    }
+}
+
+pub(crate) fn parse_retries(input: &str) -> TResult<u32> {
+    input
+        .parse::<u32>()
+        .map_err(|_| CargoMSRVError::InvalidRetries(input.to_string()))
+}
+
+/// Parses a duration such as `90`, `90s`, `5m` or `1h`; without a unit, it's in seconds.
+pub(crate) fn parse_duration(input: &str) -> TResult<Duration> {
+    let (number, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => input.split_at(index),
+        None => (input, "s"),
+    };
+
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return Err(CargoMSRVError::InvalidDuration(input.to_string())),
+    };
+
+    match number
+        .parse::<u64>()
+        .ok()
+        .filter(|number| *number > 0)
+        .and_then(|number| number.checked_mul(seconds_per_unit))
+    {
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+        None => Err(CargoMSRVError::InvalidDuration(input.to_string())),
+    }
+}
+
 fn parse_version(input: &str) -> Result<semver::Version, semver::Error> {
     match input {
         "2015" => Ok(semver::Version::new(1, 0, 0)),
@@ -357,8 +1245,15 @@ fn parse_version(input: &str) -> Result<semver::Version, semver::Error> {
 
 #[cfg(test)]
 mod tests {
+    use super::{Config, ConfigBuilder, ModeIntent, ReleaseSource, SearchMethod};
+    use crate::config_env::ConfigEnv;
+    use crate::errors::CargoMSRVError;
+    use crate::manifest::BareVersion;
+    use clap::ArgMatches;
     use parameterized::parameterized;
     use rust_releases::semver::Version;
+    use std::convert::TryFrom;
+    use std::ffi::OsString;
 
     #[parameterized(
         input = {
@@ -378,4 +1273,247 @@ mod tests {
         let version = super::super::parse_version(input).unwrap();
         assert_eq!(version, expected_version)
     }
+
+    #[yare::parameterized(
+        one = { "1", Some(1) },
+        sixteen = { "16", Some(16) },
+        zero = { "0", None },
+        negative = { "-1", None },
+        not_a_number = { "x", None },
+    )]
+    fn parse_jobs(input: &str, expected: Option<usize>) {
+        let jobs = super::super::parse_jobs(input).ok();
+        assert_eq!(jobs, expected)
+    }
+
+    #[yare::parameterized(
+        seconds = { "90", Some(90) },
+        seconds_unit = { "90s", Some(90) },
+        minutes = { "5m", Some(300) },
+        hours = { "1h", Some(3600) },
+        zero = { "0s", None },
+        unknown_unit = { "5d", None },
+        no_number = { "m", None },
+        fraction = { "1.5m", None },
+        overflow = { "999999999999999999h", None },
+    )]
+    fn parse_duration(input: &str, expected: Option<u64>) {
+        let duration = super::super::parse_duration(input).ok();
+        assert_eq!(duration.map(|duration| duration.as_secs()), expected)
+    }
+
+    #[yare::parameterized(
+        rust_changelog = { "rust-changelog", Some(ReleaseSource::RustChangelog) },
+        rust_dist = { "rust-dist", Some(ReleaseSource::RustDist) },
+        file = { "file:releases.json", Some(ReleaseSource::File("releases.json".into())) },
+        installed = { "installed", Some(ReleaseSource::Installed) },
+        no_path = { "file:", None },
+        unknown = { "github", None },
+    )]
+    fn parse_release_source(input: &str, expected: Option<ReleaseSource>) {
+        use std::convert::TryFrom;
+
+        assert_eq!(ReleaseSource::try_from(input).ok(), expected)
+    }
+
+    #[yare::parameterized(
+        append = { vec!["cargo", "check"], vec!["cargo", "check", "--features", "a"] },
+        before_separator = { vec!["cargo", "test", "--", "--nocapture"], vec!["cargo", "test", "--features", "a", "--", "--nocapture"] },
+    )]
+    fn insert_cargo_args(command: Vec<&str>, expected: Vec<&str>) {
+        let mut command = command.into_iter().map(String::from).collect();
+        let args = vec!["--features".to_string(), "a".to_string()];
+
+        super::super::insert_cargo_args(&mut command, args);
+        assert_eq!(command, expected)
+    }
+
+    #[yare::parameterized(
+        all = { vec!["cargo", "check", "--all"], vec!["cargo", "check", "-p", "member"] },
+        workspace = { vec!["cargo", "test", "--workspace", "--", "--all"], vec!["cargo", "test", "-p", "member", "--", "--all"] },
+    )]
+    fn check_command_for_package(command: Vec<&str>, expected: Vec<&str>) {
+        let config = ConfigBuilder::new(ModeIntent::DetermineMSRV, "x86_64-unknown-linux-gnu")
+            .check_command(command)
+            .package("member".to_string())
+            .build();
+
+        assert_eq!(config.effective_check_command(), expected)
+    }
+
+    #[test]
+    fn generate_lockfile_rust_version() {
+        let matches = crate::cli::cli().get_matches_from(vec![
+            "cargo-msrv",
+            "--generate-lockfile",
+            "1.56",
+            "--path",
+            env!("CARGO_MANIFEST_DIR"),
+        ]);
+        let config = Config::try_from(&matches).unwrap();
+
+        assert_eq!(
+            config.lockfile_rust_version(),
+            Some(&BareVersion::TwoComponents(1, 56))
+        );
+    }
+
+    #[test]
+    fn check_command_for_cross_target() {
+        let config = ConfigBuilder::new(ModeIntent::DetermineMSRV, "x86_64-unknown-linux-gnu")
+            .check_command(vec!["cargo", "test", "--", "--nocapture"])
+            .cross_target("wasm32-unknown-unknown".to_string())
+            .build();
+
+        assert_eq!(
+            config.effective_check_command(),
+            vec![
+                "cargo",
+                "test",
+                "--target",
+                "wasm32-unknown-unknown",
+                "--",
+                "--nocapture"
+            ]
+        )
+    }
+
+    fn matches(args: &[&str]) -> ArgMatches<'static> {
+        crate::cli::cli().get_matches_from(["cargo-msrv"].iter().chain(args))
+    }
+
+    fn env(vars: &[(&str, &str)]) -> ConfigEnv {
+        ConfigEnv::from_vars(
+            vars.iter()
+                .map(|(name, value)| (OsString::from(name), OsString::from(value))),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn required_option_in_env() {
+        let path = env!("CARGO_MANIFEST_DIR");
+        let matches = matches(&["--strict", "--path", path]);
+
+        let config =
+            Config::from_matches_and_env(&matches, env(&[("CARGO_MSRV_VERIFY", "true")])).unwrap();
+        assert!(matches!(config.action_intent(), ModeIntent::VerifyMSRV));
+        assert!(config.strict());
+
+        let missing = Config::from_matches_and_env(&matches, env(&[]));
+        assert!(matches!(
+            missing,
+            Err(CargoMSRVError::MissingRequiredOption { .. })
+        ));
+    }
+
+    #[test]
+    fn conflicting_options_in_env() {
+        let matches = matches(&["--path", env!("CARGO_MANIFEST_DIR")]);
+        let vars = env(&[
+            ("CARGO_MSRV_NIGHTLY", "true"),
+            ("CARGO_MSRV_VERIFY", "true"),
+        ]);
+
+        match Config::from_matches_and_env(&matches, vars) {
+            Err(CargoMSRVError::ConflictingOptions { option, other }) => {
+                assert!(option.contains("CARGO_MSRV_NIGHTLY"), "{}", option);
+                assert!(other.contains("CARGO_MSRV_VERIFY"), "{}", other);
+            }
+            _ => panic!("expected the options to conflict"),
+        }
+    }
+
+    #[test]
+    fn nightly_msrv_is_not_written() {
+        let args = ["cargo-msrv", "--nightly", "--write-msrv"];
+        assert!(crate::cli::cli()
+            .get_matches_from_safe(args.iter())
+            .is_err());
+
+        let matches = matches(&["--write-msrv", "--path", env!("CARGO_MANIFEST_DIR")]);
+        let vars = env(&[("CARGO_MSRV_NIGHTLY", "true")]);
+
+        assert!(matches!(
+            Config::from_matches_and_env(&matches, vars),
+            Err(CargoMSRVError::ConflictingOptions { .. })
+        ));
+    }
+
+    #[test]
+    fn alternatives_in_env_and_on_command_line() {
+        let matches = matches(&["--galloping", "--path", env!("CARGO_MANIFEST_DIR")]);
+        let vars = env(&[("CARGO_MSRV_BISECT", "true")]);
+
+        // the command line takes precedence
+        let config = Config::from_matches_and_env(&matches, vars).unwrap();
+        assert!(matches!(
+            config.search_method(),
+            SearchMethod::Galloping { .. }
+        ));
+    }
+
+    #[yare::parameterized(
+        default = { &[], SearchMethod::Bisect },
+        galloping = { &["--galloping"], SearchMethod::Galloping { seed: None } },
+    )]
+    fn search_method_of_nightly_mode(args: &[&str], expected: SearchMethod) {
+        let mut args = args.to_vec();
+        args.extend_from_slice(&["--nightly", "--path", env!("CARGO_MANIFEST_DIR")]);
+
+        let matches = matches(&args);
+        let config = Config::from_matches_and_env(&matches, env(&[])).unwrap();
+        assert_eq!(config.search_method(), &expected);
+    }
+
+    #[yare::parameterized(
+        default = { &[], true },
+        installed = { &["--release-source", "installed"], false },
+    )]
+    fn installs_toolchains(args: &[&str], expected: bool) {
+        let mut args = args.to_vec();
+        args.extend_from_slice(&["--path", env!("CARGO_MANIFEST_DIR")]);
+
+        let matches = matches(&args);
+        let config = Config::from_matches_and_env(&matches, env(&[])).unwrap();
+        assert_eq!(config.installs_toolchains(), expected);
+    }
+
+    #[test]
+    fn conflicting_option_in_file() {
+        let root = std::env::temp_dir().join(format!("cargo-msrv-conflict-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[package.metadata.cargo-msrv]\nnightly = true\n",
+        )
+        .unwrap();
+
+        let matches = matches(&["--verify", "--path", root.to_str().unwrap()]);
+        let config = Config::from_matches_and_env(&matches, env(&[]));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        match config {
+            Err(CargoMSRVError::ConflictingOptions { option, other }) => {
+                assert!(option.contains("'nightly'"), "{}", option);
+                assert_eq!(other, "'--verify'");
+            }
+            _ => panic!("expected the options to conflict"),
+        }
+    }
+
+    #[yare::parameterized(
+        target = { "CARGO_MSRV_TARGET", "cargo-msrv-unknown-target" },
+        path = { "CARGO_MSRV_PATH", "/cargo-msrv/no/such/folder" },
+    )]
+    fn invalid_value_in_env(name: &str, value: &str) {
+        let matches = matches(&[]);
+
+        match Config::from_matches_and_env(&matches, env(&[(name, value)])) {
+            Err(CargoMSRVError::InvalidEnvOption { name: invalid, .. }) => {
+                assert_eq!(invalid, name)
+            }
+            _ => panic!("expected the value of {} to be rejected", name),
+        }
+    }
 }
@@ -0,0 +1,164 @@
+//! Options of cargo-msrv, given in environment variables, e.g. in a CI container.
+//!
+//! Each option can be given in a variable named after its long flag on the command line, prefixed
+//! with `CARGO_MSRV_`, e.g. `CARGO_MSRV_RELEASE_SOURCE=rust-dist` for `--release-source rust-dist`,
+//! and `CARGO_MSRV_CHECK_COMMAND` for the check command. Flags are set with `true` or `1`, and unset
+//! with `false` or `0`. Options which take several values, such as `CARGO_MSRV_TARGET`, take a list
+//! separated by `;`, except for the check command, which is split on whitespace. Empty variables
+//! are ignored.
+//!
+//! Options given on the command line take precedence over those given in environment variables,
+//! which take precedence over those in the configuration file.
+
+use crate::cli::id;
+use crate::config_file::{FileValue, Kind, OPTIONS};
+use crate::errors::{CargoMSRVError, TResult};
+use std::ffi::OsString;
+
+/// The prefix of the names of the variables.
+pub const PREFIX: &str = "CARGO_MSRV_";
+
+/// The options which can be given in an environment variable, but not in a configuration file.
+pub(crate) const ENV_OPTIONS: &[(&str, &str, Kind)] = &[
+    ("path", id::ARG_SEEK_PATH, Kind::Value),
+    ("verify", id::ARG_VERIFY, Kind::Flag),
+    ("generate-lockfile", id::ARG_GENERATE_LOCKFILE, Kind::Value),
+];
+
+/// The options given in environment variables.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigEnv {
+    // the name of the variable, flag id and value of each given option
+    options: Vec<(String, &'static str, FileValue)>,
+}
+
+impl ConfigEnv {
+    /// Reads the options given in the environment of the process.
+    pub fn from_env() -> TResult<Self> {
+        Self::from_vars(std::env::vars_os())
+    }
+
+    /// Reads the options given in the variables with the `CARGO_MSRV_` prefix.
+    pub fn from_vars<I: IntoIterator<Item = (OsString, OsString)>>(vars: I) -> TResult<Self> {
+        let mut options = Vec::new();
+
+        for (name, value) in vars {
+            let name = match name.into_string() {
+                Ok(name) if name.starts_with(PREFIX) => name,
+                _ => continue,
+            };
+
+            let key = name[PREFIX.len()..].to_ascii_lowercase().replace('_', "-");
+            let (_, id, kind) = OPTIONS
+                .iter()
+                .chain(ENV_OPTIONS.iter())
+                .find(|(option, _, _)| *option == key)
+                .ok_or_else(|| CargoMSRVError::UnknownEnvOption(name.clone()))?;
+
+            let invalid = |reason: String| CargoMSRVError::InvalidEnvOption {
+                name: name.clone(),
+                reason,
+            };
+
+            let value = value
+                .into_string()
+                .map_err(|_| invalid("the value is not valid unicode".to_string()))?;
+
+            if value.trim().is_empty() {
+                continue;
+            }
+
+            let value = env_value(&key, *kind, &value).map_err(invalid)?;
+            options.push((name, *id, value));
+        }
+
+        Ok(Self { options })
+    }
+
+    /// The value of the option with the given flag id, and the name of its variable, if it's given.
+    pub fn get(&self, id: &str) -> Option<(&str, &FileValue)> {
+        self.options
+            .iter()
+            .find(|(_, option, _)| *option == id)
+            .map(|(name, _, value)| (name.as_str(), value))
+    }
+}
+
+fn env_value(key: &str, kind: Kind, value: &str) -> Result<FileValue, String> {
+    let value = value.trim();
+
+    let values = match kind {
+        Kind::Flag => {
+            return match value {
+                "true" | "1" => Ok(FileValue::Flag(true)),
+                "false" | "0" => Ok(FileValue::Flag(false)),
+                _ => Err(format!("expected true or false, but found '{}'", value)),
+            }
+        }
+        Kind::Value => vec![value.to_string()],
+        Kind::Values if key == "check-command" => {
+            value.split_whitespace().map(String::from).collect()
+        }
+        Kind::Values => value
+            .split(';')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect(),
+    };
+
+    Ok(FileValue::Values(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_env(vars: &[(&str, &str)]) -> TResult<ConfigEnv> {
+        ConfigEnv::from_vars(
+            vars.iter()
+                .map(|(name, value)| (OsString::from(name), OsString::from(value))),
+        )
+    }
+
+    fn values(values: &[&str]) -> FileValue {
+        FileValue::Values(values.iter().map(|value| value.to_string()).collect())
+    }
+
+    #[yare::parameterized(
+        flag = { "CARGO_MSRV_BISECT", "true", id::ARG_BISECT, FileValue::Flag(true) },
+        flag_number = { "CARGO_MSRV_NO_CACHE", "1", id::ARG_NO_CACHE, FileValue::Flag(true) },
+        unset_flag = { "CARGO_MSRV_STRICT", "false", id::ARG_STRICT, FileValue::Flag(false) },
+        value = { "CARGO_MSRV_MIN", "1.45", id::ARG_MIN, values(&["1.45"]) },
+        release_source = { "CARGO_MSRV_RELEASE_SOURCE", "file:RELEASES.md", id::ARG_RELEASE_SOURCE, values(&["file:RELEASES.md"]) },
+        path = { "CARGO_MSRV_PATH", "/crate", id::ARG_SEEK_PATH, values(&["/crate"]) },
+        verify = { "CARGO_MSRV_VERIFY", "true", id::ARG_VERIFY, FileValue::Flag(true) },
+        generate_lockfile = { "CARGO_MSRV_GENERATE_LOCKFILE", "1.50", id::ARG_GENERATE_LOCKFILE, values(&["1.50"]) },
+        list = { "CARGO_MSRV_TARGET", "x86_64-unknown-linux-gnu; wasm32-unknown-unknown", id::ARG_SEEK_CUSTOM_TARGET, values(&["x86_64-unknown-linux-gnu", "wasm32-unknown-unknown"]) },
+        feature_sets = { "CARGO_MSRV_FEATURE_SET", "std, serde;alloc", id::ARG_FEATURE_SET, values(&["std, serde", "alloc"]) },
+        env = { "CARGO_MSRV_ENV", "RUSTFLAGS=-C opt-level=1;[<1.50]CARGO_NET_OFFLINE=true", id::ARG_ENV, values(&["RUSTFLAGS=-C opt-level=1", "[<1.50]CARGO_NET_OFFLINE=true"]) },
+        check_command = { "CARGO_MSRV_CHECK_COMMAND", "cargo test  --all-features", id::ARG_CUSTOM_CHECK, values(&["cargo", "test", "--all-features"]) },
+    )]
+    fn option(name: &str, value: &str, id: &str, expected: FileValue) {
+        let env = config_env(&[(name, value)]).unwrap();
+
+        assert_eq!(env.get(id), Some((name, &expected)));
+    }
+
+    #[test]
+    fn other_variables_are_ignored() {
+        let env = config_env(&[("CARGO_HOME", "/cargo"), ("CARGO_MSRV_MIN", "")]).unwrap();
+
+        assert!(env.options.is_empty());
+    }
+
+    #[yare::parameterized(
+        unknown = { "CARGO_MSRV_BISEKT", "true", "CARGO_MSRV_BISEKT" },
+        flag_with_value = { "CARGO_MSRV_BISECT", "yes", "CARGO_MSRV_BISECT" },
+    )]
+    fn invalid_option(name: &str, value: &str, expected: &str) {
+        let message = config_env(&[(name, value)]).unwrap_err().to_string();
+
+        assert!(message.contains(expected), "{}", message);
+    }
+}
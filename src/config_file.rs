@@ -0,0 +1,403 @@
+//! Defaults for the options of cargo-msrv, given in a configuration file, so they don't have to be
+//! repeated on each run.
+//!
+//! The configuration is read from a `cargo-msrv.toml` file in the root of the crate, if it exists,
+//! and otherwise from the `[package.metadata.cargo-msrv]` table of its `Cargo.toml`, and the
+//! `[workspace.metadata.cargo-msrv]` table of the `Cargo.toml` of its workspace root, of which the
+//! former takes precedence. Options are named after their long flag on the command line, e.g.:
+//!
+//! ```toml
+//! [package.metadata.cargo-msrv]
+//! bisect = true
+//! ignore-lockfile = true
+//! min = "1.45"
+//! check-command = "cargo test --all-features"
+//! env = { RUSTFLAGS = "-Dwarnings" }
+//! ```
+//!
+//! Options given on the command line take precedence over those in the configuration file.
+
+use crate::cli::id;
+use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::TomlMap;
+use crate::workspace::workspace_root;
+use decent_toml_rs_alternative::TomlValue;
+use std::path::{Path, PathBuf};
+
+/// The name of the standalone configuration file.
+pub const CONFIG_FILE: &str = "cargo-msrv.toml";
+
+/// The name of the table in the `package.metadata` or `workspace.metadata` of `Cargo.toml`.
+const METADATA_TABLE: &str = "cargo-msrv";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Kind {
+    // set with `true` or `false`
+    Flag,
+    // a single value
+    Value,
+    // a list of values, or a single value
+    Values,
+}
+
+/// The options which can be given in a configuration file: their key, which is their long flag on
+/// the command line, the id of the flag, and the kind of value they take. The path to the crate and
+/// the mode (`--verify` or `--generate-lockfile`) can't be given in a configuration file.
+pub(crate) const OPTIONS: &[(&str, &str, Kind)] = &[
+    ("check-command", id::ARG_CUSTOM_CHECK, Kind::Values),
+    ("target", id::ARG_SEEK_CUSTOM_TARGET, Kind::Values),
+    (
+        "include-all-patch-releases",
+        id::ARG_INCLUDE_ALL_PATCH_RELEASES,
+        Kind::Flag,
+    ),
+    ("min", id::ARG_MIN, Kind::Value),
+    ("max", id::ARG_MAX, Kind::Value),
+    ("bisect", id::ARG_BISECT, Kind::Flag),
+    ("galloping", id::ARG_GALLOPING, Kind::Flag),
+    ("toolchain-file", id::ARG_TOOLCHAIN_FILE, Kind::Flag),
+    ("write-msrv", id::ARG_WRITE_MSRV, Kind::Flag),
+    ("ignore-lockfile", id::ARG_IGNORE_LOCKFILE, Kind::Flag),
+    ("translate-lockfile", id::ARG_TRANSLATE_LOCKFILE, Kind::Flag),
+    ("minimal-versions", id::ARG_MINIMAL_VERSIONS, Kind::Flag),
+    (
+        "direct-minimal-versions",
+        id::ARG_DIRECT_MINIMAL_VERSIONS,
+        Kind::Flag,
+    ),
+    ("output-format", id::ARG_OUTPUT_FORMAT, Kind::Value),
+    ("strict", id::ARG_STRICT, Kind::Flag),
+    (
+        "blocking-dependencies",
+        id::ARG_BLOCKING_DEPENDENCIES,
+        Kind::Flag,
+    ),
+    ("release-source", id::ARG_RELEASE_SOURCE, Kind::Value),
+    ("no-log", id::ARG_NO_LOG, Kind::Flag),
+    (
+        "no-read-min-edition",
+        id::ARG_NO_READ_MIN_EDITION,
+        Kind::Flag,
+    ),
+    ("no-cache", id::ARG_NO_CACHE, Kind::Flag),
+    ("jobs", id::ARG_JOBS, Kind::Value),
+    ("clean-target-dirs", id::ARG_CLEAN_TARGET_DIRS, Kind::Flag),
+    ("check-timeout", id::ARG_CHECK_TIMEOUT, Kind::Value),
+    ("on-check-timeout", id::ARG_ON_CHECK_TIMEOUT, Kind::Value),
+    ("retries", id::ARG_RETRIES, Kind::Value),
+    ("env", id::ARG_ENV, Kind::Values),
+    ("env-file", id::ARG_ENV_FILE, Kind::Value),
+    ("toolchain-dir", id::ARG_TOOLCHAIN_DIR, Kind::Value),
+    ("nightly", id::ARG_NIGHTLY, Kind::Flag),
+    ("workspace", id::ARG_WORKSPACE, Kind::Flag),
+    ("feature-set", id::ARG_FEATURE_SET, Kind::Values),
+    ("each-feature", id::ARG_EACH_FEATURE, Kind::Flag),
+    ("feature-powerset", id::ARG_FEATURE_POWERSET, Kind::Flag),
+];
+
+/// The value of an option given in a configuration file, or in an environment variable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FileValue {
+    Flag(bool),
+    Values(Vec<String>),
+}
+
+/// The options given in a configuration file, or in the configuration tables of the manifests of
+/// a crate and its workspace root.
+#[derive(Clone, Debug)]
+pub struct ConfigFile {
+    // the file, key, flag id and value of each given option
+    options: Vec<(PathBuf, &'static str, &'static str, FileValue)>,
+}
+
+impl ConfigFile {
+    /// Finds the configuration of the crate in the given folder, if there is any.
+    pub fn find(crate_root: &Path) -> TResult<Option<Self>> {
+        let standalone = crate_root.join(CONFIG_FILE);
+
+        if standalone.is_file() {
+            let document = read_toml(&standalone)?;
+            return Self::from_tables(&[(&standalone, Some(&document))]).map(Some);
+        }
+
+        // a manifest which can't be read is reported by cargo, once the check runs
+        let manifest = crate_root.join("Cargo.toml");
+        let document = match parse_manifest(&manifest) {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+
+        // the workspace table of a workspace member is found in the manifest of the workspace root
+        let root = workspace_root(crate_root, &document)
+            .ok()
+            .flatten()
+            .filter(|root| *root != manifest)
+            .and_then(|root| parse_manifest(&root).map(|document| (root, document)));
+
+        let mut tables = Vec::new();
+
+        if let Some((root, root_document)) = &root {
+            tables.push((root.as_path(), metadata_table(root_document, "workspace")));
+        }
+
+        tables.push((&manifest, metadata_table(&document, "workspace")));
+        tables.push((&manifest, metadata_table(&document, "package")));
+
+        if tables.iter().all(|(_, table)| table.is_none()) {
+            return Ok(None);
+        }
+
+        Self::from_tables(&tables).map(Some)
+    }
+
+    /// The value of the option with the given flag id, the file in which it's given, and its key
+    /// in the file, if it's given.
+    pub fn get(&self, id: &str) -> Option<(&Path, &'static str, &FileValue)> {
+        self.options
+            .iter()
+            .find(|(_, _, option, _)| *option == id)
+            .map(|(path, key, _, value)| (path.as_path(), *key, value))
+    }
+
+    // The options of later tables take precedence over those of earlier tables
+    fn from_tables(tables: &[(&Path, Option<&TomlMap>)]) -> TResult<Self> {
+        let mut options: Vec<(PathBuf, &'static str, &'static str, FileValue)> = Vec::new();
+
+        for (path, table) in tables {
+            let table = match table {
+                Some(table) => table,
+                None => continue,
+            };
+
+            let invalid = |reason: String| CargoMSRVError::InvalidConfigFile {
+                path: path.to_path_buf(),
+                reason,
+            };
+
+            let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+            // sorted, so the first unknown or invalid option is always the same one
+            let mut keys = table.keys().collect::<Vec<_>>();
+            keys.sort();
+
+            for key in keys {
+                let (key, id, kind) = OPTIONS
+                    .iter()
+                    .find(|(option, _, _)| option == key)
+                    .ok_or_else(|| invalid(format!("unknown option '{}'", key)))?;
+
+                let value = file_value(key, *kind, &table[*key], base).map_err(|reason| {
+                    invalid(format!("invalid value for '{}': {}", key, reason))
+                })?;
+
+                options.retain(|(_, _, option, _)| option != id);
+                options.push((path.to_path_buf(), key, id, value));
+            }
+        }
+
+        Ok(Self { options })
+    }
+}
+
+fn parse_manifest(path: &Path) -> Option<TomlMap> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| decent_toml_rs_alternative::parse_toml(&contents).ok())
+}
+
+fn read_toml(path: &Path) -> TResult<TomlMap> {
+    let invalid = |reason: String| CargoMSRVError::InvalidConfigFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let contents = std::fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+    decent_toml_rs_alternative::parse_toml(&contents).map_err(|err| invalid(format!("{:?}", err)))
+}
+
+fn metadata_table<'d>(document: &'d TomlMap, section: &str) -> Option<&'d TomlMap> {
+    match document
+        .get(section)?
+        .get("metadata")?
+        .get(METADATA_TABLE)?
+    {
+        TomlValue::Table(table) => Some(table),
+        _ => None,
+    }
+}
+
+fn file_value(key: &str, kind: Kind, value: &TomlValue, base: &Path) -> Result<FileValue, String> {
+    let values = match (kind, value) {
+        (Kind::Flag, TomlValue::Boolean(flag)) => return Ok(FileValue::Flag(*flag)),
+        (Kind::Flag, _) => return Err("expected true or false".to_string()),
+        // e.g. `check-command = "cargo test --all-features"`
+        (Kind::Values, TomlValue::String(command)) if key == "check-command" => command
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>(),
+        // e.g. `env = { RUSTFLAGS = "-Dwarnings", "[<1.50]CARGO_NET_OFFLINE" = "true" }`
+        (Kind::Values, TomlValue::Table(vars)) if key == "env" => {
+            let mut vars = vars
+                .iter()
+                .map(|(name, value)| scalar(value).map(|value| format!("{}={}", name, value)))
+                .collect::<Result<Vec<_>, _>>()?;
+            vars.sort();
+            vars
+        }
+        (Kind::Values, TomlValue::Array(values)) => {
+            values.iter().map(scalar).collect::<Result<Vec<_>, _>>()?
+        }
+        (_, value) => vec![scalar(value)?],
+    };
+
+    if values.is_empty() {
+        return Err("expected at least one value".to_string());
+    }
+
+    // relative paths are relative to the configuration file, rather than the working directory
+    let values = values
+        .into_iter()
+        .map(|value| match key {
+            "env-file" | "toolchain-dir" => relative_to(base, &value),
+            "release-source" => match value.strip_prefix(crate::config::RELEASE_SOURCE_FILE_PREFIX)
+            {
+                Some(path) if !path.is_empty() => format!(
+                    "{}{}",
+                    crate::config::RELEASE_SOURCE_FILE_PREFIX,
+                    relative_to(base, path)
+                ),
+                _ => value,
+            },
+            _ => value,
+        })
+        .collect();
+
+    Ok(FileValue::Values(values))
+}
+
+fn scalar(value: &TomlValue) -> Result<String, String> {
+    match value {
+        TomlValue::String(value) => Ok(value.clone()),
+        TomlValue::Integer(value) => Ok(value.to_string()),
+        // a float loses the formatting of a version, e.g. `1.40` is read as `1.4`
+        TomlValue::Float(value) => Err(format!(
+            "found the number '{}', quote versions as strings, e.g. \"1.40\"",
+            value
+        )),
+        other => Err(format!(
+            "expected a string or an integer, but found '{:?}'",
+            other
+        )),
+    }
+}
+
+fn relative_to(base: &Path, path: &str) -> String {
+    base.join(path).to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_file(contents: &str) -> TResult<ConfigFile> {
+        let document = decent_toml_rs_alternative::parse_toml(contents).unwrap();
+        ConfigFile::from_tables(&[(Path::new("/crate/cargo-msrv.toml"), Some(&document))])
+    }
+
+    fn values(values: &[&str]) -> FileValue {
+        FileValue::Values(values.iter().map(|value| value.to_string()).collect())
+    }
+
+    #[yare::parameterized(
+        flag = { "bisect = true", id::ARG_BISECT, FileValue::Flag(true) },
+        unset_flag = { "no-cache = false", id::ARG_NO_CACHE, FileValue::Flag(false) },
+        string = { "min = \"1.45\"", id::ARG_MIN, values(&["1.45"]) },
+        integer = { "jobs = 4", id::ARG_JOBS, values(&["4"]) },
+        list = { "target = [\"x86_64-unknown-linux-gnu\", \"wasm32-unknown-unknown\"]", id::ARG_SEEK_CUSTOM_TARGET, values(&["x86_64-unknown-linux-gnu", "wasm32-unknown-unknown"]) },
+        check_command = { "check-command = \"cargo test --all-features\"", id::ARG_CUSTOM_CHECK, values(&["cargo", "test", "--all-features"]) },
+        check_command_list = { "check-command = [\"cargo\", \"test\"]", id::ARG_CUSTOM_CHECK, values(&["cargo", "test"]) },
+        env_table = { "env = { RUSTFLAGS = \"-Dwarnings\", \"[<1.50]CARGO_NET_OFFLINE\" = \"true\" }", id::ARG_ENV, values(&["RUSTFLAGS=-Dwarnings", "[<1.50]CARGO_NET_OFFLINE=true"]) },
+        env_file = { "env-file = \"ci.env\"", id::ARG_ENV_FILE, values(&["/crate/ci.env"]) },
+        release_file = { "release-source = \"file:RELEASES.md\"", id::ARG_RELEASE_SOURCE, values(&["file:/crate/RELEASES.md"]) },
+        release_source = { "release-source = \"rust-dist\"", id::ARG_RELEASE_SOURCE, values(&["rust-dist"]) },
+    )]
+    fn option(contents: &str, id: &str, expected: FileValue) {
+        let file = config_file(contents).unwrap();
+
+        assert_eq!(file.get(id).map(|(_, _, value)| value), Some(&expected));
+    }
+
+    #[yare::parameterized(
+        unknown = { "bisekt = true", "unknown option 'bisekt'" },
+        flag_with_value = { "bisect = \"yes\"", "invalid value for 'bisect': expected true or false" },
+        empty_list = { "target = []", "invalid value for 'target': expected at least one value" },
+        mode = { "verify = true", "unknown option 'verify'" },
+        float = { "min = 1.40", "invalid value for 'min': found the number '1.4', quote versions as strings, e.g. \"1.40\"" },
+    )]
+    fn invalid_option(contents: &str, expected: &str) {
+        let message = config_file(contents).unwrap_err().to_string();
+
+        assert!(message.contains(expected), "{}", message);
+    }
+
+    #[test]
+    fn package_takes_precedence_over_workspace() {
+        let document = decent_toml_rs_alternative::parse_toml(
+            "[workspace.metadata.cargo-msrv]\nbisect = true\njobs = 2\n\n[package.metadata.cargo-msrv]\njobs = 4\n",
+        )
+        .unwrap();
+
+        let manifest = Path::new("/crate/Cargo.toml");
+        let file = ConfigFile::from_tables(&[
+            (manifest, metadata_table(&document, "workspace")),
+            (manifest, metadata_table(&document, "package")),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            file.get(id::ARG_BISECT),
+            Some((manifest, "bisect", &FileValue::Flag(true)))
+        );
+        assert_eq!(
+            file.get(id::ARG_JOBS),
+            Some((manifest, "jobs", &values(&["4"])))
+        );
+    }
+
+    #[test]
+    fn workspace_table_of_workspace_root() {
+        let root =
+            std::env::temp_dir().join(format!("cargo-msrv-config-ws-{}", std::process::id()));
+        let member = root.join("member");
+        std::fs::create_dir_all(&member).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"member\"]\n\n[workspace.metadata.cargo-msrv]\nbisect = true\njobs = 2\nenv-file = \"ci.env\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"member\"\nversion = \"0.1.0\"\n\n[package.metadata.cargo-msrv]\njobs = 4\n",
+        )
+        .unwrap();
+
+        let file = ConfigFile::find(&member);
+        std::fs::remove_dir_all(&root).unwrap();
+        let file = file.unwrap().unwrap();
+
+        let root_manifest = root.join("Cargo.toml");
+        assert_eq!(
+            file.get(id::ARG_BISECT),
+            Some((root_manifest.as_path(), "bisect", &FileValue::Flag(true)))
+        );
+        // relative paths are relative to the manifest of the workspace root
+        assert_eq!(
+            file.get(id::ARG_ENV_FILE).map(|(_, _, value)| value),
+            Some(&values(&[root.join("ci.env").to_str().unwrap()]))
+        );
+        assert_eq!(
+            file.get(id::ARG_JOBS),
+            Some((member.join("Cargo.toml").as_path(), "jobs", &values(&["4"])))
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! The dependencies of a crate, as resolved in its `Cargo.lock` lockfile, and the MSRVs they
+//! declare in their own `Cargo.toml` manifest.
+//!
+//! The manifest of a dependency is taken from the vendored sources of the crate (see
+//! `cargo vendor`), if there are any, and otherwise from cargo's registry cache. Nothing is
+//! downloaded, so the MSRV of a dependency which cargo hasn't downloaded yet is unknown.
+
+use crate::diagnostics::Diagnostics;
+use crate::errors::TResult;
+use crate::lockfile::CARGO_LOCK;
+use crate::manifest::{BareVersion, CargoManifest, TomlMap};
+use crate::read_manifest;
+use decent_toml_rs_alternative::TomlValue;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+/// A package in a `Cargo.lock` lockfile.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LockedPackage {
+    name: String,
+    version: String,
+    // absent for path dependencies, such as the members of the workspace
+    source: Option<String>,
+}
+
+impl LockedPackage {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Whether the package is a dependency from a registry or git repository, rather than a local
+    /// package such as a workspace member
+    pub fn is_remote(&self) -> bool {
+        self.source.is_some()
+    }
+}
+
+/// Reads the packages in a `Cargo.lock` lockfile.
+pub fn read_lockfile(path: &Path) -> TResult<Vec<LockedPackage>> {
+    read_manifest(path).map(|lockfile| locked_packages(&lockfile))
+}
+
+fn locked_packages(lockfile: &TomlMap) -> Vec<LockedPackage> {
+    let packages = match lockfile.get("package") {
+        Some(TomlValue::Array(packages)) => packages,
+        _ => return Vec::new(),
+    };
+
+    packages
+        .iter()
+        .filter_map(|package| {
+            Some(LockedPackage {
+                name: package.get("name")?.as_string()?,
+                version: package.get("version")?.as_string()?,
+                source: package.get("source").and_then(TomlValue::as_string),
+            })
+        })
+        .collect()
+}
+
+/// The lockfile of a crate. The lockfile of a workspace member is found in the root of the workspace.
+pub fn find_lockfile(crate_root: &Path) -> Option<PathBuf> {
+    crate_root
+        .ancestors()
+        .map(|dir| dir.join(CARGO_LOCK))
+        .find(|lockfile| lockfile.is_file())
+}
+
+/// The directory in which cargo keeps its caches, such as the downloaded packages.
+pub(crate) fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))
+}
+
+/// Finds the `Cargo.toml` manifests of the dependencies of a crate, first in the source
+/// directories of its cargo configuration, such as the `vendor` directory created by
+/// `cargo vendor`, and then in the registry cache of cargo.
+#[derive(Clone, Debug)]
+pub struct ManifestLookup {
+    vendored: Vec<PathBuf>,
+    cargo_home: Option<PathBuf>,
+}
+
+impl ManifestLookup {
+    pub fn new(crate_root: &Path) -> Self {
+        Self::with_cargo_home(crate_root, cargo_home())
+    }
+
+    /// Looks up the manifests with the configuration and registry cache found in the given
+    /// cargo home, instead of `$CARGO_HOME`
+    pub fn with_cargo_home(crate_root: &Path, cargo_home: Option<PathBuf>) -> Self {
+        Self {
+            vendored: vendor_directories(crate_root, cargo_home.as_deref()),
+            cargo_home,
+        }
+    }
+
+    pub fn manifest(&self, name: &str, version: &str) -> Option<PathBuf> {
+        vendored_manifest(&self.vendored, name, version).or_else(|| {
+            self.cargo_home
+                .as_deref()
+                .and_then(|home| registry_manifest(home, name, version))
+        })
+    }
+}
+
+/// The `Cargo.toml` manifest of a package downloaded from a registry, if cargo has unpacked it in
+/// its registry cache.
+pub fn registry_manifest(cargo_home: &Path, name: &str, version: &str) -> Option<PathBuf> {
+    // one directory per registry, e.g. `github.com-1ecc6299db9ec823`
+    std::fs::read_dir(cargo_home.join("registry").join("src"))
+        .ok()?
+        .filter_map(|registry| registry.ok())
+        .map(|registry| {
+            registry
+                .path()
+                .join(format!("{}-{}", name, version))
+                .join("Cargo.toml")
+        })
+        .find(|manifest| manifest.is_file())
+}
+
+/// The MSRV declared in a `Cargo.toml` manifest, if any.
+pub fn declared_rust_version(manifest: &Path) -> Option<BareVersion> {
+    CargoManifest::try_from(read_manifest(manifest).ok()?)
+        .ok()?
+        .minimum_rust_version()
+        .cloned()
+}
+
+/// The source directories listed in the `[source]` tables of the cargo configuration files of a
+/// crate, and of the given cargo home, such as the `vendor` directory created by `cargo vendor`.
+pub fn vendor_directories(crate_root: &Path, cargo_home: Option<&Path>) -> Vec<PathBuf> {
+    // a configuration file is found in `<dir>/.cargo/`, and its relative paths are relative to `<dir>`
+    let dirs = crate_root
+        .ancestors()
+        .map(Path::to_path_buf)
+        .chain(cargo_home.and_then(|home| home.parent().map(Path::to_path_buf)));
+
+    dirs.flat_map(|dir| {
+        ["config.toml", "config"]
+            .iter()
+            .map(|name| dir.join(".cargo").join(name))
+            .filter(|config| config.is_file())
+            .filter_map(|config| read_manifest(&config).ok())
+            .flat_map(|config| source_directories(&config))
+            .map(|directory| dir.join(directory))
+            .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+fn source_directories(config: &TomlMap) -> Vec<String> {
+    let sources = match config.get("source") {
+        Some(TomlValue::Table(sources)) => sources,
+        _ => return Vec::new(),
+    };
+
+    let mut directories = sources
+        .values()
+        .filter_map(|source| source.get("directory")?.as_string())
+        .collect::<Vec<_>>();
+    directories.sort();
+    directories
+}
+
+/// The `Cargo.toml` manifest of a vendored package, in one of the given source directories. The
+/// directory of a package is named after the package, and its version when several versions of
+/// the package are vendored.
+pub fn vendored_manifest(directories: &[PathBuf], name: &str, version: &str) -> Option<PathBuf> {
+    directories
+        .iter()
+        .flat_map(|directory| {
+            vec![
+                directory.join(format!("{}-{}", name, version)),
+                directory.join(name),
+            ]
+        })
+        .map(|package| package.join("Cargo.toml"))
+        .filter(|manifest| manifest.is_file())
+        .find(|manifest| {
+            read_manifest(manifest)
+                .ok()
+                .and_then(|manifest| manifest.get("package")?.get("version")?.as_string())
+                .as_deref()
+                == Some(version)
+        })
+}
+
+/// A dependency in the lockfile, and the MSRV it declares.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DependencyMsrv {
+    name: String,
+    version: String,
+    // absent when the dependency isn't vendored, and cargo hasn't downloaded it
+    manifest: Option<PathBuf>,
+    rust_version: Option<BareVersion>,
+}
+
+impl DependencyMsrv {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The manifest of the dependency, if it's vendored, or cargo has downloaded it
+    pub fn manifest(&self) -> Option<&Path> {
+        self.manifest.as_deref()
+    }
+
+    /// The MSRV declared by the dependency, if it declares one
+    pub fn rust_version(&self) -> Option<&BareVersion> {
+        self.rust_version.as_ref()
+    }
+}
+
+/// The MSRVs declared by the dependencies in a lockfile, from the highest to the lowest MSRV,
+/// followed by the dependencies which declare no MSRV, and those of which the manifest wasn't
+/// found. Local packages, such as the members of the workspace, are left out.
+pub fn dependency_msrvs(
+    lockfile: &Path,
+    manifests: &ManifestLookup,
+) -> TResult<Vec<DependencyMsrv>> {
+    let mut dependencies = read_lockfile(lockfile)?
+        .into_iter()
+        .filter(LockedPackage::is_remote)
+        .map(|package| {
+            let manifest = manifests.manifest(&package.name, &package.version);
+            let rust_version = manifest.as_deref().and_then(declared_rust_version);
+
+            DependencyMsrv {
+                name: package.name,
+                version: package.version,
+                manifest,
+                rust_version,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    dependencies.sort_by(|lhs, rhs| {
+        msrv_order(rhs)
+            .cmp(&msrv_order(lhs))
+            .then_with(|| lhs.name.cmp(&rhs.name))
+            .then_with(|| lhs.version.cmp(&rhs.version))
+    });
+    dependencies.dedup();
+
+    Ok(dependencies)
+}
+
+// Orders dependencies by their MSRV, with those which declare none, and then those of which the
+// manifest wasn't found, below any MSRV
+fn msrv_order(dependency: &DependencyMsrv) -> (Option<(u64, u64, u64)>, bool) {
+    let rust_version = dependency
+        .rust_version
+        .as_ref()
+        .map(|version| match version {
+            BareVersion::TwoComponents(major, minor) => (*major, *minor, 0),
+            BareVersion::ThreeComponents(major, minor, patch) => (*major, *minor, *patch),
+        });
+
+    (rust_version, dependency.manifest.is_some())
+}
+
+/// A dependency which failed to compile with the release below the MSRV.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockingDependency {
+    name: String,
+    version: Option<String>,
+    rust_version: Option<BareVersion>,
+}
+
+impl BlockingDependency {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// The MSRV declared by the dependency, if it declares one, and it's vendored, or cargo has
+    /// downloaded it
+    pub fn rust_version(&self) -> Option<&BareVersion> {
+        self.rust_version.as_ref()
+    }
+}
+
+/// The dependencies which failed to compile during a check, in the order in which they failed.
+///
+/// Packages which aren't remote dependencies in the lockfile, such as the workspace members, and
+/// the packages named in `local`, are not dependencies, and are left out.
+pub fn blocking_dependencies(
+    diagnostics: &Diagnostics,
+    lockfile: &[LockedPackage],
+    local: &[String],
+    manifests: &ManifestLookup,
+) -> Vec<BlockingDependency> {
+    diagnostics
+        .failed_packages()
+        .iter()
+        .filter(|failed| !local.iter().any(|name| name == failed.name()))
+        .filter_map(|failed| {
+            let locked = lockfile
+                .iter()
+                .filter(|package| package.name() == failed.name())
+                .collect::<Vec<_>>();
+
+            // local packages aren't dependencies
+            if !locked.is_empty() && !locked.iter().any(|package| package.is_remote()) {
+                return None;
+            }
+
+            let version = failed
+                .version()
+                .map(ToString::to_string)
+                .or_else(|| locked.first().map(|package| package.version.clone()));
+
+            let rust_version = version
+                .as_deref()
+                .and_then(|version| manifests.manifest(failed.name(), version))
+                .and_then(|manifest| declared_rust_version(&manifest));
+
+            Some(BlockingDependency {
+                name: failed.name().to_string(),
+                version,
+                rust_version,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{CargoManifestParser, TomlParser};
+
+    const LOCKFILE: &str = r#"# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.130"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "f12d06de37cf59146fbdecab66aa99f9fe4f78722e3607577a5375d66bd0c913"
+"#;
+
+    fn lockfile() -> Vec<LockedPackage> {
+        let lockfile = CargoManifestParser.parse::<TomlMap>(LOCKFILE).unwrap();
+
+        locked_packages(&lockfile)
+    }
+
+    #[test]
+    fn packages_in_lockfile() {
+        let packages = lockfile();
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name(), "app");
+        assert!(!packages[0].is_remote());
+        assert_eq!(packages[1].name(), "serde");
+        assert_eq!(packages[1].version(), "1.0.130");
+        assert!(packages[1].is_remote());
+    }
+
+    #[test]
+    fn msrvs_of_vendored_dependencies() {
+        let root = std::env::temp_dir().join(format!("cargo-msrv-vendored-{}", std::process::id()));
+        let manifest = |path: &str, contents: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+
+        manifest(
+            "Cargo.lock",
+            &format!(
+                "{}\n[[package]]\nname = \"cargo-msrv-test-missing\"\nversion = \"0.1.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n[[package]]\nname = \"cargo-msrv-test-old\"\nversion = \"0.2.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n[[package]]\nname = \"cargo-msrv-test-none\"\nversion = \"1.0.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                LOCKFILE
+            ),
+        );
+        manifest(
+            ".cargo/config.toml",
+            "[source.crates-io]\nreplace-with = \"vendored-sources\"\n\n[source.vendored-sources]\ndirectory = \"vendor\"\n",
+        );
+        manifest(
+            "vendor/serde/Cargo.toml",
+            "[package]\nname = \"serde\"\nversion = \"1.0.130\"\nrust-version = \"1.56\"\n",
+        );
+        manifest(
+            "vendor/cargo-msrv-test-old-0.2.0/Cargo.toml",
+            "[package]\nname = \"cargo-msrv-test-old\"\nversion = \"0.2.0\"\n\n[package.metadata]\nmsrv = \"1.40.0\"\n",
+        );
+        manifest(
+            "vendor/cargo-msrv-test-none/Cargo.toml",
+            "[package]\nname = \"cargo-msrv-test-none\"\nversion = \"1.0.0\"\n",
+        );
+
+        // an empty cargo home, so the configuration and registry cache of the user aren't read
+        let manifests = ManifestLookup::with_cargo_home(&root, Some(root.join("cargo-home")));
+        let dependencies = dependency_msrvs(&root.join("Cargo.lock"), &manifests);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let dependencies = dependencies
+            .unwrap()
+            .into_iter()
+            .map(|dependency| {
+                (
+                    dependency.name().to_string(),
+                    dependency.rust_version().map(ToString::to_string),
+                    dependency.manifest().is_some(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            dependencies,
+            vec![
+                ("serde".to_string(), Some("1.56".to_string()), true),
+                (
+                    "cargo-msrv-test-old".to_string(),
+                    Some("1.40.0".to_string()),
+                    true
+                ),
+                ("cargo-msrv-test-none".to_string(), None, true),
+                ("cargo-msrv-test-missing".to_string(), None, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn dependencies_which_failed() {
+        let diagnostics = Diagnostics::parse(
+            "",
+            "error: could not compile `serde`\nerror: could not compile `app`\nerror: could not compile `other`",
+        );
+
+        let root = std::env::temp_dir().join(format!("cargo-msrv-blocking-{}", std::process::id()));
+        let manifest = root.join("vendor").join("serde").join("Cargo.toml");
+        std::fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+        std::fs::write(
+            &manifest,
+            "[package]\nname = \"serde\"\nversion = \"1.0.130\"\nrust-version = \"1.56\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::write(
+            root.join(".cargo").join("config.toml"),
+            "[source.vendored-sources]\ndirectory = \"vendor\"\n",
+        )
+        .unwrap();
+
+        let manifests = ManifestLookup::with_cargo_home(&root, Some(root.join("cargo-home")));
+        let blocking = blocking_dependencies(
+            &diagnostics,
+            &lockfile(),
+            &["other".to_string()],
+            &manifests,
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(blocking.len(), 1);
+        assert_eq!(blocking[0].name(), "serde");
+        assert_eq!(blocking[0].version(), Some("1.0.130"));
+        assert_eq!(
+            blocking[0].rust_version(),
+            Some(&BareVersion::TwoComponents(1, 56))
+        );
+    }
+}
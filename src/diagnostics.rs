@@ -0,0 +1,505 @@
+//! The diagnostics emitted by a failed check, and what they tell about the Rust version a crate
+//! requires.
+//!
+//! Checks run cargo with `--message-format=json`, so the compiler errors can be read from its
+//! output. Some errors tell which Rust version is required at least, for example when an unstable
+//! feature is used which has been stabilized in a later release, or when a dependency requires a
+//! later `rustc`. The search uses this to skip the releases which can't pass the check.
+
+use crate::config::insert_cargo_args;
+use rust_releases::semver;
+
+/// Cargo supports `--message-format=json` since Rust 1.13.
+const MESSAGE_FORMAT_JSON_SINCE: semver::Version = semver::Version::new(1, 13, 0);
+
+/// The cargo subcommands which compile the crate, and accept `--message-format`.
+const COMPILING_SUBCOMMANDS: &[&str] = &["build", "check", "clippy", "doc", "rustc", "test"];
+
+/// The Rust versions in which editions became stable, by the name of the cargo feature which
+/// enabled them before.
+const EDITION_FEATURES: &[(&str, (u64, u64))] = &[
+    ("edition", (1, 31)),
+    ("edition2021", (1, 56)),
+    ("edition2024", (1, 85)),
+];
+
+/// The Rust versions in which features were stabilized, by the name of the feature gate.
+///
+/// This is not an exhaustive list. Features which are not listed don't help the search, but don't
+/// hurt it either.
+const STABILIZED_FEATURES: &[(&str, (u64, u64))] = &[
+    ("vecdeque_rotate", (1, 36)),
+    ("async_await", (1, 39)),
+    ("min_const_generics", (1, 51)),
+    ("unsigned_abs", (1, 51)),
+    ("or_patterns", (1, 53)),
+    ("array_map", (1, 55)),
+    ("shrink_to", (1, 56)),
+    ("format_args_capture", (1, 58)),
+    ("available_parallelism", (1, 59)),
+    ("const_generics_defaults", (1, 59)),
+    ("destructuring_assignment", (1, 59)),
+    ("const_fn_trait_bound", (1, 61)),
+    ("bool_to_option", (1, 62)),
+    ("total_cmp", (1, 62)),
+    ("array_from_fn", (1, 63)),
+    ("io_safety", (1, 63)),
+    ("scoped_threads", (1, 63)),
+    ("core_ffi_c", (1, 64)),
+    ("future_poll_fn", (1, 64)),
+    ("ready_macro", (1, 64)),
+    ("generic_associated_types", (1, 65)),
+    ("label_break_value", (1, 65)),
+    ("let_else", (1, 65)),
+    ("mixed_integer_ops", (1, 66)),
+    ("int_log", (1, 67)),
+    ("cstr_from_bytes_until_nul", (1, 69)),
+    ("is_some_and", (1, 70)),
+    ("is_terminal", (1, 70)),
+    ("once_cell", (1, 70)),
+    ("async_fn_in_trait", (1, 75)),
+    ("return_position_impl_trait_in_trait", (1, 75)),
+    ("c_str_literals", (1, 77)),
+    ("slice_group_by", (1, 77)),
+    ("associated_type_bounds", (1, 79)),
+    ("inline_const", (1, 79)),
+    ("div_duration", (1, 80)),
+    ("exclusive_range_pattern", (1, 80)),
+    ("lazy_cell", (1, 80)),
+    ("let_chains", (1, 88)),
+];
+
+/// A compiler error, emitted by a failed check.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Diagnostic {
+    message: String,
+    code: Option<String>,
+    file: Option<String>,
+    line: Option<u64>,
+    column: Option<u64>,
+}
+
+impl Diagnostic {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The error code, e.g. `E0658`
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
+    /// The file of the primary span of the error, relative to the package root
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn line(&self) -> Option<u64> {
+        self.line
+    }
+
+    pub fn column(&self) -> Option<u64> {
+        self.column
+    }
+}
+
+/// A package which failed to compile during a check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FailedPackage {
+    name: String,
+    version: Option<semver::Version>,
+}
+
+impl FailedPackage {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The version of the package, if the diagnostics tell
+    pub fn version(&self) -> Option<&semver::Version> {
+        self.version.as_ref()
+    }
+}
+
+/// The diagnostics of a check.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Diagnostics {
+    errors: Vec<Diagnostic>,
+    required_version: Option<semver::Version>,
+    failed_packages: Vec<FailedPackage>,
+}
+
+impl Diagnostics {
+    /// Parses the output of a check: the compiler messages which cargo prints to stdout in its
+    /// JSON format, and cargo's own errors, which it prints to stderr.
+    pub fn parse(stdout: &str, stderr: &str) -> Self {
+        let mut errors = Vec::new();
+        let mut required = Vec::new();
+        let mut failed_packages = Vec::new();
+
+        for message in stdout.lines().filter_map(|line| json::parse(line).ok()) {
+            if message["reason"].as_str() != Some("compiler-message")
+                || message["message"]["level"].as_str() != Some("error")
+            {
+                continue;
+            }
+
+            failed_packages.extend(message["package_id"].as_str().and_then(parse_package_id));
+
+            let message = &message["message"];
+            required.extend(feature_gates(message).filter_map(stabilized_in));
+
+            let primary_span = message["spans"]
+                .members()
+                .find(|span| span["is_primary"].as_bool() == Some(true));
+
+            errors.push(Diagnostic {
+                message: message["message"].as_str().unwrap_or_default().to_string(),
+                code: message["code"]["code"].as_str().map(String::from),
+                file: primary_span.and_then(|span| span["file_name"].as_str().map(String::from)),
+                line: primary_span.and_then(|span| span["line_start"].as_u64()),
+                column: primary_span.and_then(|span| span["column_start"].as_u64()),
+            });
+        }
+
+        for line in stderr.lines() {
+            required.extend(required_rustc(line));
+            required.extend(required_edition(line));
+            failed_packages.extend(failed_package(line));
+        }
+
+        // a package fails with many errors, but only its first failure tells when it failed
+        let mut seen = std::collections::HashSet::new();
+        failed_packages.retain(|package: &FailedPackage| seen.insert(package.name.clone()));
+
+        Self {
+            errors,
+            required_version: required.into_iter().max(),
+            failed_packages,
+        }
+    }
+
+    pub fn errors(&self) -> &[Diagnostic] {
+        &self.errors
+    }
+
+    /// The least recent Rust version with which the check could pass, according to the diagnostics,
+    /// if they tell.
+    pub fn required_version(&self) -> Option<&semver::Version> {
+        self.required_version.as_ref()
+    }
+
+    /// Whether nothing is known about the check, e.g. because it passed.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.failed_packages.is_empty()
+    }
+
+    /// The packages which failed to compile, in the order in which they failed.
+    pub fn failed_packages(&self) -> &[FailedPackage] {
+        &self.failed_packages
+    }
+
+    /// Writes the diagnostics as JSON, so they can be stored with the cached outcome of a check.
+    pub(crate) fn to_json(&self) -> json::JsonValue {
+        let errors = self
+            .errors
+            .iter()
+            .map(|error| {
+                json::object! {
+                    message: error.message.as_str(),
+                    code: error.code.as_deref(),
+                    file: error.file.as_deref(),
+                    line: error.line,
+                    column: error.column,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let failed_packages = self
+            .failed_packages
+            .iter()
+            .map(|package| {
+                json::object! {
+                    name: package.name.as_str(),
+                    version: package.version.as_ref().map(ToString::to_string),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        json::object! {
+            errors: errors,
+            required_version: self.required_version.as_ref().map(ToString::to_string),
+            failed_packages: failed_packages,
+        }
+    }
+
+    /// Reads diagnostics written by [`Diagnostics::to_json`].
+    pub(crate) fn from_json(value: &json::JsonValue) -> Option<Self> {
+        if !value.is_object() {
+            return None;
+        }
+
+        let errors = value["errors"]
+            .members()
+            .map(|error| {
+                Some(Diagnostic {
+                    message: error["message"].as_str()?.to_string(),
+                    code: error["code"].as_str().map(String::from),
+                    file: error["file"].as_str().map(String::from),
+                    line: error["line"].as_u64(),
+                    column: error["column"].as_u64(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let failed_packages = value["failed_packages"]
+            .members()
+            .map(|package| {
+                Some(FailedPackage {
+                    name: package["name"].as_str()?.to_string(),
+                    version: package["version"]
+                        .as_str()
+                        .and_then(|version| semver::Version::parse(version).ok()),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            errors,
+            required_version: value["required_version"]
+                .as_str()
+                .and_then(|version| semver::Version::parse(version).ok()),
+            failed_packages,
+        })
+    }
+}
+
+/// Adds `--message-format=json` to a check command, if it's a cargo command which compiles the
+/// crate, and the version of cargo supports it.
+pub fn with_json_messages(check: &[String], version: &semver::Version) -> Vec<String> {
+    let mut check = check.to_vec();
+
+    let compiles = check.first().map(String::as_str) == Some("cargo")
+        && check
+            .get(1)
+            .map(|subcommand| COMPILING_SUBCOMMANDS.contains(&subcommand.as_str()))
+            .unwrap_or_default();
+    let has_message_format = check.iter().any(|arg| arg.starts_with("--message-format"));
+
+    if compiles && !has_message_format && *version >= MESSAGE_FORMAT_JSON_SINCE {
+        insert_cargo_args(&mut check, vec!["--message-format=json".to_string()]);
+    }
+
+    check
+}
+
+// The names of the feature gates an error asks to enable, e.g. "use of unstable library feature
+// 'once_cell'", or a help message like "add `#![feature(let_else)]` to the crate attributes to enable"
+fn feature_gates(message: &json::JsonValue) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(message)
+        .chain(message["children"].members())
+        .filter_map(|message| message["message"].as_str())
+        .filter_map(|text| {
+            quoted_after(text, "unstable library feature ")
+                .or_else(|| enclosed(text, "#![feature(", ")]"))
+        })
+}
+
+fn stabilized_in(feature: String) -> Option<semver::Version> {
+    STABILIZED_FEATURES
+        .iter()
+        .find(|(name, _)| *name == feature)
+        .map(|(_, (major, minor))| semver::Version::new(*major, *minor, 0))
+}
+
+// e.g. "error: package `x v1.0.0` cannot be built because it requires rustc 1.56 or newer, while
+// the currently active rustc version is 1.50.0"
+fn required_rustc(line: &str) -> Option<semver::Version> {
+    let version = enclosed(line, "requires rustc ", " or newer")?;
+    let mut components = version
+        .trim_start_matches('v')
+        .split('.')
+        .map(str::parse::<u64>);
+
+    match (components.next(), components.next(), components.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), None) => Some(semver::Version::new(major, minor, 0)),
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => {
+            Some(semver::Version::new(major, minor, patch))
+        }
+        _ => None,
+    }
+}
+
+// e.g. "feature `edition2021` is required"
+fn required_edition(line: &str) -> Option<semver::Version> {
+    let feature = enclosed(line, "feature `", "` is required")?;
+
+    EDITION_FEATURES
+        .iter()
+        .find(|(name, _)| *name == feature)
+        .map(|(_, (major, minor))| semver::Version::new(*major, *minor, 0))
+}
+
+// Cargo identifies packages as `name version (source)`, or since Rust 1.77 as `source#name@version`,
+// where `name@` is omitted if the name equals the last segment of the source URL
+fn parse_package_id(id: &str) -> Option<FailedPackage> {
+    let (name, version) = match id.find('#') {
+        Some(position) => {
+            let (source, fragment) = (&id[..position], &id[position + 1..]);
+
+            match fragment.find('@') {
+                Some(at) => (&fragment[..at], &fragment[at + 1..]),
+                None => (source.rsplit('/').next()?, fragment),
+            }
+        }
+        None => {
+            let mut parts = id.split_whitespace();
+            (parts.next()?, parts.next()?)
+        }
+    };
+
+    Some(FailedPackage {
+        name: name.to_string(),
+        version: semver::Version::parse(version).ok(),
+    })
+}
+
+// The package named by one of cargo's own errors, e.g. "error: could not compile `a`",
+// "package `b v0.1.0` cannot be built because it requires rustc 1.56 or newer", or
+// "failed to parse manifest at `/home/.cargo/registry/src/<registry>/c-1.0.0/Cargo.toml`"
+fn failed_package(line: &str) -> Option<FailedPackage> {
+    if let Some(name) = enclosed(line, "could not compile `", "`") {
+        return Some(FailedPackage {
+            name,
+            version: None,
+        });
+    }
+
+    if let Some(package) = enclosed(line, "package `", "` cannot be built") {
+        let mut parts = package.split(' ');
+        let name = parts.next()?.to_string();
+        let version = parts
+            .next()
+            .and_then(|version| semver::Version::parse(version.trim_start_matches('v')).ok());
+
+        return Some(FailedPackage { name, version });
+    }
+
+    let manifest = enclosed(line, "failed to parse manifest at `", "`")?;
+    let directory = std::path::Path::new(&manifest)
+        .parent()?
+        .file_name()?
+        .to_str()?
+        .to_string();
+
+    // the directory is named `name-version`, where the name may contain dashes as well
+    directory.match_indices('-').find_map(|(position, _)| {
+        semver::Version::parse(&directory[position + 1..])
+            .ok()
+            .map(|version| FailedPackage {
+                name: directory[..position].to_string(),
+                version: Some(version),
+            })
+    })
+}
+
+// The text after `prefix`, enclosed in single quotes or backticks
+fn quoted_after(text: &str, prefix: &str) -> Option<String> {
+    let rest = &text[text.find(prefix)? + prefix.len()..];
+    let quote = rest.chars().next().filter(|c| *c == '\'' || *c == '`')?;
+
+    enclosed(rest, &quote.to_string(), &quote.to_string())
+}
+
+// The text between the first `start`, and the first `end` after it
+fn enclosed(text: &str, start: &str, end: &str) -> Option<String> {
+    let rest = &text[text.find(start)? + start.len()..];
+
+    Some(rest[..rest.find(end)?].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSTABLE_LIBRARY_FEATURE: &str = r#"{"reason":"compiler-message","package_id":"a 0.1.0","message":{"message":"use of unstable library feature 'once_cell'","code":{"code":"E0658","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","line_start":3,"column_start":9,"is_primary":true}],"children":[{"message":"add `#![feature(once_cell)]` to the crate attributes to enable","code":null,"level":"help","spans":[],"children":[]}]}}"#;
+
+    const UNSTABLE_LANGUAGE_FEATURE: &str = r#"{"reason":"compiler-message","package_id":"a 0.1.0","message":{"message":"`let...else` statements are unstable","code":{"code":"E0658","explanation":null},"level":"error","spans":[{"file_name":"src/main.rs","line_start":7,"column_start":5,"is_primary":true}],"children":[{"message":"add `#![feature(let_else)]` to the crate attributes to enable","code":null,"level":"help","spans":[],"children":[]}]}}"#;
+
+    const OTHER_ERROR: &str = r#"{"reason":"compiler-message","package_id":"a 0.1.0","message":{"message":"cannot find value `x` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"src/main.rs","line_start":2,"column_start":13,"is_primary":true}],"children":[]}}"#;
+
+    const WARNING: &str = r#"{"reason":"compiler-message","package_id":"a 0.1.0","message":{"message":"unused variable: `y`","code":null,"level":"warning","spans":[],"children":[]}}"#;
+
+    #[test]
+    fn errors() {
+        let stdout = [WARNING, OTHER_ERROR, "not json"].join("\n");
+        let diagnostics = Diagnostics::parse(&stdout, "");
+
+        assert_eq!(
+            diagnostics.errors(),
+            &[Diagnostic {
+                message: "cannot find value `x` in this scope".to_string(),
+                code: Some("E0425".to_string()),
+                file: Some("src/main.rs".to_string()),
+                line: Some(2),
+                column: Some(13),
+            }]
+        );
+        assert_eq!(diagnostics.required_version(), None);
+    }
+
+    #[yare::parameterized(
+        library_feature = { UNSTABLE_LIBRARY_FEATURE, "", Some((1, 70, 0)) },
+        language_feature = { UNSTABLE_LANGUAGE_FEATURE, "", Some((1, 65, 0)) },
+        highest = { &[UNSTABLE_LANGUAGE_FEATURE, UNSTABLE_LIBRARY_FEATURE].join("\n"), "", Some((1, 70, 0)) },
+        rustc_two_components = { "", "error: package `b v0.1.0` cannot be built because it requires rustc 1.56 or newer, while the currently active rustc version is 1.50.0", Some((1, 56, 0)) },
+        rustc_three_components = { "", "error: package `b v0.1.0` cannot be built because it requires rustc 1.60.1 or newer", Some((1, 60, 1)) },
+        edition_2021 = { "", "  feature `edition2021` is required", Some((1, 56, 0)) },
+        edition_2018 = { "", "  feature `edition` is required", Some((1, 31, 0)) },
+        unknown = { OTHER_ERROR, "error: could not compile `a`", None },
+    )]
+    fn required_version(stdout: &str, stderr: &str, expected: Option<(u64, u64, u64)>) {
+        let diagnostics = Diagnostics::parse(stdout, stderr);
+        let expected =
+            expected.map(|(major, minor, patch)| semver::Version::new(major, minor, patch));
+
+        assert_eq!(diagnostics.required_version(), expected.as_ref());
+    }
+
+    #[yare::parameterized(
+        compiler_message = { UNSTABLE_LIBRARY_FEATURE, "", vec![("a", Some("0.1.0"))] },
+        package_id_since_1_77 = { r#"{"reason":"compiler-message","package_id":"registry+https://github.com/rust-lang/crates.io-index#serde_json@1.0.100","message":{"message":"m","code":null,"level":"error","spans":[],"children":[]}}"#, "", vec![("serde_json", Some("1.0.100"))] },
+        path_package_id = { r#"{"reason":"compiler-message","package_id":"path+file:///home/me/some-crate#0.2.0","message":{"message":"m","code":null,"level":"error","spans":[],"children":[]}}"#, "", vec![("some-crate", Some("0.2.0"))] },
+        could_not_compile = { "", "error: could not compile `b` due to 2 previous errors\nerror: could not compile `a`", vec![("b", None), ("a", None)] },
+        requires_rustc = { "", "error: package `b v0.1.0` cannot be built because it requires rustc 1.56 or newer", vec![("b", Some("0.1.0"))] },
+        manifest = { "", "error: failed to parse manifest at `/home/me/.cargo/registry/src/github.com-1ecc6299db9ec823/proc-macro2-1.0.36/Cargo.toml`", vec![("proc-macro2", Some("1.0.36"))] },
+        deduplicated = { UNSTABLE_LIBRARY_FEATURE, "error: could not compile `a`", vec![("a", Some("0.1.0"))] },
+    )]
+    fn failed_packages(stdout: &str, stderr: &str, expected: Vec<(&str, Option<&str>)>) {
+        let diagnostics = Diagnostics::parse(stdout, stderr);
+        let expected = expected
+            .into_iter()
+            .map(|(name, version)| FailedPackage {
+                name: name.to_string(),
+                version: version.map(|version| semver::Version::parse(version).unwrap()),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(diagnostics.failed_packages(), expected.as_slice());
+    }
+
+    #[yare::parameterized(
+        check = { vec!["cargo", "check", "--all"], (1, 38, 0), vec!["cargo", "check", "--all", "--message-format=json"] },
+        before_separator = { vec!["cargo", "test", "--", "--nocapture"], (1, 38, 0), vec!["cargo", "test", "--message-format=json", "--", "--nocapture"] },
+        already_given = { vec!["cargo", "check", "--message-format", "short"], (1, 38, 0), vec!["cargo", "check", "--message-format", "short"] },
+        not_compiling = { vec!["cargo", "fmt"], (1, 38, 0), vec!["cargo", "fmt"] },
+        not_cargo = { vec!["make", "check"], (1, 38, 0), vec!["make", "check"] },
+        too_old = { vec!["cargo", "check"], (1, 12, 0), vec!["cargo", "check"] },
+    )]
+    fn json_messages(check: Vec<&str>, version: (u64, u64, u64), expected: Vec<&str>) {
+        let check = check.into_iter().map(String::from).collect::<Vec<_>>();
+        let version = semver::Version::new(version.0, version.1, version.2);
+
+        assert_eq!(with_json_messages(&check, &version), expected);
+    }
+}
@@ -0,0 +1,363 @@
+//! Generating a lockfile with which a crate builds on a given Rust version.
+//!
+//! Dependencies are often updated to semver compatible versions which require a more recent Rust
+//! version. Each time the check fails, the first dependency which failed to compile is downgraded
+//! with `cargo update --precise`, until the check passes. Like the checks, cargo is run with the
+//! toolchain of the given Rust version, and the environment variables set for it. The versions to
+//! downgrade to are taken from cargo's local cache of the registry index, which also lists the
+//! `rust-version` declared by each version (if any).
+
+use crate::check::{check_toolchain, run_cargo};
+use crate::config::Config;
+use crate::dependencies::{blocking_dependencies, cargo_home, read_lockfile, ManifestLookup};
+use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::BareVersion;
+use crate::reporter::{Output, ProgressAction};
+use rust_releases::semver;
+use std::convert::TryFrom;
+use std::path::Path;
+
+/// The name of the generated lockfile, next to the `Cargo.lock` lockfile.
+pub const MSRV_LOCKFILE: &str = "Cargo.lock.msrv";
+
+/// The maximum number of downgrades, before giving up.
+const MAX_DOWNGRADES: usize = 64;
+
+/// A package which was downgraded to an earlier version.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PinnedPackage {
+    name: String,
+    from: semver::Version,
+    to: semver::Version,
+}
+
+impl PinnedPackage {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The version in the original lockfile
+    pub fn from(&self) -> &semver::Version {
+        &self.from
+    }
+
+    /// The version in the generated lockfile
+    pub fn to(&self) -> &semver::Version {
+        &self.to
+    }
+}
+
+/// Downgrades the dependencies in the lockfile, until the check passes with the given version.
+///
+/// The lockfile at `lockfile` is modified in place. Local packages named in `local` are never
+/// downgraded.
+pub fn downgrade_dependencies(
+    config: &Config,
+    version: &semver::Version,
+    lockfile: &Path,
+    local: &[String],
+    output: &impl Output,
+) -> TResult<Vec<PinnedPackage>> {
+    let crate_root = crate::crate_root_folder(config)?;
+    let manifests = ManifestLookup::new(&crate_root);
+
+    let mut pinned: Vec<PinnedPackage> = Vec::new();
+
+    for downgrades in 0..MAX_DOWNGRADES {
+        output.set_steps(downgrades as u64 + 1);
+        output.progress(ProgressAction::Checking(version));
+
+        let outcome = check_toolchain(version, config, output)?;
+
+        if outcome.is_success() {
+            return Ok(pinned);
+        }
+
+        let locked = read_lockfile(lockfile)?;
+        let blocking = blocking_dependencies(outcome.diagnostics(), &locked, local, &manifests);
+
+        let dependency = blocking.first().ok_or_else(|| {
+            CargoMSRVError::UnableToDowngradeDependencies(format!(
+                "the check fails with {}, but none of the dependencies failed to compile",
+                version
+            ))
+        })?;
+
+        let name = dependency.name();
+        let current = dependency
+            .version()
+            .and_then(|version| semver::Version::parse(version).ok())
+            .ok_or_else(|| {
+                CargoMSRVError::UnableToDowngradeDependencies(format!(
+                    "the version of '{}' is unknown",
+                    name
+                ))
+            })?;
+
+        let next = choose_version(&current, &cached_versions(name), version).ok_or_else(|| {
+            CargoMSRVError::UnableToDowngradeDependencies(format!(
+                "no earlier version of '{} {}' may be compatible with {}",
+                name, current, version
+            ))
+        })?;
+
+        update_precise(config, version, name, &current, &next, output)?;
+
+        match pinned
+            .iter_mut()
+            .find(|package| package.name == name && package.to == current)
+        {
+            Some(package) => package.to = next,
+            None => pinned.push(PinnedPackage {
+                name: name.to_string(),
+                from: current,
+                to: next,
+            }),
+        }
+    }
+
+    Err(CargoMSRVError::UnableToDowngradeDependencies(format!(
+        "the check still fails with {} after {} downgrades",
+        version, MAX_DOWNGRADES
+    )))
+}
+
+// Downgrades a package in the lockfile with `cargo update --precise`, run with the toolchain of
+// the checked version, like the checks. Old cargo versions only accept the `name:version` form of
+// the package id.
+fn update_precise(
+    config: &Config,
+    version: &semver::Version,
+    name: &str,
+    current: &semver::Version,
+    next: &semver::Version,
+    output: &impl Output,
+) -> TResult<()> {
+    tracing::info!("Downgrading {} from {} to {}", name, current, next);
+
+    let args = [
+        "update".to_string(),
+        "--package".to_string(),
+        format!("{}:{}", name, current),
+        "--precise".to_string(),
+        next.to_string(),
+    ];
+    let output = run_cargo(version, config, &args, output)?;
+
+    if !output.status.success() {
+        return Err(CargoMSRVError::UnableToDowngradeDependencies(format!(
+            "`cargo update --precise {}` of '{}' failed: {}",
+            next,
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// A published version of a package, as listed in the registry index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct IndexedVersion {
+    version: semver::Version,
+    rust_version: Option<semver::Version>,
+    yanked: bool,
+}
+
+// The versions of a package listed in cargo's local cache of the registry index, which cargo keeps
+// at `registry/index/<registry>/.cache/<prefix>/<name>`.
+fn cached_versions(name: &str) -> Vec<IndexedVersion> {
+    let registries = match cargo_home()
+        .and_then(|home| std::fs::read_dir(home.join("registry").join("index")).ok())
+    {
+        Some(registries) => registries,
+        None => return Vec::new(),
+    };
+
+    let name = name.to_lowercase();
+
+    registries
+        .filter_map(|registry| registry.ok())
+        .filter_map(|registry| {
+            std::fs::read(
+                registry
+                    .path()
+                    .join(".cache")
+                    .join(index_prefix(&name))
+                    .join(&name),
+            )
+            .ok()
+        })
+        .flat_map(|cache| parse_index_cache(&cache))
+        .collect()
+}
+
+// The directories in which the registry index stores a package, e.g. `se/rd` for `serde`
+fn index_prefix(name: &str) -> String {
+    match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[..2], &name[2..4]),
+    }
+}
+
+// The cache file consists of a header, followed by pairs of a version and its entry in the index
+// (a JSON object), all separated by NUL bytes
+fn parse_index_cache(cache: &[u8]) -> Vec<IndexedVersion> {
+    cache
+        .split(|byte| *byte == 0)
+        .filter(|part| part.first() == Some(&b'{'))
+        .filter_map(|entry| json::parse(&String::from_utf8_lossy(entry)).ok())
+        .filter_map(|entry| {
+            Some(IndexedVersion {
+                version: semver::Version::parse(entry["vers"].as_str()?).ok()?,
+                rust_version: entry["rust_version"]
+                    .as_str()
+                    .and_then(|version| BareVersion::try_from(version).ok())
+                    .map(|version| match version {
+                        BareVersion::TwoComponents(major, minor) => {
+                            semver::Version::new(major, minor, 0)
+                        }
+                        BareVersion::ThreeComponents(major, minor, patch) => {
+                            semver::Version::new(major, minor, patch)
+                        }
+                    }),
+                yanked: entry["yanked"].as_bool().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+// Chooses the version to downgrade to: the most recent earlier version which declares to be
+// compatible with the given Rust version. If none declares so, it's the most recent version of the
+// previous release series (e.g. `1.3.x` for `1.4.2`, or `0.4.1` for `0.4.2`), which doesn't declare
+// to be incompatible. Only semver compatible versions are considered, since the dependents of the
+// package wouldn't accept other versions.
+fn choose_version(
+    current: &semver::Version,
+    versions: &[IndexedVersion],
+    rust_version: &semver::Version,
+) -> Option<semver::Version> {
+    let candidates = versions
+        .iter()
+        .filter(|candidate| {
+            !candidate.yanked
+                && candidate.version.pre.is_empty()
+                && candidate.version < *current
+                && semver_compatible(current, &candidate.version)
+        })
+        .collect::<Vec<_>>();
+
+    let declared_compatible = candidates
+        .iter()
+        .filter(|candidate| {
+            candidate
+                .rust_version
+                .as_ref()
+                .map(|required| required <= rust_version)
+                .unwrap_or_default()
+        })
+        .map(|candidate| &candidate.version)
+        .max();
+
+    let previous_series = candidates
+        .iter()
+        .filter(|candidate| {
+            candidate
+                .rust_version
+                .as_ref()
+                .map(|required| required <= rust_version)
+                .unwrap_or(true)
+        })
+        .filter(|candidate| {
+            current.major == 0
+                || (candidate.version.major, candidate.version.minor)
+                    < (current.major, current.minor)
+        })
+        .map(|candidate| &candidate.version)
+        .max();
+
+    declared_compatible.or(previous_series).cloned()
+}
+
+// Whether a caret requirement on the current version would also accept the candidate
+fn semver_compatible(current: &semver::Version, candidate: &semver::Version) -> bool {
+    match (current.major, current.minor) {
+        (0, 0) => candidate == current,
+        (0, minor) => candidate.major == 0 && candidate.minor == minor,
+        (major, _) => candidate.major == major,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed(version: &str, rust_version: Option<(u64, u64)>) -> IndexedVersion {
+        IndexedVersion {
+            version: semver::Version::parse(version).unwrap(),
+            rust_version: rust_version.map(|(major, minor)| semver::Version::new(major, minor, 0)),
+            yanked: false,
+        }
+    }
+
+    #[test]
+    fn index_cache() {
+        let cache = b"\x03\x02\x00\x00\x00etag\x000.1.0\x00{\"name\":\"a\",\"vers\":\"0.1.0\",\"yanked\":false}\x000.2.0\x00{\"name\":\"a\",\"vers\":\"0.2.0\",\"yanked\":true,\"rust_version\":\"1.56\"}";
+
+        assert_eq!(
+            parse_index_cache(cache),
+            vec![
+                indexed("0.1.0", None),
+                IndexedVersion {
+                    yanked: true,
+                    ..indexed("0.2.0", Some((1, 56)))
+                },
+            ]
+        );
+    }
+
+    #[yare::parameterized(
+        one = { "a", "1" },
+        two = { "ab", "2" },
+        three = { "abc", "3/a" },
+        four = { "serde", "se/rd" },
+    )]
+    fn prefix(name: &str, expected: &str) {
+        assert_eq!(index_prefix(name), expected);
+    }
+
+    #[yare::parameterized(
+        declared_compatible = { "1.4.2", (1, 50), Some("1.3.1") },
+        previous_series = { "1.4.2", (1, 40), Some("1.2.5") },
+        zero_major = { "0.4.2", (1, 40), Some("0.4.1") },
+        none = { "1.0.0", (1, 40), None },
+    )]
+    fn choose(current: &str, rust_version: (u64, u64), expected: Option<&str>) {
+        let versions = vec![
+            indexed("0.4.0", None),
+            indexed("0.4.1", None),
+            indexed("0.4.2", None),
+            indexed("1.0.0", Some((1, 45))),
+            indexed("1.2.5", None),
+            indexed("1.3.0", Some((1, 45))),
+            indexed("1.3.1", Some((1, 48))),
+            indexed("1.4.0", Some((1, 56))),
+            indexed("1.4.1", None),
+            indexed("1.4.2", Some((1, 60))),
+            indexed("2.0.0", Some((1, 40))),
+        ];
+
+        let chosen = choose_version(
+            &semver::Version::parse(current).unwrap(),
+            &versions,
+            &semver::Version::new(rust_version.0, rust_version.1, 0),
+        );
+
+        assert_eq!(
+            chosen,
+            expected.map(|version| semver::Version::parse(version).unwrap())
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Environment variables which are set for the checks, e.g. `RUSTFLAGS` or `CARGO_NET_OFFLINE`.
+//!
+//! A variable is given as `NAME=VALUE`, and may be preceded by a Rust version requirement in
+//! brackets, e.g. `[<1.50]RUSTFLAGS=-Zfoo`, in which case it's only set for the checks of the
+//! versions which match the requirement.
+//!
+//! Variables can also be read from a file, which lists one variable per line. Empty lines and
+//! lines starting with `#` are skipped. Values are taken as is, quotes are not removed.
+
+use crate::errors::{CargoMSRVError, TResult};
+use rust_releases::semver;
+use std::path::Path;
+
+/// The environment variables which are set for the checks. A variable which is given more than
+/// once is set to the value given last.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Environment {
+    vars: Vec<EnvVar>,
+}
+
+impl Environment {
+    /// Reads the variables listed in a file.
+    pub fn from_file(path: &Path) -> TResult<Self> {
+        let invalid = |reason: String| CargoMSRVError::InvalidEnvFile {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let contents = std::fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+
+        let vars = contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| {
+                EnvVar::parse(line).map_err(|err| invalid(format!("line {}: {}", number, err)))
+            })
+            .collect::<TResult<_>>()?;
+
+        Ok(Self { vars })
+    }
+
+    pub fn push(&mut self, var: EnvVar) {
+        self.vars.push(var);
+    }
+
+    /// The names and values of the variables which are set for the checks of the given version.
+    ///
+    /// Commands which don't run a specific Rust version, such as resolving the minimal versions of
+    /// the dependencies with a nightly toolchain, only get the variables without a version
+    /// requirement.
+    pub fn vars_for(&self, version: Option<&semver::Version>) -> Vec<(&str, &str)> {
+        let mut vars: Vec<(&str, &str)> = Vec::new();
+
+        for var in self.vars.iter().filter(|var| var.applies_to(version)) {
+            match vars.iter_mut().find(|(name, _)| *name == var.name) {
+                Some(set) => set.1 = &var.value,
+                None => vars.push((&var.name, &var.value)),
+            }
+        }
+
+        vars
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnvVar {
+    name: String,
+    value: String,
+    versions: Option<semver::VersionReq>,
+}
+
+impl EnvVar {
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            versions: None,
+        }
+    }
+
+    /// Only sets the variable for the checks of the versions which match the requirement.
+    pub fn with_versions(mut self, versions: semver::VersionReq) -> Self {
+        self.versions = Some(versions);
+        self
+    }
+
+    /// Parses a variable given as `NAME=VALUE` or `[<version requirement>]NAME=VALUE`.
+    pub fn parse(input: &str) -> TResult<Self> {
+        let invalid = || CargoMSRVError::InvalidEnvVar(input.to_string());
+
+        let (versions, var) = match input.strip_prefix('[') {
+            Some(rest) => {
+                let end = rest.find(']').ok_or_else(invalid)?;
+                let versions = semver::VersionReq::parse(&rest[..end]).map_err(|_| invalid())?;
+
+                (Some(versions), &rest[end + 1..])
+            }
+            None => (None, input),
+        };
+
+        let separator = var.find('=').ok_or_else(invalid)?;
+        let (name, value) = (&var[..separator], &var[separator + 1..]);
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            value: value.to_string(),
+            versions,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    fn applies_to(&self, version: Option<&semver::Version>) -> bool {
+        match (&self.versions, version) {
+            (None, _) => true,
+            (Some(versions), Some(version)) => versions.matches(version),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[yare::parameterized(
+        plain = { "CARGO_NET_OFFLINE=true", Some(("CARGO_NET_OFFLINE", "true", None)) },
+        empty_value = { "RUSTFLAGS=", Some(("RUSTFLAGS", "", None)) },
+        value_with_separators = { "PKG_CONFIG_PATH=/a:/b=c", Some(("PKG_CONFIG_PATH", "/a:/b=c", None)) },
+        versions = { "[<1.50]RUSTFLAGS=-Zfoo", Some(("RUSTFLAGS", "-Zfoo", Some("<1.50"))) },
+        version_range = { "[>=1.40, <1.50]RUSTFLAGS=-C opt-level=1", Some(("RUSTFLAGS", "-C opt-level=1", Some(">=1.40, <1.50"))) },
+        no_value = { "RUSTFLAGS", None },
+        no_name = { "=true", None },
+        unclosed_versions = { "[<1.50RUSTFLAGS=-Zfoo", None },
+        invalid_versions = { "[soon]RUSTFLAGS=-Zfoo", None },
+    )]
+    fn parse(input: &str, expected: Option<(&str, &str, Option<&str>)>) {
+        let expected = expected.map(|(name, value, versions)| EnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+            versions: versions.map(|versions| semver::VersionReq::parse(versions).unwrap()),
+        });
+
+        assert_eq!(EnvVar::parse(input).ok(), expected);
+    }
+
+    #[yare::parameterized(
+        old = { Some(semver::Version::new(1, 40, 0)), vec![("RUSTFLAGS", "-Zfoo"), ("CARGO_NET_OFFLINE", "true")] },
+        new = { Some(semver::Version::new(1, 56, 0)), vec![("RUSTFLAGS", "-Dwarnings"), ("CARGO_NET_OFFLINE", "true")] },
+        unversioned = { None, vec![("RUSTFLAGS", "-Dwarnings"), ("CARGO_NET_OFFLINE", "true")] },
+    )]
+    fn vars_for(version: Option<semver::Version>, expected: Vec<(&str, &str)>) {
+        let mut environment = Environment::default();
+        environment.push(EnvVar::new("RUSTFLAGS", "-Dwarnings"));
+        environment.push(EnvVar::new("CARGO_NET_OFFLINE", "true"));
+        environment.push(
+            EnvVar::new("RUSTFLAGS", "-Zfoo")
+                .with_versions(semver::VersionReq::parse("<1.50").unwrap()),
+        );
+
+        assert_eq!(environment.vars_for(version.as_ref()), expected);
+    }
+
+    #[test]
+    fn from_file() {
+        let path = std::env::temp_dir().join(format!("cargo-msrv-env-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "# flags for the old toolchains\n[<1.50]RUSTFLAGS=-Zfoo\n\nCARGO_NET_OFFLINE=true\n",
+        )
+        .unwrap();
+
+        let environment = Environment::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let environment = environment.unwrap();
+        assert_eq!(
+            environment.vars_for(Some(&semver::Version::new(1, 49, 0))),
+            vec![("RUSTFLAGS", "-Zfoo"), ("CARGO_NET_OFFLINE", "true")]
+        );
+    }
+
+    #[test]
+    fn from_file_with_invalid_line() {
+        let path =
+            std::env::temp_dir().join(format!("cargo-msrv-env-invalid-{}", std::process::id()));
+        std::fs::write(&path, "CARGO_NET_OFFLINE=true\nRUSTFLAGS\n").unwrap();
+
+        let environment = Environment::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let message = environment.unwrap_err().to_string();
+        assert!(message.contains("line 2"), "{}", message);
+    }
+}
@@ -6,6 +6,7 @@ use std::fmt::Formatter;
 use std::io;
 use std::path::PathBuf;
 use std::string::FromUtf8Error;
+use std::time::Duration;
 
 pub type TResult<T> = Result<T, CargoMSRVError>;
 
@@ -15,24 +16,104 @@ pub enum CargoMSRVError {
     Env(env::VarError),
     GenericMessage(String),
     Io(io::Error),
+    InvalidJobs(String),
+    InvalidDuration(String),
+    InvalidRetries(String),
+    InvalidEnvVar(String),
+    InvalidEnvFile {
+        path: PathBuf,
+        reason: String,
+    },
+    InvalidConfigFile {
+        path: PathBuf,
+        reason: String,
+    },
+    UnknownEnvOption(String),
+    InvalidEnvOption {
+        name: String,
+        reason: String,
+    },
+    ConflictingOptions {
+        option: String,
+        other: String,
+    },
+    MissingRequiredOption {
+        option: String,
+        required: String,
+    },
+    InvalidOutputFormat(String),
+    InvalidTimeoutPolicy(String),
+    UnableToStopCheck(String),
+    CheckTimedOut {
+        toolchain: String,
+        timeout: Duration,
+    },
     InvalidRustVersionNumber(std::num::ParseIntError),
     InvalidUTF8(FromUtf8Error),
     NoVersionMatchesManifestMSRV(crate::manifest::BareVersion, Vec<crate::semver::Version>),
     NoMSRVKeyInCargoToml(PathBuf),
     ParseToml(decent_toml_rs_alternative::TomlError),
     RustReleasesSource(rust_releases::RustChangelogError),
-    RustReleasesRustDistSource(rust_releases::RustDistError),
+    // boxed, since the error is much larger than the other variants
+    RustReleasesRustDistSource(Box<rust_releases::RustDistError>),
     RustReleasesSourceParseError(String),
+    InvalidReleaseFile {
+        path: PathBuf,
+        reason: String,
+    },
+    NoInstalledReleases {
+        target: String,
+    },
     RustupInstallFailed(ToolchainSpecifier),
+    NightlyUnavailable(ToolchainSpecifier),
     RustupRunWithCommandFailed,
     SemverError(rust_releases::semver::Error),
     SystemTime(std::time::SystemTimeError),
     ToolchainNotInstalled,
+    ToolchainNotInDirectory {
+        toolchain: String,
+        directory: PathBuf,
+    },
+    TargetNotInToolchain {
+        toolchain: String,
+        target: String,
+    },
+    TooManyFeaturesForPowerset {
+        features: usize,
+        max: usize,
+    },
+    UnableToReadWorkspace(String),
+    RustupTargetAddFailed {
+        toolchain: String,
+        target: String,
+    },
+    InvalidNightlyDate(String),
+    DeclaredMSRVNotMinimal {
+        declared: crate::semver::Version,
+        compatible: crate::semver::Version,
+    },
+    UnableToDowngradeDependencies(String),
+    LockfileNotFound(PathBuf),
+    UnableToWriteMSRV {
+        path: PathBuf,
+        reason: String,
+    },
+    UnableToResolveMinimalVersions(String),
     UnknownTarget,
+    UnableToAccessCacheFolder,
     UnableToAccessLogFolder,
     UnableToCacheChannelManifest,
-    UnableToFindAnyGoodVersion { command: String },
-    UnableToParseBareVersion { version: String, message: String },
+    UnableToVerifyMSRV {
+        msrv: crate::semver::Version,
+        command: String,
+    },
+    UnableToFindAnyGoodVersion {
+        command: String,
+    },
+    UnableToParseBareVersion {
+        version: String,
+        message: String,
+    },
     UnableToParseBareVersionNumber(std::num::ParseIntError),
     UnableToInitTracing,
     UnableToParseCliArgs,
@@ -47,6 +128,20 @@ impl fmt::Display for CargoMSRVError {
             CargoMSRVError::Env(err) => err.fmt(f),
             CargoMSRVError::GenericMessage(msg) => write!(f, "{}", msg.as_str()),
             CargoMSRVError::Io(err) => err.fmt(f),
+            CargoMSRVError::InvalidJobs(jobs) => write!(f, "Unable to parse the number of jobs from '{}', expected a positive integer.", jobs),
+            CargoMSRVError::InvalidDuration(duration) => write!(f, "Unable to parse the duration '{}', expected a positive number of seconds, optionally followed by 's', 'm' or 'h' (e.g. '90s' or '5m').", duration),
+            CargoMSRVError::InvalidRetries(retries) => write!(f, "Unable to parse the number of retries from '{}', expected a non-negative integer.", retries),
+            CargoMSRVError::InvalidEnvVar(var) => write!(f, "Unable to parse the environment variable '{}', expected NAME=VALUE, optionally preceded by a Rust version requirement in brackets (e.g. '[<1.50]RUSTFLAGS=-Zfoo').", var),
+            CargoMSRVError::InvalidEnvFile { path, reason } => write!(f, "Unable to read the environment variables listed in '{}': {}", path.display(), reason),
+            CargoMSRVError::InvalidConfigFile { path, reason } => write!(f, "Unable to read the configuration in '{}': {}", path.display(), reason),
+            CargoMSRVError::UnknownEnvOption(name) => write!(f, "The environment variable '{}' doesn't name an option of cargo-msrv, expected the long flag of an option prefixed with '{}' (e.g. 'CARGO_MSRV_RELEASE_SOURCE').", name, crate::config_env::PREFIX),
+            CargoMSRVError::InvalidEnvOption { name, reason } => write!(f, "Invalid value for the environment variable '{}': {}", name, reason),
+            CargoMSRVError::ConflictingOptions { option, other } => write!(f, "The option {} can't be used together with {}.", option, other),
+            CargoMSRVError::MissingRequiredOption { option, required } => write!(f, "The option {} can only be used together with {}.", option, required),
+            CargoMSRVError::InvalidOutputFormat(format) => write!(f, "Unknown output format '{}', expected 'json'.", format),
+            CargoMSRVError::InvalidTimeoutPolicy(policy) => write!(f, "Unknown check timeout policy '{}', expected 'fail' or 'abort'.", policy),
+            CargoMSRVError::UnableToStopCheck(reason) => write!(f, "Unable to stop the check after its timeout: {}", reason),
+            CargoMSRVError::CheckTimedOut { toolchain, timeout } => write!(f, "The check with toolchain '{}' didn't finish within {}s, and was aborted.", toolchain, timeout.as_secs()),
             CargoMSRVError::InvalidRustVersionNumber(err) => err.fmt(f),
             CargoMSRVError::InvalidUTF8(err) => err.fmt(f),
             CargoMSRVError::NoVersionMatchesManifestMSRV(msrv, versions_available) => write!(f, "The MSRV requirement ({}) in the Cargo manifest did not match any available version, available: {}", msrv, versions_available.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(", ")),
@@ -55,15 +150,31 @@ impl fmt::Display for CargoMSRVError {
             CargoMSRVError::RustReleasesSource(err) => err.fmt(f),
             CargoMSRVError::RustReleasesRustDistSource(err) => err.fmt(f),
             CargoMSRVError::RustReleasesSourceParseError(err) => write!(f, "Unable to parse rust-releases source from '{}'", err),
+            CargoMSRVError::NoInstalledReleases { target } => write!(f, "No toolchains of stable releases for target '{}' are installed with rustup (e.g. 1.56.0-{})", target, target),
+            CargoMSRVError::InvalidReleaseFile { path, reason } => write!(f, "Unable to read the releases listed in '{}': {}", path.display(), reason),
             CargoMSRVError::RustupInstallFailed(toolchain) => f.write_fmt(format_args!("Unable to install toolchain with `rustup install {}`.", toolchain)),
+            CargoMSRVError::NightlyUnavailable(toolchain) => write!(f, "The nightly toolchain '{}' is not available: no nightly was published on that day, or it lacks a component.", toolchain),
             CargoMSRVError::RustupRunWithCommandFailed => write!(f, "Check toolchain (with `rustup run <toolchain> <command>`) failed."),
             CargoMSRVError::SemverError(err) => write!(f, "{}", err),
             CargoMSRVError::SystemTime(err) => err.fmt(f),
             CargoMSRVError::ToolchainNotInstalled => write!(f, "The given toolchain could not be found. Run `rustup toolchain list` for an overview of installed toolchains."),
+            CargoMSRVError::ToolchainNotInDirectory { toolchain, directory } => write!(f, "The toolchain '{}' could not be found in '{}', expected its binaries in '{}'.", toolchain, directory.display(), directory.join(toolchain).join("bin").display()),
+            CargoMSRVError::TargetNotInToolchain { toolchain, target } => write!(f, "The toolchain '{}' doesn't contain the standard library of the target '{}'.", toolchain, target),
+            CargoMSRVError::TooManyFeaturesForPowerset { features, max } => write!(f, "Unable to check every combination of {} features, at most {} features are supported. Use --feature-set to select the feature sets to check instead.", features, max),
+            CargoMSRVError::UnableToReadWorkspace(reason) => write!(f, "Unable to read the members of the workspace: {}", reason),
+            CargoMSRVError::RustupTargetAddFailed { toolchain, target } => write!(f, "Unable to add target '{}' to toolchain '{}'", target, toolchain),
+            CargoMSRVError::InvalidNightlyDate(date) => write!(f, "Unable to parse '{}' as the date of a nightly (expected YYYY-MM-DD)", date),
+            CargoMSRVError::DeclaredMSRVNotMinimal { declared, compatible } => write!(f, "The declared MSRV ({}) could be lowered, since the crate is also compatible with {}", declared, compatible),
+            CargoMSRVError::UnableToDowngradeDependencies(reason) => write!(f, "Unable to generate a lockfile by downgrading dependencies: {}", reason),
+            CargoMSRVError::LockfileNotFound(crate_root) => write!(f, "No Cargo.lock lockfile found for the crate in '{}'. Create one with `cargo generate-lockfile`.", crate_root.display()),
+            CargoMSRVError::UnableToWriteMSRV { path, reason } => write!(f, "Unable to write the MSRV to '{}': {}", path.display(), reason),
+            CargoMSRVError::UnableToResolveMinimalVersions(reason) => write!(f, "Unable to resolve the minimal versions of the dependencies: {}", reason),
             CargoMSRVError::UnknownTarget => write!(f, "The given target could not be found. Run `rustup target list` for an overview of available toolchains."),
+            CargoMSRVError::UnableToAccessCacheFolder => write!(f, "Unable to access cache folder, run with --no-cache to try again without caching."),
             CargoMSRVError::UnableToAccessLogFolder => write!(f, "Unable to access log folder, run with --no-log to try again without logging."),
             CargoMSRVError::UnableToCacheChannelManifest => write!(f, "Unable to get or store the channel manifest on disk."),
             CargoMSRVError::UnableToInitTracing => write!(f, "Unable to init logger, run with --no-log to try again without logging."),
+            CargoMSRVError::UnableToVerifyMSRV { msrv, command } => write!(f, "The declared MSRV ({}) is not compatible: `{}` failed with this toolchain.", msrv, command),
             CargoMSRVError::UnableToFindAnyGoodVersion { command } => write!(f, r#"Unable to find a Minimum Supported Rust Version (MSRV).
 
 If you think this result is erroneous, please run: `{}` manually.
@@ -139,6 +250,6 @@ impl From<rust_releases::RustChangelogError> for CargoMSRVError {
 
 impl From<rust_releases::RustDistError> for CargoMSRVError {
     fn from(err: rust_releases::RustDistError) -> Self {
-        CargoMSRVError::RustReleasesRustDistSource(err)
+        CargoMSRVError::RustReleasesRustDistSource(Box::new(err))
     }
 }
@@ -0,0 +1,149 @@
+//! Why a check failed.
+//!
+//! Most checks fail because the crate doesn't compile with the toolchain, but a check may also fail
+//! for reasons which have nothing to do with the Rust version: the compiler or linker may be killed
+//! (e.g. by the out-of-memory killer), or the disk may be full. Such failures would derail the
+//! search, so they're told apart by the exit status and the output of the check, and the check is
+//! run once more.
+
+use crate::diagnostics::Diagnostics;
+use std::fmt;
+use std::process::Output;
+
+/// Messages which tell that the check ran out of memory.
+const OUT_OF_MEMORY_MESSAGES: &[&str] = &[
+    "memory allocation of",
+    "out of memory",
+    "cannot allocate memory",
+    "std::bad_alloc",
+];
+
+/// Messages which tell that the check failed to read or write a file.
+const IO_MESSAGES: &[&str] = &[
+    "no space left on device",
+    "disk quota exceeded",
+    "input/output error",
+    "too many open files",
+    "resource temporarily unavailable",
+];
+
+/// Messages after which cargo and linkers report the signal which killed a process, e.g.
+/// `(signal: 9, SIGKILL: kill)` or `collect2: fatal error: ld terminated with signal 9 [Killed]`.
+const SIGNAL_PREFIXES: &[&str] = &["(signal: ", "terminated with signal "];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Failure {
+    /// The crate, or one of its dependencies, can't be built with the toolchain.
+    Compilation,
+    /// The check, or a process it started, was killed by the given signal.
+    Signaled(i32),
+    /// The check ran out of memory.
+    OutOfMemory,
+    /// The check failed to read or write a file, e.g. because the disk is full.
+    Io,
+}
+
+impl Failure {
+    /// Classifies the failure of a check by its exit status and output.
+    pub fn of(output: &Output, diagnostics: &Diagnostics) -> Self {
+        classify(
+            exit_signal(output),
+            &String::from_utf8_lossy(&output.stderr),
+            diagnostics,
+        )
+    }
+
+    /// Whether the check failed for a reason unrelated to the Rust version, and may pass when it's
+    /// run once more.
+    pub fn is_spurious(self) -> bool {
+        self != Failure::Compilation
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Compilation => write!(f, "the crate failed to compile"),
+            Failure::Signaled(signal) => write!(f, "the check was killed by signal {}", signal),
+            Failure::OutOfMemory => write!(f, "the check ran out of memory"),
+            Failure::Io => write!(f, "the check failed with an I/O error"),
+        }
+    }
+}
+
+// Compiler errors tell that the toolchain reached a verdict, even if something else went wrong
+// as well. Failures which can't be explained are assumed to be caused by the Rust version.
+fn classify(signal: Option<i32>, stderr: &str, diagnostics: &Diagnostics) -> Failure {
+    if !diagnostics.errors().is_empty() || diagnostics.required_version().is_some() {
+        return Failure::Compilation;
+    }
+
+    let stderr = stderr.to_ascii_lowercase();
+    let mentions = |messages: &[&str]| messages.iter().any(|message| stderr.contains(message));
+
+    if mentions(OUT_OF_MEMORY_MESSAGES) {
+        Failure::OutOfMemory
+    } else if mentions(IO_MESSAGES) {
+        Failure::Io
+    } else if let Some(signal) = signal.or_else(|| reported_signal(&stderr)) {
+        Failure::Signaled(signal)
+    } else {
+        Failure::Compilation
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(output: &Output) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    output.status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_output: &Output) -> Option<i32> {
+    None
+}
+
+fn reported_signal(stderr: &str) -> Option<i32> {
+    SIGNAL_PREFIXES.iter().find_map(|prefix| {
+        let start = stderr.find(prefix)? + prefix.len();
+        let number = &stderr[start..];
+        let end = number
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(number.len());
+
+        number[..end].parse().ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPILER_ERROR: &str = r#"{"reason":"compiler-message","package_id":"a 0.1.0 (path+file:///a)","message":{"message":"cannot find value `x` in this scope","code":{"code":"E0425"},"level":"error","spans":[]}}"#;
+
+    #[yare::parameterized(
+        compiler_error = { None, COMPILER_ERROR, "error: could not compile `a`", Failure::Compilation },
+        compiler_error_and_killed = { None, COMPILER_ERROR, "(signal: 9, SIGKILL: kill)", Failure::Compilation },
+        unexplained = { None, "", "error: failed to select a version for `b`", Failure::Compilation },
+        rustc_killed = { None, "", "error: could not compile `a`\n\nCaused by:\n  process didn't exit successfully: `rustc --crate-name a` (signal: 9, SIGKILL: kill)", Failure::Signaled(9) },
+        linker_killed = { None, "", "collect2: fatal error: ld terminated with signal 9 [Killed]", Failure::Signaled(9) },
+        check_killed = { Some(15), "", "", Failure::Signaled(15) },
+        allocation_failed = { None, "", "memory allocation of 1048576 bytes failed", Failure::OutOfMemory },
+        allocation_failed_killed = { Some(6), "", "memory allocation of 1048576 bytes failed", Failure::OutOfMemory },
+        disk_full = { None, "", "error: failed to write `target/debug/a`\n\nCaused by:\n  No space left on device (os error 28)", Failure::Io },
+    )]
+    fn classify_failure(signal: Option<i32>, stdout: &str, stderr: &str, expected: Failure) {
+        let diagnostics = Diagnostics::parse(stdout, stderr);
+
+        assert_eq!(classify(signal, stderr, &diagnostics), expected);
+    }
+
+    #[test]
+    fn spurious() {
+        assert!(!Failure::Compilation.is_spurious());
+        assert!(Failure::Signaled(9).is_spurious());
+        assert!(Failure::OutOfMemory.is_spurious());
+        assert!(Failure::Io.is_spurious());
+    }
+}
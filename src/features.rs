@@ -0,0 +1,208 @@
+use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::TomlMap;
+use decent_toml_rs_alternative::TomlValue;
+use std::fmt::{Display, Formatter};
+
+/// A set of cargo features with which a crate is checked.
+///
+/// The default features are not enabled implicitly. They can be included by adding the
+/// `default` feature to the set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeatureSet {
+    features: Vec<String>,
+}
+
+impl FeatureSet {
+    pub fn new<I: IntoIterator<Item = S>, S: Into<String>>(features: I) -> Self {
+        Self {
+            features: features.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Parses a comma separated list of features, e.g. `default,serde`.
+    pub fn parse(input: &str) -> Self {
+        Self::new(
+            input
+                .split(',')
+                .map(str::trim)
+                .filter(|feature| !feature.is_empty()),
+        )
+    }
+
+    pub fn features(&self) -> &[String] {
+        &self.features
+    }
+
+    /// The arguments passed to cargo to select exactly this set of features.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = vec!["--no-default-features".to_string()];
+
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+
+        args
+    }
+}
+
+impl Display for FeatureSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.features.is_empty() {
+            f.write_str("(no features)")
+        } else {
+            f.write_str(&self.features.join(","))
+        }
+    }
+}
+
+/// The feature sets for which the MSRV will be determined separately.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FeatureCombinations {
+    /// The feature sets given by the user
+    Given(Vec<FeatureSet>),
+    /// No features, and each feature defined in the `[features]` table on its own
+    EachFeature,
+    /// Every combination of the features defined in the `[features]` table, except `default`
+    Powerset,
+}
+
+impl FeatureCombinations {
+    /// Generates the feature sets, using the features defined in the given `Cargo.toml` manifest.
+    pub fn feature_sets(&self, manifest: &TomlMap) -> TResult<Vec<FeatureSet>> {
+        match self {
+            Self::Given(sets) => Ok(sets.clone()),
+            Self::EachFeature => Ok(std::iter::once(FeatureSet::new(Vec::<String>::new()))
+                .chain(
+                    declared_features(manifest)
+                        .into_iter()
+                        .map(|feature| FeatureSet::new(vec![feature])),
+                )
+                .collect()),
+            Self::Powerset => {
+                let features = declared_features(manifest)
+                    .into_iter()
+                    .filter(|feature| feature != "default")
+                    .collect::<Vec<_>>();
+
+                powerset(&features)
+            }
+        }
+    }
+}
+
+/// The maximum number of features of which we generate the powerset, i.e. at most 4096 feature sets.
+const MAX_POWERSET_FEATURES: usize = 12;
+
+fn powerset(features: &[String]) -> TResult<Vec<FeatureSet>> {
+    if features.len() > MAX_POWERSET_FEATURES {
+        return Err(CargoMSRVError::TooManyFeaturesForPowerset {
+            features: features.len(),
+            max: MAX_POWERSET_FEATURES,
+        });
+    }
+
+    Ok((0..1usize << features.len())
+        .map(|mask| {
+            FeatureSet::new(
+                features
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, feature)| feature.as_str()),
+            )
+        })
+        .collect())
+}
+
+/// The features declared in the `[features]` table of a `Cargo.toml` manifest, in alphabetical order.
+fn declared_features(manifest: &TomlMap) -> Vec<String> {
+    let mut features = match manifest.get("features") {
+        Some(TomlValue::Table(table)) => table.keys().cloned().collect::<Vec<_>>(),
+        _ => Vec::new(),
+    };
+
+    features.sort();
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{CargoManifestParser, TomlParser};
+
+    const MANIFEST: &str = r#"[package]
+name = "some"
+version = "0.1.0"
+
+[features]
+default = ["std"]
+std = []
+serde = []
+"#;
+
+    fn manifest() -> TomlMap {
+        CargoManifestParser.parse::<TomlMap>(MANIFEST).unwrap()
+    }
+
+    #[yare::parameterized(
+        empty = { "", vec![] },
+        single = { "serde", vec!["serde"] },
+        multiple = { "default,serde", vec!["default", "serde"] },
+        whitespace = { " std , serde ", vec!["std", "serde"] },
+    )]
+    fn parse_feature_set(input: &str, expected: Vec<&str>) {
+        assert_eq!(FeatureSet::parse(input), FeatureSet::new(expected));
+    }
+
+    #[yare::parameterized(
+        none = { vec![], vec!["--no-default-features"] },
+        some = { vec!["std", "serde"], vec!["--no-default-features", "--features", "std,serde"] },
+    )]
+    fn cargo_args(features: Vec<&str>, expected: Vec<&str>) {
+        assert_eq!(FeatureSet::new(features).cargo_args(), expected);
+    }
+
+    #[test]
+    fn each_feature() {
+        let sets = FeatureCombinations::EachFeature
+            .feature_sets(&manifest())
+            .unwrap();
+
+        assert_eq!(
+            sets,
+            vec![
+                FeatureSet::new(Vec::<String>::new()),
+                FeatureSet::new(vec!["default"]),
+                FeatureSet::new(vec!["serde"]),
+                FeatureSet::new(vec!["std"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn feature_powerset() {
+        let sets = FeatureCombinations::Powerset
+            .feature_sets(&manifest())
+            .unwrap();
+
+        assert_eq!(
+            sets,
+            vec![
+                FeatureSet::new(Vec::<String>::new()),
+                FeatureSet::new(vec!["serde"]),
+                FeatureSet::new(vec!["std"]),
+                FeatureSet::new(vec!["serde", "std"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn feature_powerset_too_large() {
+        let features = (0..=MAX_POWERSET_FEATURES)
+            .map(|i| format!("f{}", i))
+            .collect::<Vec<_>>();
+
+        assert!(powerset(&features).is_err());
+    }
+}
@@ -1,91 +1,108 @@
-use crate::command::command_with_output;
 use crate::errors::{CargoMSRVError, TResult};
+use crate::toolchain::ToolchainProvider;
+use rust_releases::{semver, Release, ReleaseIndex};
 
 pub type ToolchainSpecifier = String;
 
 /// Verify that the given toolchain is installed.
-/// with `rustup toolchain list`
-pub fn is_toolchain_installed<S: AsRef<str>>(name: S) -> TResult<()> {
-    let toolchain = name.as_ref();
-    command_with_output(&["toolchain", "list"]).and_then(|child| {
-        let output = child.wait_with_output()?;
-
-        String::from_utf8(output.stdout)
-            .map_err(From::from)
-            .and_then(|string| {
-                let mut lines = string.lines();
+pub fn is_toolchain_installed<S: AsRef<str>>(
+    provider: &dyn ToolchainProvider,
+    name: S,
+) -> TResult<()> {
+    if provider.is_installed(name.as_ref())? {
+        Ok(())
+    } else {
+        Err(CargoMSRVError::ToolchainNotInstalled)
+    }
+}
 
-                // the default toolchain is formatted like so:
-                // <toolchain> (default)
-                if let Some(first) = lines.next() {
-                    if let Some(default) = first.split_ascii_whitespace().next() {
-                        if default == toolchain {
-                            return Ok(());
-                        }
-                    }
-                }
+/// An index of the stable releases of which the toolchain for the given target is installed, e.g.
+/// `1.56.0-x86_64-unknown-linux-gnu`, ordered from the most recent to the least recent release.
+pub fn installed_release_index(
+    provider: &dyn ToolchainProvider,
+    target: &str,
+) -> TResult<ReleaseIndex> {
+    let releases = installed_releases(&provider.list()?, target);
 
-                // after the default toolchain, all other installed toolchains are listed
-                // one per line
-                for line in lines {
-                    if line == toolchain {
-                        return Ok(());
-                    }
-                }
+    if releases.is_empty() {
+        return Err(CargoMSRVError::NoInstalledReleases {
+            target: target.to_string(),
+        });
+    }
 
-                Err(CargoMSRVError::ToolchainNotInstalled)
-            })
-    })
+    Ok(releases.into_iter().map(Release::new_stable).collect())
 }
 
-/// Check if the given target is available.
-/// with `rustup target list`
-pub fn is_target_available<S: AsRef<str>>(name: S) -> TResult<()> {
-    let toolchain = name.as_ref();
-    command_with_output(&["target", "list"]).and_then(|child| {
-        let output = child.wait_with_output()?;
+// Channels such as `stable` or `nightly-2021-10-21`, and toolchains which don't specify the patch
+// version, such as `1.56-<target>`, are left out, since their version is unknown or may change.
+fn installed_releases(toolchains: &[ToolchainSpecifier], target: &str) -> Vec<semver::Version> {
+    let suffix = format!("-{}", target);
 
-        String::from_utf8(output.stdout)
-            .map_err(From::from)
-            .and_then(|string| {
-                // Each target is listed on a single line.
-                // If a target is installed, it is listed as <target> (installed).
-                // If a target is the default, it is listed as <target> (default).
-                for line in string.lines() {
-                    if let Some(it) = line.split_ascii_whitespace().next() {
-                        if it == toolchain {
-                            return Ok(());
-                        }
-                    }
-                }
+    let mut releases = toolchains
+        .iter()
+        .filter(|toolchain| toolchain.ends_with(&suffix))
+        .map(|toolchain| &toolchain[..toolchain.len() - suffix.len()])
+        .filter(|version| {
+            version.split('.').count() == 3
+                && version.chars().all(|c| c.is_ascii_digit() || c == '.')
+        })
+        .filter_map(|version| semver::Version::parse(version).ok())
+        .collect::<Vec<_>>();
+
+    releases.sort_by(|lhs, rhs| rhs.cmp(lhs));
+    releases.dedup();
+    releases
+}
 
-                Err(CargoMSRVError::UnknownTarget)
-            })
-    })
+/// Check if the given target is available, i.e. if the provider can install its standard library.
+pub fn is_target_available<S: AsRef<str>>(
+    provider: &dyn ToolchainProvider,
+    name: S,
+) -> TResult<()> {
+    if provider
+        .targets()?
+        .iter()
+        .any(|target| target == name.as_ref())
+    {
+        Ok(())
+    } else {
+        Err(CargoMSRVError::UnknownTarget)
+    }
 }
 
-/// Uses the `.rustup/settings.toml` file to determine the default target (aka the
-/// `default_host_triple`) if not set by a user.
-pub fn default_target() -> TResult<String> {
-    command_with_output(&["show"]).and_then(|child| {
-        let output = child.wait_with_output()?;
+/// The target of the host (aka the `default_host_triple`), which is checked if no target is set by
+/// a user.
+pub fn default_target(provider: &dyn ToolchainProvider) -> TResult<String> {
+    provider.default_target()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn installed_stable_releases() {
+        let toolchains = vec![
+            "stable-x86_64-unknown-linux-gnu",
+            "nightly-2021-10-21-x86_64-unknown-linux-gnu",
+            "1.38.0-x86_64-unknown-linux-gnu",
+            "1.56-x86_64-unknown-linux-gnu",
+            "1.56.0-x86_64-unknown-linux-gnu",
+            "1.56.0-beta.1-x86_64-unknown-linux-gnu",
+            "1.47.0-wasm32-unknown-unknown",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
 
-        String::from_utf8(output.stdout)
-            .map_err(From::from)
-            .and_then(|string| {
-                // the first line contains the default target
-                // e.g. `Default host: x86_64-unknown-linux-gnu`
+        let releases = installed_releases(&toolchains, "x86_64-unknown-linux-gnu");
 
-                string
-                    .lines()
-                    .next()
-                    .ok_or(CargoMSRVError::DefaultHostTripleNotFound)
-                    .and_then(|line| {
-                        line.split_ascii_whitespace()
-                            .nth(2)
-                            .ok_or(CargoMSRVError::DefaultHostTripleNotFound)
-                            .map(String::from)
-                    })
-            })
-    })
+        assert_eq!(
+            releases,
+            vec![
+                semver::Version::new(1, 56, 0),
+                semver::Version::new(1, 38, 0)
+            ]
+        );
+    }
 }
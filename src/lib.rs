@@ -1,42 +1,101 @@
 #![deny(clippy::all)]
 #![allow(clippy::upper_case_acronyms, clippy::unnecessary_wraps)]
 
-use crate::check::{as_toolchain_specifier, check_toolchain, Outcome};
-use crate::config::{Config, ModeIntent, ReleaseSource};
+use crate::check::{as_toolchain_specifier, check_toolchain, check_toolchains, Outcome};
+use crate::config::{
+    Config, ConfigBuilder, MinimalVersions, ModeIntent, ReleaseSource, SearchMethod,
+};
+use crate::dependencies::{
+    blocking_dependencies, dependency_msrvs, find_lockfile, read_lockfile, BlockingDependency,
+    ManifestLookup,
+};
+use crate::downgrade::{downgrade_dependencies, MSRV_LOCKFILE};
 use crate::errors::{CargoMSRVError, TResult};
-use crate::manifest::{CargoManifest, CargoManifestParser, TomlMap, TomlParser};
-use crate::reporter::{Output, ProgressAction};
+use crate::features::FeatureCombinations;
+use crate::fetch::installed_release_index;
+use crate::lockfile::{LockfileHandler, CARGO_LOCK, CARGO_LOCK_ORIGINAL};
+use crate::manifest::{BareVersion, CargoManifest, CargoManifestParser, TomlMap, TomlParser};
+use crate::nightly::{nightly_index, NightlyDate, FIRST_NIGHTLY};
+use crate::release_file::release_index_from_file;
+use crate::reporter::{Output, ProgressAction, Scope};
+use crate::workspace::{is_virtual_manifest, Workspace};
 use rust_releases::linear::LatestStableReleases;
 use rust_releases::{
     semver, Channel, FetchResources, Release, ReleaseIndex, RustChangelog, RustDist, Source,
 };
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 
+pub mod cache;
 pub mod check;
 pub mod cli;
 pub mod command;
 pub mod config;
+pub mod config_env;
+pub mod config_file;
+pub mod dependencies;
+pub mod diagnostics;
+pub mod downgrade;
+pub mod environment;
 pub mod errors;
+pub mod failure;
+pub mod features;
 pub mod fetch;
 pub mod lockfile;
 pub(crate) mod manifest;
+pub mod manifest_editor;
+pub mod nightly;
+pub mod release_file;
 pub mod reporter;
+pub mod toolchain;
+pub mod workspace;
 
 pub fn run_app<R: Output>(config: &Config, reporter: &R) -> TResult<()> {
-    reporter.progress(ProgressAction::FetchingIndex);
+    // listing the MSRVs of the dependencies, and setting the MSRV, don't require the releases
+    match config.action_intent() {
+        ModeIntent::DetermineMSRV => {
+            run_determine_msrv_action(config, reporter, &release_index(config, reporter)?)
+        }
+        ModeIntent::VerifyMSRV => {
+            run_verify_msrv_action(config, reporter, &release_index(config, reporter)?)
+        }
+        ModeIntent::GenerateLockfile => {
+            run_generate_lockfile_action(config, reporter, &release_index(config, reporter)?)
+        }
+        ModeIntent::ListDependencies => run_list_dependencies_action(config, reporter),
+        ModeIntent::SetMSRV => run_set_msrv_action(config, reporter),
+    }
+}
+
+fn release_index<R: Output>(config: &Config, reporter: &R) -> TResult<ReleaseIndex> {
+    let index = if config.nightly() {
+        nightly_index(
+            config
+                .minimum_version()
+                .and_then(NightlyDate::from_version)
+                .unwrap_or(FIRST_NIGHTLY),
+            config
+                .maximum_version()
+                .and_then(NightlyDate::from_version)
+                .unwrap_or_else(NightlyDate::latest),
+        )
+    } else {
+        reporter.progress(ProgressAction::FetchingIndex);
 
-    let index = match config.release_source() {
-        ReleaseSource::RustChangelog => {
-            RustChangelog::fetch_channel(Channel::Stable)?.build_index()?
+        match config.release_source() {
+            ReleaseSource::RustChangelog => {
+                RustChangelog::fetch_channel(Channel::Stable)?.build_index()?
+            }
+            ReleaseSource::RustDist => RustDist::fetch_channel(Channel::Stable)?.build_index()?,
+            ReleaseSource::File(path) => release_index_from_file(path)?,
+            ReleaseSource::Installed => {
+                installed_release_index(config.toolchain_provider().as_ref(), config.target())?
+            }
         }
-        ReleaseSource::RustDist => RustDist::fetch_channel(Channel::Stable)?.build_index()?,
     };
 
-    match config.action_intent() {
-        ModeIntent::DetermineMSRV => run_determine_msrv_action(config, reporter, &index),
-        ModeIntent::VerifyMSRV => run_verify_msrv_action(config, reporter, &index),
-    }
+    Ok(index)
 }
 
 fn run_determine_msrv_action<R: Output>(
@@ -44,34 +103,372 @@ fn run_determine_msrv_action<R: Output>(
     reporter: &R,
     release_index: &ReleaseIndex,
 ) -> TResult<()> {
-    match determine_msrv(config, reporter, release_index)? {
+    let compatibility = match config.minimal_versions() {
+        Some(minimal_versions) => {
+            determine_msrv_per_resolution(config, minimal_versions, reporter, release_index)?
+        }
+        None => determine_msrv_of_targets(config, reporter, release_index)?,
+    };
+
+    match compatibility {
         MinimalCompatibility::NoCompatibleToolchains => {
             Err(CargoMSRVError::UnableToFindAnyGoodVersion {
                 command: config.check_command().join(" "),
             })
         }
-        MinimalCompatibility::CapableToolchain { ref version, .. }
-            if config.output_toolchain_file() =>
-        {
-            output_toolchain_file(config, version)
+        MinimalCompatibility::CapableToolchain { ref version, .. } => {
+            if config.output_toolchain_file() {
+                output_toolchain_file(config, version)?;
+            }
+
+            if config.write_msrv() {
+                let version =
+                    BareVersion::ThreeComponents(version.major, version.minor, version.patch);
+                write_msrv(config, reporter, &version)?;
+            }
+
+            Ok(())
         }
-        MinimalCompatibility::CapableToolchain { .. } => Ok(()),
     }
 }
 
-// NB: only public for integration testing
-pub fn run_verify_msrv_action<R: Output>(
+/// Determines the MSRV with the locked versions of the dependencies, and once more with the
+/// minimal versions of the dependencies.
+fn determine_msrv_per_resolution<R: Output>(
     config: &Config,
+    minimal_versions: MinimalVersions,
     reporter: &R,
     release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    let resolutions = ["locked", minimal_versions.unstable_flag()];
+
+    reporter.scope(Scope::Resolution(resolutions[0]));
+    let locked = determine_msrv_of_targets(config, reporter, release_index)?;
+
+    reporter.scope(Scope::Resolution(resolutions[1]));
+    let minimal =
+        determine_msrv_with_minimal_versions(config, minimal_versions, reporter, release_index)?;
+
+    let scopes = resolutions
+        .iter()
+        .map(|resolution| Scope::Resolution(resolution))
+        .collect::<Vec<_>>();
+
+    Ok(finish_scopes(reporter, &scopes, &[locked, minimal]))
+}
+
+/// Determines the MSRV with a lockfile which resolves the minimal versions of the dependencies.
+///
+/// The lockfile is generated with a nightly cargo, so it's translated into a format which each of
+/// the checked toolchains can read. The original lockfile is restored afterwards.
+fn determine_msrv_with_minimal_versions<R: Output>(
+    config: &Config,
+    minimal_versions: MinimalVersions,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    let crate_root = crate_root_folder(config)?;
+    let lockfile = find_lockfile(&crate_root).unwrap_or_else(|| crate_root.join(CARGO_LOCK));
+    let config = ConfigBuilder::from_config(config.clone())
+        .translate_lockfile(true)
+        .build();
+
+    if lockfile.is_file() {
+        let handle = LockfileHandler::with_replacement(&lockfile, CARGO_LOCK_ORIGINAL)
+            .move_lockfile()?
+            .generate_lockfile(|_| {
+                resolve_minimal_versions(&config, &crate_root, minimal_versions)
+            })?;
+
+        let compatibility = determine_msrv_of_targets(&config, reporter, release_index);
+        handle.restore_lockfile()?;
+
+        compatibility
+    } else {
+        resolve_minimal_versions(&config, &crate_root, minimal_versions)?;
+
+        let compatibility = determine_msrv_of_targets(&config, reporter, release_index);
+        LockfileHandler::new(&lockfile).remove_lockfile()?;
+
+        compatibility
+    }
+}
+
+// Generates a lockfile with the minimal versions of the dependencies, with a nightly cargo
+fn resolve_minimal_versions(
+    config: &Config,
+    crate_root: &Path,
+    minimal_versions: MinimalVersions,
 ) -> TResult<()> {
-    let crate_folder = crate_root_folder(config)?;
-    let cargo_toml = crate_folder.join("Cargo.toml");
+    let manifest = crate_root.join("Cargo.toml");
+
+    tracing::info!(
+        "Resolving the dependencies with -Z {}",
+        minimal_versions.unstable_flag()
+    );
+
+    let update = vec![
+        "cargo".to_string(),
+        "update".to_string(),
+        "-Z".to_string(),
+        minimal_versions.unstable_flag().to_string(),
+        "--manifest-path".to_string(),
+        manifest.to_string_lossy().to_string(),
+    ];
+
+    // only the variables which aren't limited to some Rust versions apply to the nightly toolchain
+    let envs = config
+        .environment()
+        .vars_for(None)
+        .into_iter()
+        .map(|(name, value)| (name, OsStr::new(value)))
+        .collect::<Vec<_>>();
+
+    let output = config
+        .toolchain_provider()
+        .run("nightly", &update, Some(crate_root), &envs)
+        .and_then(|child| child.wait_with_output().map_err(CargoMSRVError::Io))?;
+
+    if !output.status.success() {
+        return Err(CargoMSRVError::UnableToResolveMinimalVersions(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Determines the MSRV of the crate, for each of the cross compilation targets if there are any.
+fn determine_msrv_of_targets<R: Output>(
+    config: &Config,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    if config.cross_targets().is_empty() {
+        determine_msrv_of_crate(config, reporter, release_index)
+    } else {
+        determine_msrv_per_target(config, reporter, release_index)
+    }
+}
+
+/// Determines the MSRV for each target separately, by cross compiling from the host.
+fn determine_msrv_per_target<R: Output>(
+    config: &Config,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    let targets = config.cross_targets();
+    let mut compatibilities = Vec::with_capacity(targets.len());
+
+    for target in targets {
+        reporter.scope(Scope::Target(target));
+
+        let config = ConfigBuilder::from_config(config.clone())
+            .cross_target(target.clone())
+            .build();
+
+        compatibilities.push(determine_msrv_of_crate(&config, reporter, release_index)?);
+    }
+
+    let scopes = targets
+        .iter()
+        .map(|target| Scope::Target(target))
+        .collect::<Vec<_>>();
+
+    Ok(finish_scopes(reporter, &scopes, &compatibilities))
+}
+
+/// Determines the MSRV of the crate, or of each of its members if it's a workspace.
+fn determine_msrv_of_crate<R: Output>(
+    config: &Config,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    if is_workspace_search(config)? {
+        determine_msrv_per_member(config, reporter, release_index)
+    } else {
+        let cargo_toml = crate_root_folder(config)?.join("Cargo.toml");
+        determine_msrv_of_package(config, &cargo_toml, reporter, release_index)
+    }
+}
+
+/// Starts the galloping search at the MSRV declared in the given manifest, if it declares one.
+fn seed_galloping_search<'a>(
+    config: &Config<'a>,
+    cargo_toml: &Path,
+    release_index: &ReleaseIndex,
+) -> TResult<Config<'a>> {
+    if !cargo_toml.is_file() {
+        return Ok(config.clone());
+    }
+
+    let manifest = CargoManifest::try_from(read_manifest(cargo_toml)?)?;
+    let seed = manifest.minimum_rust_version().and_then(|version| {
+        version
+            .try_to_semver(release_index.releases().iter().map(Release::version))
+            .ok()
+    });
+
+    Ok(ConfigBuilder::from_config(config.clone())
+        .search_method(SearchMethod::Galloping {
+            seed: seed.cloned(),
+        })
+        .build())
+}
+
+// Workspace members are searched separately if requested, or if the manifest is a virtual manifest
+// (i.e. there's no package of its own to check)
+fn is_workspace_search(config: &Config) -> TResult<bool> {
+    if config.package().is_some() {
+        return Ok(false);
+    }
 
-    let contents = std::fs::read_to_string(&cargo_toml).map_err(CargoMSRVError::Io)?;
+    if config.workspace() {
+        return Ok(true);
+    }
+
+    let cargo_toml = crate_root_folder(config)?.join("Cargo.toml");
+    if !cargo_toml.is_file() {
+        return Ok(false);
+    }
+
+    Ok(is_virtual_manifest(&read_manifest(&cargo_toml)?))
+}
+
+/// Determines the MSRV of each workspace member separately, in dependency order. The MSRV of the
+/// members a member depends on is used as the lower bound of its search.
+fn determine_msrv_per_member<R: Output>(
+    config: &Config,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    let workspace = Workspace::load(&crate_root_folder(config)?)?;
+    let members = workspace.members_in_dependency_order()?;
+    let mut compatibilities: Vec<MinimalCompatibility> = Vec::with_capacity(members.len());
+
+    for member in &members {
+        reporter.scope(Scope::WorkspaceMember(member.name()));
+
+        // dependencies precede their dependents, so their compatibility is already known
+        let lower_bound = member
+            .dependencies()
+            .iter()
+            .filter_map(|dependency| members.iter().position(|m| m.name() == dependency))
+            .filter_map(|index| compatibilities[index].version())
+            .chain(config.minimum_version())
+            .max()
+            .cloned();
 
-    let manifest = CargoManifestParser::default().parse::<TomlMap>(&contents)?;
-    let manifest = CargoManifest::try_from(manifest)?;
+        let mut builder =
+            ConfigBuilder::from_config(config.clone()).package(member.name().to_string());
+        if let Some(version) = lower_bound {
+            builder = builder.minimum_version(version);
+        }
+        let mut member_config = builder.build();
+
+        // the galloping search of a member starts at the MSRV declared by the member
+        if let SearchMethod::Galloping { seed: None } = member_config.search_method() {
+            member_config =
+                seed_galloping_search(&member_config, member.manifest_path(), release_index)?;
+        }
+
+        compatibilities.push(determine_msrv_of_package(
+            &member_config,
+            member.manifest_path(),
+            reporter,
+            release_index,
+        )?);
+    }
+
+    let scopes = members
+        .iter()
+        .map(|member| Scope::WorkspaceMember(member.name()))
+        .collect::<Vec<_>>();
+
+    Ok(finish_scopes(reporter, &scopes, &compatibilities))
+}
+
+/// Determines the MSRV of a single package, for each of the feature sets given by the config, if any.
+fn determine_msrv_of_package<R: Output>(
+    config: &Config,
+    cargo_toml: &Path,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    match config.feature_combinations() {
+        Some(combinations) => {
+            let manifest = read_manifest(cargo_toml)?;
+            determine_msrv_per_feature_set(config, combinations, &manifest, reporter, release_index)
+        }
+        None => determine_msrv(config, reporter, release_index),
+    }
+}
+
+/// Determines the MSRV for each feature set separately.
+fn determine_msrv_per_feature_set<R: Output>(
+    config: &Config,
+    combinations: &FeatureCombinations,
+    manifest: &TomlMap,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<MinimalCompatibility> {
+    let feature_sets = combinations.feature_sets(manifest)?;
+    let mut compatibilities = Vec::with_capacity(feature_sets.len());
+
+    for set in &feature_sets {
+        reporter.scope(Scope::FeatureSet(set));
+
+        let config = ConfigBuilder::from_config(config.clone())
+            .features(set.clone())
+            .build();
+
+        compatibilities.push(determine_msrv(&config, reporter, release_index)?);
+    }
+
+    let scopes = feature_sets
+        .iter()
+        .map(Scope::FeatureSet)
+        .collect::<Vec<_>>();
+
+    Ok(finish_scopes(reporter, &scopes, &compatibilities))
+}
+
+/// Reports the MSRV of each scope. The overall MSRV is the highest of them, and only exists if
+/// each scope is compatible with some toolchain.
+fn finish_scopes(
+    reporter: &impl Output,
+    scopes: &[Scope],
+    compatibilities: &[MinimalCompatibility],
+) -> MinimalCompatibility {
+    let overall = if compatibilities.contains(&MinimalCompatibility::NoCompatibleToolchains) {
+        MinimalCompatibility::NoCompatibleToolchains
+    } else {
+        compatibilities
+            .iter()
+            .max_by_key(|compatibility| compatibility.version())
+            .cloned()
+            .unwrap_or(MinimalCompatibility::NoCompatibleToolchains)
+    };
+
+    let results = scopes
+        .iter()
+        .copied()
+        .zip(compatibilities.iter().map(MinimalCompatibility::version))
+        .collect::<Vec<_>>();
+
+    reporter.finish_scopes(&results, overall.version());
+
+    overall
+}
+
+// NB: only public for integration testing
+pub fn run_verify_msrv_action<R: Output>(
+    config: &Config,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<()> {
+    let cargo_toml = crate_root_folder(config)?.join("Cargo.toml");
+    let manifest = CargoManifest::try_from(read_manifest(&cargo_toml)?)?;
 
     let version = manifest
         .minimum_rust_version()
@@ -85,20 +482,166 @@ pub fn run_verify_msrv_action<R: Output>(
 
     let cmd = config.check_command_string();
     reporter.mode(ModeIntent::VerifyMSRV);
+
+    // in strict mode, the release just below the MSRV is checked too
+    let below = if config.strict() {
+        release_below(version, config, release_index)
+    } else {
+        None
+    };
+    reporter.set_steps(1 + below.is_some() as u64);
+
     let status = check_toolchain(version, config, reporter)?;
-    report_verify_completion(reporter, status, &cmd);
+
+    let below = match below {
+        Some(below) if status.is_success() => Some(check_toolchain(&below, config, reporter)?),
+        _ => None,
+    };
+
+    report_verify_completion(reporter, status, below, &cmd)
+}
+
+/// Lists the MSRVs declared by the dependencies of the crate, as resolved in its lockfile, without
+/// building anything.
+pub fn run_list_dependencies_action<R: Output>(config: &Config, reporter: &R) -> TResult<()> {
+    reporter.mode(ModeIntent::ListDependencies);
+
+    let crate_root = crate_root_folder(config)?;
+    let manifests = ManifestLookup::new(&crate_root);
+    let lockfile =
+        find_lockfile(&crate_root).ok_or_else(|| CargoMSRVError::LockfileNotFound(crate_root))?;
+
+    let dependencies = dependency_msrvs(&lockfile, &manifests)?;
+    reporter.dependency_msrvs(&lockfile, &dependencies);
 
     Ok(())
 }
 
-fn report_verify_completion(output: &impl Output, status: Outcome, cmd: &str) {
-    if status.is_success() {
-        output.finish_success(ModeIntent::VerifyMSRV, status.version());
-    } else {
-        output.finish_failure(ModeIntent::VerifyMSRV, cmd);
+/// Writes the given MSRV to the manifest of the crate, without checking whether the crate is
+/// compatible with it.
+pub fn run_set_msrv_action<R: Output>(config: &Config, reporter: &R) -> TResult<()> {
+    reporter.mode(ModeIntent::SetMSRV);
+
+    let version = config
+        .manifest_rust_version()
+        .ok_or(CargoMSRVError::UnableToParseCliArgs)?;
+
+    write_msrv(config, reporter, version)
+}
+
+fn write_msrv<R: Output>(config: &Config, reporter: &R, version: &BareVersion) -> TResult<()> {
+    let (manifest, field) = manifest_editor::write_msrv(&crate_root_folder(config)?, version)?;
+    reporter.msrv_written(&manifest, field, version);
+
+    Ok(())
+}
+
+/// Generates a lockfile with which the crate builds on the given Rust version, by downgrading the
+/// dependencies which fail to compile with it. The generated lockfile is written next to the
+/// `Cargo.lock` lockfile, which is restored afterwards.
+pub fn run_generate_lockfile_action<R: Output>(
+    config: &Config,
+    reporter: &R,
+    release_index: &ReleaseIndex,
+) -> TResult<()> {
+    let version = config
+        .lockfile_rust_version()
+        .ok_or(CargoMSRVError::UnableToParseCliArgs)?
+        .try_to_semver(release_index.releases().iter().map(Release::version))?;
+
+    let cmd = config.check_command_string();
+    reporter.mode(ModeIntent::GenerateLockfile);
+
+    let crate_root = crate_root_folder(config)?;
+    let lockfile = find_lockfile(&crate_root).unwrap_or_else(|| crate_root.join(CARGO_LOCK));
+    let original = std::fs::read(&lockfile).ok();
+    let package = package_name(&crate_root)?;
+
+    let pinned = downgrade_dependencies(config, version, &lockfile, package.as_slice(), reporter);
+    let generated = std::fs::read(&lockfile);
+
+    // restore the original lockfile, or remove the generated one if there was none
+    match original {
+        Some(contents) => std::fs::write(&lockfile, contents)?,
+        None if lockfile.is_file() => std::fs::remove_file(&lockfile)?,
+        None => {}
+    }
+
+    let pinned = match pinned {
+        Ok(pinned) => pinned,
+        Err(err) => {
+            reporter.finish_failure(ModeIntent::GenerateLockfile, &cmd);
+            return Err(err);
+        }
+    };
+
+    let msrv_lockfile = lockfile.with_file_name(MSRV_LOCKFILE);
+    std::fs::write(&msrv_lockfile, generated?)?;
+
+    reporter.finish_success(ModeIntent::GenerateLockfile, version, None);
+    reporter.lockfile_generated(&msrv_lockfile, &pinned);
+
+    Ok(())
+}
+
+fn report_verify_completion(
+    output: &impl Output,
+    status: Outcome,
+    below: Option<Outcome>,
+    cmd: &str,
+) -> TResult<()> {
+    match below {
+        Some(below) if below.is_success() => {
+            output.finish_not_minimal(status.version(), below.version());
+
+            Err(CargoMSRVError::DeclaredMSRVNotMinimal {
+                declared: status.version().clone(),
+                compatible: below.version().clone(),
+            })
+        }
+        _ if status.is_success() => {
+            // in strict mode, the release below the MSRV failed the check
+            let failed_below = below
+                .as_ref()
+                .map(|below| (below.version(), below.diagnostics()));
+
+            output.finish_success(ModeIntent::VerifyMSRV, status.version(), failed_below);
+            Ok(())
+        }
+        _ => {
+            output.finish_failure(ModeIntent::VerifyMSRV, cmd);
+
+            Err(CargoMSRVError::UnableToVerifyMSRV {
+                msrv: status.version().clone(),
+                command: cmd.to_string(),
+            })
+        }
     }
 }
 
+/// The release just below the given version, i.e. the most recent release which is less recent.
+/// Unless all patch releases are included, only the latest patch release of each minor version
+/// is considered.
+fn release_below(
+    version: &semver::Version,
+    config: &Config,
+    release_index: &ReleaseIndex,
+) -> Option<semver::Version> {
+    let releases = release_index.releases().to_vec();
+
+    let releases: Vec<Release> = if config.include_all_patch_releases() {
+        releases
+    } else {
+        releases.into_iter().latest_stable_releases().collect()
+    };
+
+    releases
+        .iter()
+        .map(Release::version)
+        .find(|release| *release < version)
+        .cloned()
+}
+
 /// An enum to represent the minimal compatibility
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum MinimalCompatibility {
@@ -121,6 +664,13 @@ impl MinimalCompatibility {
 
         panic!("Unable to unwrap MinimalCompatibility (CapableToolchain::version)")
     }
+
+    pub fn version(&self) -> Option<&semver::Version> {
+        match self {
+            Self::CapableToolchain { version, .. } => Some(version),
+            Self::NoCompatibleToolchains => None,
+        }
+    }
 }
 
 impl From<Outcome> for MinimalCompatibility {
@@ -141,11 +691,22 @@ pub fn determine_msrv<R: Output>(
     reporter: &R,
     index: &rust_releases::ReleaseIndex,
 ) -> TResult<MinimalCompatibility> {
+    let seeded;
+    let config = match config.search_method() {
+        SearchMethod::Galloping { seed: None } => {
+            let cargo_toml = crate_root_folder(config)?.join("Cargo.toml");
+            seeded = seed_galloping_search(config, &cargo_toml, index)?;
+            &seeded
+        }
+        _ => config,
+    };
+
     let cmd = config.check_command_string();
 
     let releases = index.releases();
 
-    let releases = if config.include_all_patch_releases() {
+    // nightlies don't have patch releases
+    let releases = if config.include_all_patch_releases() || config.nightly() {
         releases.to_vec()
     } else {
         releases
@@ -179,14 +740,52 @@ fn determine_msrv_impl(
     output: &impl Output,
 ) -> TResult<MinimalCompatibility> {
     let mut compatibility = MinimalCompatibility::NoCompatibleToolchains;
+    let mut failed_below = None;
 
     output.set_steps(included_releases.len() as u64);
 
-    // Whether to perform a linear (most recent to least recent), or binary search
-    if config.bisect() {
-        test_against_releases_bisect(included_releases, &mut compatibility, config, output)?;
-    } else {
-        test_against_releases_linearly(included_releases, &mut compatibility, config, output)?;
+    // the nightlies which turn out to be unavailable are removed during the search
+    let mut releases = included_releases.to_vec();
+
+    // Whether to perform a linear (most recent to least recent), binary or galloping search
+    match config.search_method() {
+        SearchMethod::Galloping { seed: Some(seed) } => {
+            match releases
+                .iter()
+                .position(|release| release.version() == seed)
+            {
+                Some(start) => test_against_releases_galloping(
+                    &releases,
+                    start,
+                    &mut compatibility,
+                    &mut failed_below,
+                    config,
+                    output,
+                )?,
+                // the declared MSRV is outside of the searched range
+                None => test_against_releases_bisect(
+                    &mut releases,
+                    &mut compatibility,
+                    &mut failed_below,
+                    config,
+                    output,
+                )?,
+            }
+        }
+        SearchMethod::Linear => test_against_releases_linearly(
+            &mut releases,
+            &mut compatibility,
+            &mut failed_below,
+            config,
+            output,
+        )?,
+        _ => test_against_releases_bisect(
+            &mut releases,
+            &mut compatibility,
+            &mut failed_below,
+            config,
+            output,
+        )?,
     }
 
     match &compatibility {
@@ -194,7 +793,21 @@ fn determine_msrv_impl(
             toolchain: _,
             version,
         } => {
-            output.finish_success(ModeIntent::DetermineMSRV, version);
+            let blocking = if config.blocking_dependencies() {
+                find_blocking_dependencies(config, &releases, version, &mut failed_below, output)?
+            } else {
+                None
+            };
+
+            let failed_below = failed_below
+                .as_ref()
+                .map(|outcome| (outcome.version(), outcome.diagnostics()));
+
+            output.finish_success(ModeIntent::DetermineMSRV, version, failed_below);
+
+            if let Some((below, dependencies)) = blocking {
+                output.blocking_dependencies(&below, &dependencies);
+            }
         }
         MinimalCompatibility::NoCompatibleToolchains => {
             output.finish_failure(ModeIntent::DetermineMSRV, cmd)
@@ -204,58 +817,417 @@ fn determine_msrv_impl(
     Ok(compatibility)
 }
 
-fn test_against_releases_linearly(
+// Finds the dependencies which fail to compile with the release just below the MSRV. Unless the
+// search already checked this release, it is checked once more, since its diagnostics are required.
+fn find_blocking_dependencies(
+    config: &Config,
     releases: &[Release],
+    msrv: &semver::Version,
+    failed_below: &mut Option<Outcome>,
+    output: &impl Output,
+) -> TResult<Option<(semver::Version, Vec<BlockingDependency>)>> {
+    let below = match releases
+        .iter()
+        .position(|release| release.version() == msrv)
+    {
+        Some(index) => &releases[index + 1..],
+        None => return Ok(None),
+    };
+
+    // the first release below the MSRV which is available, i.e. nightlies which can't be installed
+    // are skipped
+    let mut below = below.iter().map(Release::version);
+    let below = loop {
+        let version = match below.next() {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+
+        let checked = failed_below
+            .as_ref()
+            .map(|outcome| outcome.version() == version)
+            .unwrap_or_default();
+
+        if checked {
+            break version;
+        }
+
+        output.progress(ProgressAction::Checking(version));
+        let outcome = match check_toolchain(version, config, output) {
+            Err(CargoMSRVError::NightlyUnavailable(_)) => continue,
+            outcome => outcome?,
+        };
+
+        if outcome.is_success() {
+            return Ok(None);
+        }
+
+        *failed_below = Some(outcome);
+        break version;
+    };
+
+    let diagnostics = match failed_below {
+        Some(outcome) => outcome.diagnostics(),
+        None => return Ok(None),
+    };
+
+    let crate_root = crate_root_folder(config)?;
+
+    let lockfile = find_lockfile(&crate_root)
+        .map(|lockfile| read_lockfile(&lockfile))
+        .transpose()?
+        .unwrap_or_default();
+
+    let package = package_name(&crate_root)?;
+    let dependencies = blocking_dependencies(
+        diagnostics,
+        &lockfile,
+        package.as_slice(),
+        &ManifestLookup::new(&crate_root),
+    );
+
+    Ok(Some((below.clone(), dependencies)))
+}
+
+fn test_against_releases_linearly(
+    releases: &mut Vec<Release>,
     compatibility: &mut MinimalCompatibility,
+    failed_below: &mut Option<Outcome>,
     config: &Config,
     output: &impl Output,
 ) -> TResult<()> {
-    for release in releases {
-        output.progress(ProgressAction::Checking(release.version()));
-        let outcome = check_toolchain(release.version(), config, output)?;
+    let mut next = 0;
 
-        if !outcome.is_success() {
-            break;
-        }
+    // With more than one job, the next `jobs` releases are checked at the same time
+    while next < releases.len() {
+        let indices = (next..releases.len().min(next + config.jobs())).collect::<Vec<_>>();
+
+        let outcomes = match check_available(releases, &indices, config, output)? {
+            Some(outcomes) => outcomes,
+            None => continue,
+        };
 
-        *compatibility = outcome.into();
+        for outcome in outcomes {
+            if !outcome.is_success() {
+                *failed_below = Some(outcome);
+                return Ok(());
+            }
+
+            *compatibility = outcome.into();
+            next += 1;
+        }
     }
 
     Ok(())
 }
 
+// Checks the releases at the given indices. Nightlies are published most, but not all days, and
+// some lack a component. A nightly which can't be installed is removed from the releases instead,
+// in which case `None` is returned, and the search continues with the neighbouring nightlies.
+fn check_available(
+    releases: &mut Vec<Release>,
+    indices: &[usize],
+    config: &Config,
+    output: &impl Output,
+) -> TResult<Option<Vec<Outcome>>> {
+    let versions = indices
+        .iter()
+        .map(|&index| releases[index].version())
+        .collect::<Vec<_>>();
+
+    let toolchain = match check_toolchains(&versions, config, output) {
+        Err(CargoMSRVError::NightlyUnavailable(toolchain)) => toolchain,
+        outcomes => return outcomes.map(Some),
+    };
+
+    let unavailable = indices.iter().copied().find(|&index| {
+        as_toolchain_specifier(releases[index].version(), config.target()) == toolchain
+    });
+
+    match unavailable {
+        Some(index) => {
+            tracing::warn!("Skipping {}, since it can't be installed", toolchain);
+            releases.remove(index);
+            Ok(None)
+        }
+        None => Err(CargoMSRVError::NightlyUnavailable(toolchain)),
+    }
+}
+
 // Use a binary search to find the MSRV
 fn test_against_releases_bisect(
-    releases: &[Release],
+    releases: &mut Vec<Release>,
     compatibility: &mut MinimalCompatibility,
+    failed_below: &mut Option<Outcome>,
     config: &Config,
     output: &impl Output,
 ) -> TResult<()> {
-    use rust_releases::bisect::{Bisect, Narrow};
+    if config.jobs() > 1 {
+        return test_against_releases_bisect_parallel(
+            releases,
+            compatibility,
+            failed_below,
+            config,
+            output,
+        );
+    }
 
-    // track progressed items
-    let progressed = std::cell::Cell::new(0u64);
-    let mut binary_search = Bisect::from_slice(releases);
-    let outcome = binary_search.search_with_result_and_remainder(|release, remainder| {
-        output.progress(ProgressAction::Checking(release.version()));
+    // the undecided releases are those in the range [lower, upper)
+    let mut lower = 0;
+    let mut upper = releases.len();
+    let mut progressed = 0u64;
 
-        // increment progressed items
-        let steps = progressed.replace(progressed.get().saturating_add(1));
-        output.set_steps(steps + (remainder as u64));
+    while lower < upper {
+        let mid = lower + (upper - lower) / 2;
 
-        let outcome = check_toolchain(release.version(), config, output)?;
+        progressed += 1;
+        output.set_steps(progressed + (upper - lower - 1) as u64);
+
+        let outcome = match check_available(releases, &[mid], config, output)? {
+            Some(mut outcomes) => outcomes.remove(0),
+            // the releases after the unavailable nightly moved up by one
+            None => {
+                upper -= 1;
+                continue;
+            }
+        };
 
         if outcome.is_success() {
-            // Expand search space
-            TResult::Ok(Narrow::ToRight)
+            lower = mid + 1;
         } else {
-            // Shrink search space
-            TResult::Ok(Narrow::ToLeft)
+            upper = incompatible_from(releases, mid, &outcome, config).max(lower);
+            keep_most_recent_failure(failed_below, outcome);
         }
-    });
+    }
+
+    // all releases before the first incompatible release are compatible
+    *compatibility = match lower.checked_sub(1) {
+        Some(i) => {
+            let version = releases[i].version();
+
+            MinimalCompatibility::CapableToolchain {
+                toolchain: as_toolchain_specifier(version, config.target()),
+                version: version.clone(),
+            }
+        }
+        None => MinimalCompatibility::NoCompatibleToolchains,
+    };
+
+    Ok(())
+}
+
+// Keeps the outcome of the most recent release which failed the check, since it tells why the
+// release just below the MSRV is incompatible
+fn keep_most_recent_failure(failed_below: &mut Option<Outcome>, outcome: Outcome) {
+    let more_recent = match failed_below {
+        Some(failed) => outcome.version() > failed.version(),
+        None => true,
+    };
 
-    // update compatibility
-    *compatibility = outcome?
+    if more_recent {
+        *failed_below = Some(outcome);
+    }
+}
+
+// The index of the first release which is known to be incompatible, given that the release at
+// `index` failed the check. Usually that's the failed release itself, but the diagnostics of the
+// check may tell that a more recent release is required, so the releases before it can't pass the
+// check either.
+//
+// Nightlies are skipped: features are available on nightly before the stable release in which
+// they're stabilized.
+fn incompatible_from(
+    releases: &[Release],
+    index: usize,
+    outcome: &Outcome,
+    config: &Config,
+) -> usize {
+    if config.nightly() {
+        return index;
+    }
+
+    outcome
+        .diagnostics()
+        .required_version()
+        .and_then(|required| {
+            releases
+                .iter()
+                .position(|release| release.version() < required)
+        })
+        .map_or(index, |position| position.min(index))
+}
+
+// Use a galloping search, which starts at the given release, to find the MSRV
+fn test_against_releases_galloping(
+    releases: &[Release],
+    start: usize,
+    compatibility: &mut MinimalCompatibility,
+    failed_below: &mut Option<Outcome>,
+    config: &Config,
+    output: &impl Output,
+) -> TResult<()> {
+    let mut progressed = 0u64;
+
+    let compatible = gallop(releases.len(), start, |index, remainder| {
+        let version = releases[index].version();
+        output.progress(ProgressAction::Checking(version));
+
+        progressed += 1;
+        output.set_steps(progressed + remainder as u64);
+
+        let outcome = check_toolchain(version, config, output)?;
+
+        if outcome.is_success() {
+            return Ok(Probe::Compatible);
+        }
+
+        let from = incompatible_from(releases, index, &outcome, config);
+        keep_most_recent_failure(failed_below, outcome);
+
+        Ok(Probe::Incompatible { from })
+    })?;
+
+    // all releases before the first incompatible release are compatible
+    *compatibility = match compatible.checked_sub(1) {
+        Some(i) => {
+            let version = releases[i].version();
+
+            MinimalCompatibility::CapableToolchain {
+                toolchain: as_toolchain_specifier(version, config.target()),
+                version: version.clone(),
+            }
+        }
+        None => MinimalCompatibility::NoCompatibleToolchains,
+    };
+
+    Ok(())
+}
+
+// The result of checking a release during a search
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Probe {
+    Compatible,
+    // the release is incompatible, and so is each release from the given index onwards
+    Incompatible { from: usize },
+}
+
+// Finds the index of the first incompatible release (or `len`, if each release is compatible),
+// given that releases are ordered from most to least recent, so all releases before it are
+// compatible, and all releases from it onwards are incompatible.
+//
+// First, releases at exponentially increasing distances from `start` are checked, in the direction
+// of the first incompatible release, until it has been passed. Then, the remaining range is
+// narrowed down with a binary search. The second argument to `probe` is the number of releases
+// which are still undecided.
+fn gallop<F>(len: usize, start: usize, mut probe: F) -> TResult<usize>
+where
+    F: FnMut(usize, usize) -> TResult<Probe>,
+{
+    // the undecided releases are those in the range [lower, upper)
+    let mut lower = 0;
+    let mut upper = len;
+    let mut step = 1;
+
+    match probe(start, upper - lower)? {
+        Probe::Compatible => {
+            lower = start + 1;
+
+            // expand towards less recent releases
+            while start + step < upper {
+                let index = start + step;
+
+                match probe(index, upper - lower)? {
+                    Probe::Compatible => {
+                        lower = index + 1;
+                        step *= 2;
+                    }
+                    Probe::Incompatible { from } => {
+                        upper = from.min(index).max(lower);
+                        break;
+                    }
+                }
+            }
+        }
+        Probe::Incompatible { from } => {
+            upper = from.min(start);
+
+            // expand towards more recent releases
+            while upper > 0 {
+                let index = upper.saturating_sub(step);
+
+                match probe(index, upper - lower)? {
+                    Probe::Compatible => {
+                        lower = index + 1;
+                        break;
+                    }
+                    Probe::Incompatible { from } => {
+                        upper = from.min(index);
+                        step *= 2;
+                    }
+                }
+            }
+        }
+    }
+
+    while lower < upper {
+        let mid = lower + (upper - lower) / 2;
+
+        match probe(mid, upper - lower)? {
+            Probe::Compatible => lower = mid + 1,
+            Probe::Incompatible { from } => upper = from.min(mid).max(lower),
+        }
+    }
+
+    Ok(lower)
+}
+
+// Use a k-ary search to find the MSRV, where k is the number of concurrent jobs.
+//
+// Each round checks up to `jobs` releases spread evenly over the releases which are still undecided.
+// Since releases are ordered from most to least recent, all releases before the last compatible
+// release are assumed to be compatible, and all releases from the first incompatible release
+// onwards are assumed to be incompatible, just like in the binary search.
+fn test_against_releases_bisect_parallel(
+    releases: &mut Vec<Release>,
+    compatibility: &mut MinimalCompatibility,
+    failed_below: &mut Option<Outcome>,
+    config: &Config,
+    output: &impl Output,
+) -> TResult<()> {
+    // the undecided releases are those in the range [lower, upper)
+    let mut lower = 0;
+    let mut upper = releases.len();
+    let mut progressed = 0u64;
+    let mut last_compatible = None;
+
+    while lower < upper {
+        let indices = probe_indices(lower, upper, config.jobs());
+
+        let outcomes = match check_available(releases, &indices, config, output)? {
+            Some(outcomes) => outcomes,
+            // the releases after the unavailable nightly moved up by one
+            None => {
+                upper -= 1;
+                continue;
+            }
+        };
+        progressed += outcomes.len() as u64;
+
+        for (index, outcome) in indices.into_iter().zip(outcomes) {
+            if outcome.is_success() {
+                lower = index + 1;
+                last_compatible = Some(index);
+            } else {
+                upper = incompatible_from(releases, index, &outcome, config).max(lower);
+                keep_most_recent_failure(failed_below, outcome);
+                break;
+            }
+        }
+
+        output.set_steps(progressed + (upper.saturating_sub(lower) as u64));
+    }
+
+    *compatibility = last_compatible
         .map(|i| {
             let version = releases[i].version();
 
@@ -269,6 +1241,19 @@ fn test_against_releases_bisect(
     Ok(())
 }
 
+// Picks at most `count` indices, spread evenly over the range [lower, upper)
+fn probe_indices(lower: usize, upper: usize, count: usize) -> Vec<usize> {
+    let len = upper - lower;
+
+    if len <= count {
+        return (lower..upper).collect();
+    }
+
+    (1..=count)
+        .map(|part| lower + (len * part) / (count + 1))
+        .collect()
+}
+
 fn include_version(
     current: &semver::Version,
     min_version: Option<&semver::Version>,
@@ -308,7 +1293,7 @@ fn output_toolchain_file(config: &Config, stable_version: &semver::Version) -> T
         r#"[toolchain]
 channel = "{}"
 "#,
-        stable_version
+        nightly::toolchain_channel(stable_version)
     );
 
     std::fs::write(&path, content)?;
@@ -317,6 +1302,19 @@ channel = "{}"
     Ok(())
 }
 
+// The name of the package defined by the manifest in the crate root, if it defines one
+fn package_name(crate_root: &Path) -> TResult<Option<String>> {
+    Ok(read_manifest(&crate_root.join("Cargo.toml"))?
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_string()))
+}
+
+pub(crate) fn read_manifest(cargo_toml: &Path) -> TResult<TomlMap> {
+    let contents = std::fs::read_to_string(cargo_toml).map_err(CargoMSRVError::Io)?;
+    CargoManifestParser::default().parse::<TomlMap>(&contents)
+}
+
 pub fn crate_root_folder(config: &Config) -> TResult<PathBuf> {
     if let Some(path) = config.crate_path() {
         Ok(path.to_path_buf())
@@ -333,6 +1331,90 @@ mod tests {
 
     ide!();
 
+    // (len, start, first incompatible release, maximum number of checks)
+    #[yare::parameterized(
+        unchanged = { 20, 10, 11, 2 },
+        one_older = { 20, 10, 12, 3 },
+        one_newer = { 20, 10, 10, 2 },
+        far_older = { 20, 2, 17, 9 },
+        far_newer = { 20, 17, 3, 9 },
+        all_compatible = { 20, 10, 20, 6 },
+        none_compatible = { 20, 10, 0, 7 },
+        start_at_oldest = { 20, 19, 20, 1 },
+        start_at_newest = { 20, 0, 0, 1 },
+    )]
+    fn gallop(len: usize, start: usize, expected: usize, max_checks: usize) {
+        let mut checks = 0;
+
+        let first_incompatible = super::super::gallop(len, start, |index, _| {
+            checks += 1;
+            Ok(if index < expected {
+                Probe::Compatible
+            } else {
+                Probe::Incompatible { from: index }
+            })
+        })
+        .unwrap();
+
+        assert_eq!(first_incompatible, expected);
+        assert!(checks <= max_checks, "{} checks", checks);
+    }
+
+    // (len, start, first incompatible release, maximum number of checks), where each failed check
+    // tells exactly which release is required
+    #[yare::parameterized(
+        far_newer = { 20, 17, 3, 2 },
+        newest = { 20, 17, 0, 1 },
+    )]
+    fn gallop_with_required_release(len: usize, start: usize, expected: usize, max_checks: usize) {
+        let mut checks = 0;
+
+        let first_incompatible = super::super::gallop(len, start, |index, _| {
+            checks += 1;
+            Ok(if index < expected {
+                Probe::Compatible
+            } else {
+                Probe::Incompatible { from: expected }
+            })
+        })
+        .unwrap();
+
+        assert_eq!(first_incompatible, expected);
+        assert!(checks <= max_checks, "{} checks", checks);
+    }
+
+    #[yare::parameterized(
+        latest_patches = { (1, 38, 0), false, Some((1, 37, 1)) },
+        all_patches = { (1, 37, 1), true, Some((1, 37, 0)) },
+        skip_to_previous_minor = { (1, 37, 1), false, Some((1, 36, 0)) },
+        oldest = { (1, 36, 0), false, None },
+    )]
+    fn release_below(
+        version: (u64, u64, u64),
+        all_patches: bool,
+        expected: Option<(u64, u64, u64)>,
+    ) {
+        let index = vec![
+            Release::new_stable(Version::new(1, 38, 0)),
+            Release::new_stable(Version::new(1, 37, 1)),
+            Release::new_stable(Version::new(1, 37, 0)),
+            Release::new_stable(Version::new(1, 36, 0)),
+        ]
+        .into_iter()
+        .collect::<ReleaseIndex>();
+        let config = ConfigBuilder::new(ModeIntent::VerifyMSRV, "x86_64-unknown-linux-gnu")
+            .include_all_patch_releases(all_patches)
+            .build();
+
+        let version = Version::new(version.0, version.1, version.2);
+        let below = super::super::release_below(&version, &config, &index);
+
+        assert_eq!(
+            below,
+            expected.map(|(major, minor, patch)| Version::new(major, minor, patch))
+        );
+    }
+
     #[parameterized(current = {
         50, // -inf <= x <= inf
         50, // 1.50.0 <= x <= inf
@@ -391,4 +1473,15 @@ mod tests {
             max_version.as_ref()
         ));
     }
+
+    #[yare::parameterized(
+        single_job = { 0, 10, 1, vec![5] },
+        two_jobs = { 0, 9, 2, vec![3, 6] },
+        offset_range = { 4, 10, 2, vec![6, 8] },
+        fewer_releases_than_jobs = { 2, 4, 4, vec![2, 3] },
+        single_release = { 7, 8, 3, vec![7] },
+    )]
+    fn test_probe_indices(lower: usize, upper: usize, count: usize, expected: Vec<usize>) {
+        assert_eq!(probe_indices(lower, upper, count), expected);
+    }
 }
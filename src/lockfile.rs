@@ -1,62 +1,576 @@
 use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::TomlMap;
+use decent_toml_rs_alternative::TomlValue;
+use rust_releases::semver;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 
 pub struct LockfileHandler<S: LockfileState> {
     state: PathBuf,
+    // the name under which the original lockfile is kept while it's moved
+    replacement: &'static str,
     marker: PhantomData<S>,
 }
 
 pub struct Start;
 pub struct Moved;
+pub struct Replaced;
 pub struct Complete;
 
 pub trait LockfileState {}
 impl LockfileState for Start {}
 impl LockfileState for Moved {}
+impl LockfileState for Replaced {}
 impl LockfileState for Complete {}
 
 pub const CARGO_LOCK: &str = "Cargo.lock";
-const CARGO_LOCK_REPLACEMENT: &str = "Cargo.lock-ignored-for-cargo-msrv";
+pub(crate) const CARGO_LOCK_REPLACEMENT: &str = "Cargo.lock-ignored-for-cargo-msrv";
+/// The name under which the original lockfile is kept, while the MSRV is determined with a
+/// lockfile which resolves the minimal versions of the dependencies.
+pub(crate) const CARGO_LOCK_ORIGINAL: &str = "Cargo.lock-original-for-cargo-msrv";
+
+impl<S: LockfileState> LockfileHandler<S> {
+    fn into_state<T: LockfileState>(self) -> LockfileHandler<T> {
+        LockfileHandler {
+            state: self.state,
+            replacement: self.replacement,
+            marker: PhantomData,
+        }
+    }
+
+    fn replacement_path(&self) -> PathBuf {
+        self.state.parent().unwrap().join(self.replacement)
+    }
+}
 
 impl LockfileHandler<Start> {
     pub fn new<P: AsRef<Path>>(lock_file: P) -> Self {
+        Self::with_replacement(lock_file, CARGO_LOCK_REPLACEMENT)
+    }
+
+    /// Keeps the original lockfile under the given name while it's moved, so lockfiles can be moved
+    /// by multiple handlers at the same time.
+    pub fn with_replacement<P: AsRef<Path>>(lock_file: P, replacement: &'static str) -> Self {
         LockfileHandler {
             state: lock_file.as_ref().to_path_buf(),
+            replacement,
             marker: PhantomData,
         }
     }
 
     pub fn move_lockfile(self) -> TResult<LockfileHandler<Moved>> {
-        let folder = self.state.parent().unwrap();
-        std::fs::rename(self.state.as_path(), folder.join(CARGO_LOCK_REPLACEMENT))
+        std::fs::rename(self.state.as_path(), self.replacement_path())
             .map_err(CargoMSRVError::Io)?;
 
-        Ok(LockfileHandler {
-            state: self.state,
-            marker: PhantomData,
-        })
+        Ok(self.into_state())
     }
 
     pub fn remove_lockfile(self) -> TResult<LockfileHandler<Complete>> {
         std::fs::remove_file(self.state.as_path()).map_err(CargoMSRVError::Io)?;
 
-        Ok(LockfileHandler {
-            state: self.state,
-            marker: PhantomData,
-        })
+        Ok(self.into_state())
     }
 }
 
 impl LockfileHandler<Moved> {
+    /// Writes a lockfile in place of the moved lockfile, e.g. a copy of it which is translated into
+    /// an earlier format.
+    pub fn write_lockfile(self, contents: &str) -> TResult<LockfileHandler<Replaced>> {
+        self.generate_lockfile(|lockfile| {
+            std::fs::write(lockfile, contents).map_err(CargoMSRVError::Io)
+        })
+    }
+
+    /// Generates a lockfile in place of the moved lockfile, e.g. with `cargo update`.
+    ///
+    /// The original lockfile is moved back if the lockfile can't be generated.
+    pub fn generate_lockfile<F>(self, generate: F) -> TResult<LockfileHandler<Replaced>>
+    where
+        F: FnOnce(&Path) -> TResult<()>,
+    {
+        if let Err(err) = generate(self.state.as_path()) {
+            self.move_lockfile_back()?;
+            return Err(err);
+        }
+
+        Ok(self.into_state())
+    }
+
     pub fn move_lockfile_back(self) -> TResult<LockfileHandler<Complete>> {
-        let folder = self.state.parent().unwrap();
-        std::fs::rename(folder.join(CARGO_LOCK_REPLACEMENT), self.state.as_path())
+        std::fs::rename(self.replacement_path(), self.state.as_path())
             .map_err(CargoMSRVError::Io)?;
 
-        Ok(LockfileHandler {
-            state: self.state,
-            marker: PhantomData,
+        Ok(self.into_state())
+    }
+}
+
+impl LockfileHandler<Replaced> {
+    /// Removes the written lockfile, and moves the original lockfile back.
+    pub fn restore_lockfile(self) -> TResult<LockfileHandler<Complete>> {
+        std::fs::remove_file(self.state.as_path()).map_err(CargoMSRVError::Io)?;
+
+        self.into_state::<Moved>().move_lockfile_back()
+    }
+}
+
+/// The formats of `Cargo.lock` lockfiles, ordered from the least to the most recent format.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum LockfileVersion {
+    /// The original format, with the checksums in a `[metadata]` table
+    V1,
+    /// Lists the checksums with the packages, and dependencies by name only, unless ambiguous
+    V2,
+    /// Starts with a `version = 3` marker
+    V3,
+    /// Starts with a `version = 4` marker, and percent-encodes the URLs of git dependencies
+    V4,
+}
+
+impl LockfileVersion {
+    /// The first Rust version with which cargo can read lockfiles of this format.
+    pub fn readable_since(self) -> semver::Version {
+        match self {
+            Self::V1 => semver::Version::new(1, 0, 0),
+            Self::V2 => semver::Version::new(1, 38, 0),
+            Self::V3 => semver::Version::new(1, 47, 0),
+            Self::V4 => semver::Version::new(1, 78, 0),
+        }
+    }
+
+    /// The most recent format which cargo can read with the given Rust version.
+    pub fn newest_readable_by(version: &semver::Version) -> Self {
+        [Self::V4, Self::V3, Self::V2]
+            .iter()
+            .copied()
+            .find(|format| *version >= format.readable_since())
+            .unwrap_or(Self::V1)
+    }
+
+    /// The format of a lockfile.
+    ///
+    /// Lockfiles in the V1 and V2 formats don't state their format. V1 lockfiles are recognized by
+    /// their `[metadata]` checksums, and V2 lockfiles by the checksums of their packages. Without
+    /// any checksums, V1 lockfiles are recognized by dependencies which are all listed with their
+    /// version.
+    pub fn of(lockfile: &TomlMap) -> Self {
+        match lockfile.get("version") {
+            Some(TomlValue::Integer(4)) => return Self::V4,
+            Some(TomlValue::Integer(3)) => return Self::V3,
+            _ => {}
+        }
+
+        let metadata_checksums = match lockfile.get("metadata") {
+            Some(TomlValue::Table(metadata)) => {
+                metadata.keys().any(|key| key.starts_with("checksum "))
+            }
+            _ => false,
+        };
+
+        let packages = packages(lockfile);
+
+        if !metadata_checksums && packages.iter().any(|package| package.checksum.is_some()) {
+            return Self::V2;
+        }
+
+        let dependencies = packages
+            .iter()
+            .flat_map(|package| package.dependencies.iter())
+            .collect::<Vec<_>>();
+
+        if metadata_checksums
+            || (!dependencies.is_empty() && dependencies.iter().all(|dep| dep.contains(' ')))
+        {
+            Self::V1
+        } else {
+            Self::V2
+        }
+    }
+}
+
+/// Translates a lockfile into the given format. Lockfiles which are already in the given format, or
+/// an earlier one, are left as is.
+pub fn translate_lockfile(contents: &str, to: LockfileVersion) -> TResult<String> {
+    let lockfile =
+        decent_toml_rs_alternative::parse_toml(contents).map_err(CargoMSRVError::ParseToml)?;
+    let from = LockfileVersion::of(&lockfile);
+
+    if from <= to {
+        return Ok(contents.to_string());
+    }
+
+    let contents = if from == LockfileVersion::V4 {
+        decode_git_sources(contents)
+    } else {
+        contents.to_string()
+    };
+
+    Ok(match to {
+        LockfileVersion::V1 => {
+            let lockfile = decent_toml_rs_alternative::parse_toml(&contents)
+                .map_err(CargoMSRVError::ParseToml)?;
+            print_v1(&packages(&lockfile))
+        }
+        LockfileVersion::V2 => replace_version_marker(&contents, None),
+        version => replace_version_marker(&contents, Some(version)),
+    })
+}
+
+// V4 lockfiles percent-encode the query of the URLs of git sources, e.g. the branch `feature/x`
+// in `git+https://github.com/a/b?branch=feature%2Fx#<commit>`, which earlier formats don't.
+// Sources are found in the `source` of a package, and in the dependencies which name their source.
+fn decode_git_sources(contents: &str) -> String {
+    let mut decoded = String::with_capacity(contents.len());
+    let mut rest = contents;
+
+    while let Some(start) = rest.find("git+") {
+        let is_source = matches!(rest[..start].chars().last(), Some('"') | Some('('));
+        let end = rest[start..]
+            .find(&['"', ')', '\n'][..])
+            .map_or(rest.len(), |end| start + end);
+
+        decoded.push_str(&rest[..start]);
+
+        if is_source {
+            decoded.push_str(&decode_query(&rest[start..end]));
+        } else {
+            decoded.push_str(&rest[start..end]);
+        }
+
+        rest = &rest[end..];
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_query(url: &str) -> String {
+    let query = match url.find('?') {
+        Some(query) => query,
+        None => return url.to_string(),
+    };
+    let fragment = url
+        .find('#')
+        .filter(|fragment| *fragment > query)
+        .unwrap_or(url.len());
+
+    let bytes = &url.as_bytes()[query..fragment];
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes.get(i + 1..i + 3) {
+            Some(hex) if bytes[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit) => {
+                let hex = std::str::from_utf8(hex).unwrap_or_default();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(decoded) => format!("{}{}{}", &url[..query], decoded, &url[fragment..]),
+        Err(_) => url.to_string(),
+    }
+}
+
+// The version marker precedes the first table of the lockfile
+fn replace_version_marker(contents: &str, to: Option<LockfileVersion>) -> String {
+    let marker = match to {
+        Some(LockfileVersion::V4) => Some("version = 4"),
+        Some(LockfileVersion::V3) => Some("version = 3"),
+        _ => None,
+    };
+
+    let mut in_header = true;
+    let mut translated = String::with_capacity(contents.len());
+
+    for line in contents.lines() {
+        in_header = in_header && !line.starts_with('[');
+
+        if in_header && line.replace(' ', "").starts_with("version=") {
+            if let Some(marker) = marker {
+                translated.push_str(marker);
+                translated.push('\n');
+            }
+        } else {
+            translated.push_str(line);
+            translated.push('\n');
+        }
+    }
+
+    translated
+}
+
+struct Package {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+    dependencies: Vec<String>,
+}
+
+impl Package {
+    // The way a package is referred to in a V1 lockfile, e.g. `a 0.1.0 (registry+https://...)`
+    fn v1_id(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{} {} ({})", self.name, self.version, source),
+            None => format!("{} {}", self.name, self.version),
+        }
+    }
+}
+
+fn packages(lockfile: &TomlMap) -> Vec<Package> {
+    let packages = match lockfile.get("package") {
+        Some(TomlValue::Array(packages)) => packages,
+        _ => return Vec::new(),
+    };
+
+    packages
+        .iter()
+        .filter_map(|package| {
+            Some(Package {
+                name: package.get("name")?.as_string()?,
+                version: package.get("version")?.as_string()?,
+                source: package.get("source").and_then(TomlValue::as_string),
+                checksum: package.get("checksum").and_then(TomlValue::as_string),
+                dependencies: match package.get("dependencies") {
+                    Some(TomlValue::Array(dependencies)) => dependencies
+                        .iter()
+                        .filter_map(TomlValue::as_string)
+                        .collect(),
+                    _ => Vec::new(),
+                },
+            })
         })
+        .collect()
+}
+
+// Since V2, a dependency is listed as `name`, unless multiple versions of the package are locked, in
+// which case it's `name version`, or even `name version (source)`.
+fn resolve_dependency<'p>(packages: &'p [Package], dependency: &str) -> Option<&'p Package> {
+    let mut parts = dependency.splitn(3, ' ');
+    let name = parts.next()?;
+    let version = parts.next();
+    let source = parts
+        .next()
+        .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+
+    packages.iter().find(|package| {
+        package.name == name
+            && version.iter().all(|version| package.version == *version)
+            && source
+                .iter()
+                .all(|source| package.source.as_deref() == Some(*source))
+    })
+}
+
+fn print_v1(packages: &[Package]) -> String {
+    let mut lockfile = String::from(
+        "# This file is automatically @generated by Cargo.\n# It is not intended for manual editing.\n",
+    );
+
+    for package in packages {
+        lockfile.push_str("[[package]]\n");
+        lockfile.push_str(&format!("name = \"{}\"\n", package.name));
+        lockfile.push_str(&format!("version = \"{}\"\n", package.version));
+
+        if let Some(source) = &package.source {
+            lockfile.push_str(&format!("source = \"{}\"\n", source));
+        }
+
+        if !package.dependencies.is_empty() {
+            lockfile.push_str("dependencies = [\n");
+
+            for dependency in &package.dependencies {
+                let id = resolve_dependency(packages, dependency)
+                    .map(Package::v1_id)
+                    .unwrap_or_else(|| dependency.clone());
+                lockfile.push_str(&format!(" \"{}\",\n", id));
+            }
+
+            lockfile.push_str("]\n");
+        }
+
+        lockfile.push('\n');
+    }
+
+    let checksums = packages
+        .iter()
+        .filter_map(|package| {
+            package
+                .checksum
+                .as_ref()
+                .map(|checksum| (package.v1_id(), checksum))
+        })
+        .collect::<Vec<_>>();
+
+    if !checksums.is_empty() {
+        lockfile.push_str("[metadata]\n");
+
+        for (id, checksum) in checksums {
+            lockfile.push_str(&format!("\"checksum {}\" = \"{}\"\n", id, checksum));
+        }
+    }
+
+    lockfile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V3: &str = r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "itoa 0.4.8",
+ "itoa 1.0.1",
+]
+
+[[package]]
+name = "itoa"
+version = "0.4.8"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "b71991ff56294aa922b450139ee08b3bfc70982c6b2c7562771375cf73542dd4"
+
+[[package]]
+name = "itoa"
+version = "1.0.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "1aab8fc367588b89dcee83ab0fd66b72b50b72fa1904d7095045ace2b0c81c35"
+"#;
+
+    const V1: &str = r#"# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "itoa 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)",
+ "itoa 1.0.1 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "itoa"
+version = "0.4.8"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "itoa"
+version = "1.0.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum itoa 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)" = "b71991ff56294aa922b450139ee08b3bfc70982c6b2c7562771375cf73542dd4"
+"checksum itoa 1.0.1 (registry+https://github.com/rust-lang/crates.io-index)" = "1aab8fc367588b89dcee83ab0fd66b72b50b72fa1904d7095045ace2b0c81c35"
+"#;
+
+    fn version_of(contents: &str) -> LockfileVersion {
+        LockfileVersion::of(&decent_toml_rs_alternative::parse_toml(contents).unwrap())
+    }
+
+    #[yare::parameterized(
+        v1 = { V1, LockfileVersion::V1 },
+        v2 = { "[[package]]\nname = \"a\"\nversion = \"0.1.0\"\ndependencies = [\n \"b\",\n]\n", LockfileVersion::V2 },
+        v3 = { V3, LockfileVersion::V3 },
+        v4 = { "version = 4\n\n[[package]]\nname = \"a\"\nversion = \"0.1.0\"\n", LockfileVersion::V4 },
+    )]
+    fn detect_version(contents: &str, expected: LockfileVersion) {
+        assert_eq!(version_of(contents), expected);
+    }
+
+    #[yare::parameterized(
+        ancient = { (1, 20, 0), LockfileVersion::V1 },
+        v2 = { (1, 38, 0), LockfileVersion::V2 },
+        v3 = { (1, 53, 0), LockfileVersion::V3 },
+        v4 = { (1, 83, 0), LockfileVersion::V4 },
+    )]
+    fn newest_readable(version: (u64, u64, u64), expected: LockfileVersion) {
+        let version = semver::Version::new(version.0, version.1, version.2);
+
+        assert_eq!(LockfileVersion::newest_readable_by(&version), expected);
+    }
+
+    #[test]
+    fn translate_to_v2() {
+        let translated = translate_lockfile(V3, LockfileVersion::V2).unwrap();
+
+        assert!(!translated.contains("version = 3"));
+        assert_eq!(version_of(&translated), LockfileVersion::V2);
+    }
+
+    #[test]
+    fn translate_to_v1() {
+        assert_eq!(translate_lockfile(V3, LockfileVersion::V1).unwrap(), V1);
+    }
+
+    #[test]
+    fn translate_git_source_to_v3() {
+        let v4 = r#"version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "dep 0.1.0 (git+https://github.com/a/dep?branch=feature%2Fx#6f3b2a1)",
+]
+
+[[package]]
+name = "dep"
+version = "0.1.0"
+source = "git+https://github.com/a/dep?branch=feature%2Fx#6f3b2a1"
+"#;
+
+        let translated = translate_lockfile(v4, LockfileVersion::V3).unwrap();
+
+        assert_eq!(version_of(&translated), LockfileVersion::V3);
+        assert!(translated
+            .contains("source = \"git+https://github.com/a/dep?branch=feature/x#6f3b2a1\""));
+        assert!(translated
+            .contains("\"dep 0.1.0 (git+https://github.com/a/dep?branch=feature/x#6f3b2a1)\""));
+        assert!(!translated.contains("%2F"));
+    }
+
+    #[test]
+    fn generate_and_restore() {
+        let folder =
+            std::env::temp_dir().join(format!("cargo-msrv-lockfile-test-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let lockfile = folder.join(CARGO_LOCK);
+        std::fs::write(&lockfile, "original").unwrap();
+
+        // a failure to generate the lockfile moves the original back
+        let failed = LockfileHandler::with_replacement(&lockfile, CARGO_LOCK_ORIGINAL)
+            .move_lockfile()
+            .unwrap()
+            .generate_lockfile(|_| Err(CargoMSRVError::UnableToParseCliArgs));
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), "original");
+
+        let handle = LockfileHandler::with_replacement(&lockfile, CARGO_LOCK_ORIGINAL)
+            .move_lockfile()
+            .unwrap()
+            .generate_lockfile(|path| std::fs::write(path, "generated").map_err(From::from))
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), "generated");
+
+        handle.restore_lockfile().unwrap();
+        assert_eq!(std::fs::read_to_string(&lockfile).unwrap(), "original");
+        assert!(!folder.join(CARGO_LOCK_ORIGINAL).exists());
+
+        let _ = std::fs::remove_dir_all(folder);
+    }
+
+    #[test]
+    fn keep_earlier_format() {
+        assert_eq!(translate_lockfile(V1, LockfileVersion::V3).unwrap(), V1);
     }
 }
@@ -0,0 +1,798 @@
+//! Edits `Cargo.toml` manifests, while preserving their formatting and comments.
+//!
+//! The editor only understands as much of TOML as is needed to find the tables and keys of a
+//! document, and where their values are written. Everything else is kept as is.
+
+use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::{BareVersion, TomlMap};
+use crate::read_manifest;
+use crate::workspace::workspace_root;
+use decent_toml_rs_alternative::TomlValue;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// The first Rust version which understands the `package.rust-version` field.
+const FIRST_RUST_VERSION_FIELD: (u64, u64) = (1, 56);
+
+/// The field of a manifest in which the MSRV is written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MsrvField {
+    /// The `package.rust-version` field, understood by cargo since Rust 1.56.
+    RustVersion,
+    /// The `package.metadata.msrv` field, for crates with an MSRV below Rust 1.56.
+    MetadataMsrv,
+    /// The `workspace.package.rust-version` field of the workspace root, for crates which
+    /// inherit their `rust-version` from the workspace.
+    WorkspaceRustVersion,
+}
+
+impl MsrvField {
+    /// The path of the table which holds the field, and the key of the field.
+    fn location(self) -> (&'static [&'static str], &'static str) {
+        match self {
+            Self::RustVersion => (&["package"], "rust-version"),
+            Self::MetadataMsrv => (&["package", "metadata"], "msrv"),
+            Self::WorkspaceRustVersion => (&["workspace", "package"], "rust-version"),
+        }
+    }
+
+    /// The keys after which a field is inserted, when it's not yet in the table, in order of
+    /// preference. Without any of these, the field is added after the last key of the table.
+    fn insert_after(self) -> &'static [&'static str] {
+        match self {
+            Self::RustVersion | Self::WorkspaceRustVersion => &["edition", "version"],
+            Self::MetadataMsrv => &[],
+        }
+    }
+}
+
+impl fmt::Display for MsrvField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (table, key) = self.location();
+        write!(f, "{}.{}", table.join("."), key)
+    }
+}
+
+/// Writes the given MSRV to the manifest of the crate in the given folder, and returns the path
+/// of the manifest which was edited, and the field which was written.
+///
+/// A `rust-version` which is already declared is updated. Otherwise `package.rust-version` is
+/// added, unless the MSRV is below Rust 1.56, which doesn't understand it, in which case
+/// `package.metadata.msrv` is written instead. If the crate inherits its `rust-version` from its
+/// workspace, the `workspace.package.rust-version` of the workspace root is written.
+pub fn write_msrv(crate_root: &Path, version: &BareVersion) -> TResult<(PathBuf, MsrvField)> {
+    let manifest_path = crate_root.join("Cargo.toml");
+    let manifest = read_manifest(&manifest_path)?;
+
+    let field =
+        msrv_field(&manifest, version).ok_or_else(|| CargoMSRVError::UnableToWriteMSRV {
+            path: manifest_path.clone(),
+            reason: "the manifest has no [package] table".to_string(),
+        })?;
+
+    let manifest_path = match field {
+        MsrvField::WorkspaceRustVersion => workspace_root(crate_root, &manifest)?.ok_or_else(|| {
+            CargoMSRVError::UnableToWriteMSRV {
+                path: manifest_path.clone(),
+                reason:
+                    "the rust-version is inherited from the workspace, but no workspace root was found"
+                        .to_string(),
+            }
+        })?,
+        _ => manifest_path,
+    };
+
+    let to_error = |reason| CargoMSRVError::UnableToWriteMSRV {
+        path: manifest_path.clone(),
+        reason,
+    };
+
+    let contents = std::fs::read_to_string(&manifest_path)?;
+    let mut editor = ManifestEditor::new(&contents).map_err(to_error)?;
+
+    let (table, key) = field.location();
+    editor
+        .set_string(table, key, &version.to_string(), field.insert_after())
+        .map_err(to_error)?;
+
+    // the editor only understands part of TOML, so verify that it produced a valid manifest
+    decent_toml_rs_alternative::parse_toml(editor.contents()).map_err(CargoMSRVError::ParseToml)?;
+
+    std::fs::write(&manifest_path, editor.contents())?;
+
+    Ok((manifest_path, field))
+}
+
+/// Selects the field to which the given MSRV is written, or `None` if the manifest doesn't
+/// describe a package.
+fn msrv_field(manifest: &TomlMap, version: &BareVersion) -> Option<MsrvField> {
+    let package = match manifest.get("package") {
+        Some(package) => package,
+        // a virtual manifest may declare the rust-version inherited by its members
+        None => {
+            return manifest
+                .get("workspace")
+                .and_then(|workspace| workspace.get("package"))
+                .and_then(|package| package.get("rust-version"))
+                .map(|_| MsrvField::WorkspaceRustVersion)
+        }
+    };
+
+    match package.get("rust-version") {
+        Some(TomlValue::String(_)) => Some(MsrvField::RustVersion),
+        Some(rust_version) if is_inherited(rust_version) => Some(MsrvField::WorkspaceRustVersion),
+        _ if understands_rust_version(version) => Some(MsrvField::RustVersion),
+        _ => Some(MsrvField::MetadataMsrv),
+    }
+}
+
+/// Whether a field is inherited from the workspace, i.e. it's written as `field.workspace = true`.
+fn is_inherited(value: &TomlValue) -> bool {
+    matches!(value.get("workspace"), Some(TomlValue::Boolean(true)))
+}
+
+fn understands_rust_version(version: &BareVersion) -> bool {
+    let (major, minor) = match version {
+        BareVersion::TwoComponents(major, minor)
+        | BareVersion::ThreeComponents(major, minor, _) => (*major, *minor),
+    };
+
+    (major, minor) >= FIRST_RUST_VERSION_FIELD
+}
+
+/// A TOML document which can be edited without losing its formatting and comments.
+#[derive(Debug)]
+pub(crate) struct ManifestEditor {
+    contents: String,
+    headers: Vec<Header>,
+    entries: Vec<Entry>,
+}
+
+/// A `[table]` or `[[array-of-tables]]` header.
+#[derive(Debug)]
+struct Header {
+    path: Vec<String>,
+    array: bool,
+    // the end of the line of the header
+    end: usize,
+}
+
+/// A `key = value` pair.
+#[derive(Debug)]
+struct Entry {
+    // the header under which the pair is written, or `None` for the root table
+    header: Option<usize>,
+    // the dotted key, relative to the header
+    key: Vec<String>,
+    // the start of the line of the pair
+    line: usize,
+    // the start of the key
+    start: usize,
+    value: Range<usize>,
+    // the end of the line on which the value ends
+    end: usize,
+}
+
+impl ManifestEditor {
+    pub(crate) fn new(contents: &str) -> Result<Self, String> {
+        let mut editor = Self {
+            contents: contents.to_string(),
+            headers: Vec::new(),
+            entries: Vec::new(),
+        };
+        editor.parse()?;
+
+        Ok(editor)
+    }
+
+    pub(crate) fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Sets the given key of the given table to a string value. An existing value is replaced,
+    /// keeping the surrounding whitespace and comments. Otherwise, the key is inserted after the
+    /// first of the `after` keys the table contains, or after its last key, and the table is
+    /// created if it doesn't exist yet.
+    pub(crate) fn set_string(
+        &mut self,
+        table: &[&str],
+        key: &str,
+        value: &str,
+        after: &[&str],
+    ) -> Result<(), String> {
+        let mut path = table.to_vec();
+        path.push(key);
+
+        let value = quote(value);
+
+        if let Some(entry) = self.entries.iter().find(|entry| self.path(entry) == path) {
+            if self.contents[entry.value.clone()].contains('\n') {
+                return Err(format!(
+                    "the value of '{}' spans multiple lines",
+                    path.join(".")
+                ));
+            }
+
+            let range = entry.value.clone();
+            return self.edit(range, &value);
+        }
+
+        if let Some(entry) = self.entries.iter().find(|entry| {
+            let entry_path = self.path(entry);
+            entry_path.len() < path.len() && entry_path[..] == path[..entry_path.len()]
+        }) {
+            return Err(format!("'{}' is not a table", self.path(entry).join(".")));
+        }
+
+        // the table has a header of its own
+        if let Some(index) = self
+            .headers
+            .iter()
+            .position(|header| !header.array && header.path == table)
+        {
+            let entries = self
+                .entries
+                .iter()
+                .filter(|entry| entry.header == Some(index))
+                .collect::<Vec<_>>();
+
+            let anchor = after
+                .iter()
+                .find_map(|after| {
+                    entries
+                        .iter()
+                        .find(|entry| entry.key.len() == 1 && entry.key[0] == *after)
+                })
+                .or_else(|| entries.last());
+
+            return match anchor {
+                Some(anchor) => {
+                    let line = format!(
+                        "{}{} = {}\n",
+                        &self.contents[anchor.line..anchor.start],
+                        key_to_string(&[key]),
+                        value
+                    );
+                    let at = anchor.end;
+                    self.insert(at, &line)
+                }
+                None => {
+                    let at = self.headers[index].end;
+                    self.insert(at, &format!("{} = {}\n", key_to_string(&[key]), value))
+                }
+            };
+        }
+
+        // the table is defined by the dotted keys of a parent table
+        if let Some(entry) = self.entries.iter().rev().find(|entry| {
+            let entry_path = self.path(entry);
+            self.header_path(entry.header).len() < table.len()
+                && entry_path.len() > table.len()
+                && entry_path[..table.len()] == *table
+        }) {
+            let parent = self.header_path(entry.header).len();
+            let line = format!(
+                "{}{} = {}\n",
+                &self.contents[entry.line..entry.start],
+                key_to_string(&path[parent..]),
+                value
+            );
+            let at = entry.end;
+            return self.insert(at, &line);
+        }
+
+        // create the table after the tables of its parent, or else at the end of the document
+        let parent = &table[..table.len().saturating_sub(1)];
+        let at = (0..self.headers.len())
+            .filter(|index| {
+                let header = &self.headers[*index];
+                !parent.is_empty() && !header.array && starts_with(&header.path, parent)
+            })
+            .map(|index| self.end_of_table(index))
+            .max()
+            .unwrap_or(self.contents.len());
+
+        self.insert(
+            at,
+            &format!(
+                "\n[{}]\n{} = {}\n",
+                key_to_string(table),
+                key_to_string(&[key]),
+                value
+            ),
+        )
+    }
+
+    /// The full path of the key of the given entry.
+    fn path<'e>(&'e self, entry: &'e Entry) -> Vec<&'e str> {
+        let mut path = self.header_path(entry.header);
+        path.extend(entry.key.iter().map(String::as_str));
+        path
+    }
+
+    fn header_path(&self, header: Option<usize>) -> Vec<&str> {
+        match header {
+            Some(index) if !self.headers[index].array => self.headers[index]
+                .path
+                .iter()
+                .map(String::as_str)
+                .collect(),
+            // the keys of an array of tables can't be addressed
+            Some(_) => vec!["[]"],
+            None => Vec::new(),
+        }
+    }
+
+    /// The end of the last line of the given table.
+    fn end_of_table(&self, index: usize) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.header == Some(index))
+            .map(|entry| entry.end)
+            .max()
+            .unwrap_or(self.headers[index].end)
+    }
+
+    fn insert(&mut self, at: usize, text: &str) -> Result<(), String> {
+        if at > 0 && !self.contents[..at].ends_with('\n') {
+            self.edit(at..at, &format!("\n{}", text))
+        } else {
+            self.edit(at..at, text)
+        }
+    }
+
+    fn edit(&mut self, range: Range<usize>, text: &str) -> Result<(), String> {
+        self.contents.replace_range(range, text);
+        self.parse()
+    }
+
+    fn parse(&mut self) -> Result<(), String> {
+        let mut scanner = Scanner {
+            input: self.contents.as_bytes(),
+            at: 0,
+        };
+        self.headers.clear();
+        self.entries.clear();
+
+        loop {
+            scanner.skip_blank_lines();
+
+            match scanner.peek() {
+                None => return Ok(()),
+                Some(b'[') => {
+                    scanner.at += 1;
+                    let array = scanner.eat(b'[');
+                    let path = scanner.key()?;
+                    scanner.expect(b']')?;
+                    if array {
+                        scanner.expect(b']')?;
+                    }
+                    scanner.end_of_line()?;
+
+                    self.headers.push(Header {
+                        path,
+                        array,
+                        end: scanner.at,
+                    });
+                }
+                Some(_) => {
+                    let line = scanner.line_start();
+                    let start = scanner.at;
+                    let key = scanner.key()?;
+                    scanner.expect(b'=')?;
+                    scanner.skip_whitespace();
+                    let value_start = scanner.at;
+                    scanner.value()?;
+                    let value = value_start..scanner.at;
+                    scanner.end_of_line()?;
+
+                    self.entries.push(Entry {
+                        header: self.headers.len().checked_sub(1),
+                        key,
+                        line,
+                        start,
+                        value,
+                        end: scanner.at,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn starts_with(path: &[String], prefix: &[&str]) -> bool {
+    path.len() >= prefix.len() && path.iter().zip(prefix).all(|(key, prefix)| key == prefix)
+}
+
+/// Writes a string value as a TOML basic string.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes a dotted key, quoting the keys which aren't bare keys.
+fn key_to_string<S: AsRef<str>>(keys: &[S]) -> String {
+    keys.iter()
+        .map(|key| {
+            let key = key.as_ref();
+            if !key.is_empty() && key.bytes().all(is_bare_key_byte) {
+                key.to_string()
+            } else {
+                quote(key)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn is_bare_key_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-'
+}
+
+/// Scans over the parts of a TOML document. Since all of the TOML syntax is ASCII, the document is
+/// scanned by byte.
+struct Scanner<'i> {
+    input: &'i [u8],
+    at: usize,
+}
+
+impl<'i> Scanner<'i> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.at).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", byte as char)))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        let line = self.input[..self.at]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count()
+            + 1;
+        match self.peek() {
+            Some(byte) => format!(
+                "expected {} on line {}, but found '{}'",
+                expected, line, byte as char
+            ),
+            None => format!("expected {} on line {}, but the file ended", expected, line),
+        }
+    }
+
+    fn line_start(&self) -> usize {
+        self.input[..self.at]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |newline| newline + 1)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.at += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some(b'#') {
+            while !matches!(self.peek(), None | Some(b'\n')) {
+                self.at += 1;
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines and comments.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            if !(self.eat(b'\n') || self.eat(b'\r')) {
+                return;
+            }
+        }
+    }
+
+    /// Skips the rest of a line, which may only contain a comment.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        self.skip_comment();
+        self.eat(b'\r');
+        if self.eat(b'\n') || self.peek().is_none() {
+            Ok(())
+        } else {
+            Err(self.unexpected("the end of the line"))
+        }
+    }
+
+    /// Scans a dotted key, and returns its keys without quotes.
+    fn key(&mut self) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some(quote @ b'"') | Some(quote @ b'\'') => {
+                    let start = self.at;
+                    self.string(quote)?;
+                    unescape(&self.input[start + 1..self.at - 1], quote)
+                }
+                _ => {
+                    let start = self.at;
+                    while matches!(self.peek(), Some(byte) if is_bare_key_byte(byte)) {
+                        self.at += 1;
+                    }
+                    if start == self.at {
+                        return Err(self.unexpected("a key"));
+                    }
+                    String::from_utf8_lossy(&self.input[start..self.at]).into_owned()
+                }
+            };
+            keys.push(key);
+
+            self.skip_whitespace();
+            if !self.eat(b'.') {
+                return Ok(keys);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Some(quote @ b'"') | Some(quote @ b'\'') => self.string(quote),
+            Some(b'[') => {
+                self.at += 1;
+                loop {
+                    self.skip_blank_lines();
+                    if self.eat(b']') {
+                        return Ok(());
+                    }
+                    self.value()?;
+                    self.skip_blank_lines();
+                    if !self.eat(b',') {
+                        self.skip_blank_lines();
+                        return self.expect(b']');
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.at += 1;
+                self.skip_whitespace();
+                if self.eat(b'}') {
+                    return Ok(());
+                }
+                loop {
+                    self.key()?;
+                    self.expect(b'=')?;
+                    self.skip_whitespace();
+                    self.value()?;
+                    self.skip_whitespace();
+                    if !self.eat(b',') {
+                        return self.expect(b'}');
+                    }
+                }
+            }
+            _ => {
+                // numbers, booleans and dates, where a date and a time may be separated by a space
+                let start = self.at;
+                while let Some(byte) = self.peek() {
+                    let date_time_separator = byte == b' '
+                        && self.at - start == 10
+                        && self.input[start..self.at].contains(&b'-')
+                        && matches!(self.input.get(self.at + 1), Some(next) if next.is_ascii_digit());
+
+                    if !(byte.is_ascii_alphanumeric()
+                        || b"+-_.:".contains(&byte)
+                        || date_time_separator)
+                    {
+                        break;
+                    }
+                    self.at += 1;
+                }
+
+                if start == self.at {
+                    Err(self.unexpected("a value"))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Scans a basic or literal string, which may be a multi-line string.
+    fn string(&mut self, quote: u8) -> Result<(), String> {
+        let multi_line = self.input[self.at..].starts_with(&[quote; 3]);
+        self.at += if multi_line { 3 } else { 1 };
+
+        loop {
+            match self.peek() {
+                None => return Err(self.unexpected("the end of the string")),
+                Some(b'\\') if quote == b'"' => self.at += 2,
+                Some(b'\n') if !multi_line => return Err(self.unexpected("the end of the string")),
+                Some(byte) if byte == quote => {
+                    if !multi_line {
+                        self.at += 1;
+                        return Ok(());
+                    }
+                    if self.input[self.at..].starts_with(&[quote; 3]) {
+                        self.at += 3;
+                        // up to two quotes may precede the closing quotes
+                        let mut extra = 0;
+                        while extra < 2 && self.eat(quote) {
+                            extra += 1;
+                        }
+                        return Ok(());
+                    }
+                    self.at += 1;
+                }
+                Some(_) => self.at += 1,
+            }
+        }
+    }
+}
+
+/// Removes the escapes of a quoted key. Only the escapes of quotes and backslashes are expected in
+/// the keys of a manifest.
+fn unescape(key: &[u8], quote: u8) -> String {
+    let key = String::from_utf8_lossy(key);
+    if quote == b'"' {
+        key.replace("\\\"", "\"").replace("\\\\", "\\")
+    } else {
+        key.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use yare::parameterized;
+
+    fn set(contents: &str, field: MsrvField, version: &str) -> String {
+        let mut editor = ManifestEditor::new(contents).unwrap();
+        let (table, key) = field.location();
+        editor
+            .set_string(table, key, version, field.insert_after())
+            .unwrap();
+
+        decent_toml_rs_alternative::parse_toml(editor.contents()).unwrap();
+        editor.contents().to_string()
+    }
+
+    #[parameterized(
+        update_keeps_comments = {
+            "# the package\n[package]\nname = \"a\" # the name\nrust-version  =  \"1.40\"  # the MSRV\n",
+            MsrvField::RustVersion,
+            "# the package\n[package]\nname = \"a\" # the name\nrust-version  =  \"1.58.1\"  # the MSRV\n",
+        },
+        insert_after_edition = {
+            "[package]\nname = \"a\"\nedition = \"2021\"\nauthors = []\n\n[dependencies]\n",
+            MsrvField::RustVersion,
+            "[package]\nname = \"a\"\nedition = \"2021\"\nrust-version = \"1.58.1\"\nauthors = []\n\n[dependencies]\n",
+        },
+        insert_after_version = {
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\nauthors = []\n",
+            MsrvField::RustVersion,
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\nrust-version = \"1.58.1\"\nauthors = []\n",
+        },
+        insert_after_last_key = {
+            "[package]\nname = \"a\"\nauthors = [\n    \"a\", # first\n    \"b\",\n]\n\n# deps\n[dependencies]\n",
+            MsrvField::RustVersion,
+            "[package]\nname = \"a\"\nauthors = [\n    \"a\", # first\n    \"b\",\n]\nrust-version = \"1.58.1\"\n\n# deps\n[dependencies]\n",
+        },
+        insert_without_trailing_newline = {
+            "[package]\nname = \"a\"",
+            MsrvField::RustVersion,
+            "[package]\nname = \"a\"\nrust-version = \"1.58.1\"\n",
+        },
+        insert_keeps_indentation = {
+            "[package]\n  name = \"a\"\n  edition = \"2021\"\n",
+            MsrvField::RustVersion,
+            "[package]\n  name = \"a\"\n  edition = \"2021\"\n  rust-version = \"1.58.1\"\n",
+        },
+        skip_multi_line_strings = {
+            "[package]\ndescription = \"\"\"\n[workspace]\nrust-version = 1\n\"\"\"\n",
+            MsrvField::RustVersion,
+            "[package]\ndescription = \"\"\"\n[workspace]\nrust-version = 1\n\"\"\"\nrust-version = \"1.58.1\"\n",
+        },
+        update_quoted_key = {
+            "[\"package\"]\n\"rust-version\" = '1.40'\n",
+            MsrvField::RustVersion,
+            "[\"package\"]\n\"rust-version\" = \"1.58.1\"\n",
+        },
+        update_dotted_key = {
+            "package.name = \"a\"\npackage.rust-version = \"1.40\"\n",
+            MsrvField::RustVersion,
+            "package.name = \"a\"\npackage.rust-version = \"1.58.1\"\n",
+        },
+        update_metadata = {
+            "[package]\nname = \"a\"\n\n[package.metadata]\nmsrv = \"1.40.0\" # keep\n",
+            MsrvField::MetadataMsrv,
+            "[package]\nname = \"a\"\n\n[package.metadata]\nmsrv = \"1.58.1\" # keep\n",
+        },
+        insert_into_metadata = {
+            "[package]\nname = \"a\"\n\n[package.metadata]\nfoo = 1\n\n[dependencies]\n",
+            MsrvField::MetadataMsrv,
+            "[package]\nname = \"a\"\n\n[package.metadata]\nfoo = 1\nmsrv = \"1.58.1\"\n\n[dependencies]\n",
+        },
+        insert_into_dotted_metadata = {
+            "[package]\nname = \"a\"\nmetadata.foo = 1\n\n[dependencies]\n",
+            MsrvField::MetadataMsrv,
+            "[package]\nname = \"a\"\nmetadata.foo = 1\nmetadata.msrv = \"1.58.1\"\n\n[dependencies]\n",
+        },
+        insert_into_empty_table = {
+            "[package]\nname = \"a\"\n\n[package.metadata] # ours\n\n[dependencies]\n",
+            MsrvField::MetadataMsrv,
+            "[package]\nname = \"a\"\n\n[package.metadata] # ours\nmsrv = \"1.58.1\"\n\n[dependencies]\n",
+        },
+        create_metadata = {
+            "[package]\nname = \"a\"\n\n[package.metadata.docs.rs]\nall-features = true\n\n[dependencies]\nfoo = \"1\"\n",
+            MsrvField::MetadataMsrv,
+            "[package]\nname = \"a\"\n\n[package.metadata.docs.rs]\nall-features = true\n\n[package.metadata]\nmsrv = \"1.58.1\"\n\n[dependencies]\nfoo = \"1\"\n",
+        },
+        create_workspace_package = {
+            "[workspace]\nmembers = [\"a\"]\n\n[[bin]]\nname = \"b\"\n",
+            MsrvField::WorkspaceRustVersion,
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.package]\nrust-version = \"1.58.1\"\n\n[[bin]]\nname = \"b\"\n",
+        },
+        update_workspace_package = {
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.package]\nversion = \"0.1.0\"\nrust-version = \"1.40\"\n",
+            MsrvField::WorkspaceRustVersion,
+            "[workspace]\nmembers = [\"a\"]\n\n[workspace.package]\nversion = \"0.1.0\"\nrust-version = \"1.58.1\"\n",
+        },
+    )]
+    fn set_string(contents: &str, field: MsrvField, expected: &str) {
+        assert_eq!(set(contents, field, "1.58.1"), expected);
+    }
+
+    #[parameterized(
+        inline_table = { "[package]\nmetadata = { foo = 1 }\n" },
+        multi_line_value = { "[package.metadata]\nmsrv = \"\"\"\n1.40\"\"\"\n" },
+    )]
+    fn set_string_fails(contents: &str) {
+        let mut editor = ManifestEditor::new(contents).unwrap();
+        let (table, key) = MsrvField::MetadataMsrv.location();
+
+        assert!(editor.set_string(table, key, "1.40.0", &[]).is_err());
+    }
+
+    #[parameterized(
+        unterminated_string = { "[package]\nname = \"a\n" },
+        unterminated_header = { "[package\nname = \"a\"\n" },
+        missing_value = { "[package]\nname =\n" },
+        trailing_tokens = { "[package]\nname = \"a\" \"b\"\n" },
+    )]
+    fn invalid_documents(contents: &str) {
+        assert!(ManifestEditor::new(contents).is_err());
+    }
+
+    #[parameterized(
+        declared = { "[package]\nrust-version = \"1.60\"\n", "1.40.0", Some(MsrvField::RustVersion) },
+        supported = { "[package]\nname = \"a\"\n", "1.56", Some(MsrvField::RustVersion) },
+        not_supported = { "[package]\nname = \"a\"\n", "1.55.0", Some(MsrvField::MetadataMsrv) },
+        metadata_declared = { "[package.metadata]\nmsrv = \"1.40.0\"\n", "1.45.0", Some(MsrvField::MetadataMsrv) },
+        inherited = { "[package]\nrust-version.workspace = true\n", "1.60.0", Some(MsrvField::WorkspaceRustVersion) },
+        inherited_inline = { "[package]\nrust-version = { workspace = true }\n", "1.40.0", Some(MsrvField::WorkspaceRustVersion) },
+        virtual_manifest = { "[workspace.package]\nrust-version = \"1.60\"\n", "1.60.0", Some(MsrvField::WorkspaceRustVersion) },
+        no_package = { "[workspace]\nmembers = []\n", "1.60.0", None },
+    )]
+    fn select_msrv_field(contents: &str, version: &str, expected: Option<MsrvField>) {
+        let manifest = decent_toml_rs_alternative::parse_toml(contents).unwrap();
+        let version = BareVersion::try_from(version).unwrap();
+
+        assert_eq!(msrv_field(&manifest, &version), expected);
+    }
+}
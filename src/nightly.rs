@@ -0,0 +1,199 @@
+//! Nightly toolchains, identified by the date on which they were published.
+//!
+//! To search over nightlies with the same machinery as over stable releases, each nightly is
+//! represented by the semver version `1.M.0-nightly+YYYY-MM-DD`, where `1.M` is the (approximate)
+//! version of the Rust compiler at that date. These versions order the same way as their dates.
+
+use crate::errors::{CargoMSRVError, TResult};
+use rust_releases::{semver, Release, ReleaseIndex};
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const NIGHTLY: &str = "nightly";
+
+/// The release date of Rust 1.0.0; we don't search for earlier nightlies.
+pub const FIRST_NIGHTLY: NightlyDate = NightlyDate {
+    year: 2015,
+    month: 5,
+    day: 15,
+};
+
+/// A new stable version is released every six weeks.
+const DAYS_PER_RELEASE: i64 = 42;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct NightlyDate {
+    year: i64,
+    month: i64,
+    day: i64,
+}
+
+impl NightlyDate {
+    /// Parses a date formatted as `YYYY-MM-DD`.
+    pub fn parse(input: &str) -> TResult<Self> {
+        let invalid = || CargoMSRVError::InvalidNightlyDate(input.to_string());
+
+        let parts = input
+            .split('-')
+            .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+            .collect::<TResult<Vec<_>>>()?;
+
+        let date = match parts.as_slice() {
+            [year, month, day] => Self {
+                year: *year,
+                month: *month,
+                day: *day,
+            },
+            _ => return Err(invalid()),
+        };
+
+        // days which don't exist, like February 30th, don't survive the round trip
+        if date.month < 1 || date.month > 12 || Self::from_days(date.days()) != date {
+            return Err(invalid());
+        }
+
+        Ok(date)
+    }
+
+    /// The most recent nightly which is certainly published: the one from yesterday (UTC).
+    pub fn latest() -> Self {
+        let today = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() / 86_400)
+            .unwrap_or_default() as i64;
+
+        Self::from_days(today - 1)
+    }
+
+    /// The nightly represented by the given version, if it represents a nightly.
+    pub fn from_version(version: &semver::Version) -> Option<Self> {
+        if version.pre.as_str() != NIGHTLY {
+            return None;
+        }
+
+        Self::parse(version.build.as_str()).ok()
+    }
+
+    pub fn to_version(&self) -> semver::Version {
+        let minor = (self.days() - FIRST_NIGHTLY.days()).max(0) / DAYS_PER_RELEASE + 2;
+
+        semver::Version {
+            major: 1,
+            minor: minor as u64,
+            patch: 0,
+            pre: semver::Prerelease::new(NIGHTLY).expect("'nightly' is a valid pre-release"),
+            build: semver::BuildMetadata::new(&self.to_string())
+                .expect("A date is valid build metadata"),
+        }
+    }
+
+    // The number of days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    fn days(&self) -> i64 {
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = if year >= 0 { year } else { year - 399 } / 400;
+        let year_of_era = year - era * 400;
+        let month = if self.month > 2 {
+            self.month - 3
+        } else {
+            self.month + 9
+        };
+        let day_of_year = (153 * month + 2) / 5 + self.day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self { year, month, day }
+    }
+}
+
+impl Display for NightlyDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The name of the toolchain channel of a version, e.g. `1.56.0` or `nightly-2021-10-01`.
+pub fn toolchain_channel(version: &semver::Version) -> String {
+    match NightlyDate::from_version(version) {
+        Some(date) => format!("{}-{}", NIGHTLY, date),
+        None => version.to_string(),
+    }
+}
+
+/// An index of a nightly release for each day from `min` up to and including `max`, ordered from
+/// the most recent to the least recent nightly.
+pub fn nightly_index(min: NightlyDate, max: NightlyDate) -> ReleaseIndex {
+    (min.days()..=max.days())
+        .rev()
+        .map(|days| Release::new_stable(NightlyDate::from_days(days).to_version()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[yare::parameterized(
+        first_nightly = { "2015-05-15", Some((2015, 5, 15)) },
+        leap_day = { "2020-02-29", Some((2020, 2, 29)) },
+        no_leap_day = { "2021-02-29", None },
+        month_out_of_range = { "2021-13-01", None },
+        not_a_date = { "1.56.0", None },
+        missing_day = { "2021-10", None },
+    )]
+    fn parse(input: &str, expected: Option<(i64, i64, i64)>) {
+        let date = NightlyDate::parse(input).ok();
+        let expected = expected.map(|(year, month, day)| NightlyDate { year, month, day });
+
+        assert_eq!(date, expected);
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(NightlyDate::parse("1970-01-01").unwrap().days(), 0);
+        assert_eq!(NightlyDate::parse("2021-10-21").unwrap().days(), 18_921);
+        assert_eq!(NightlyDate::from_days(18_921).to_string(), "2021-10-21");
+    }
+
+    #[test]
+    fn version_round_trip() {
+        let date = NightlyDate::parse("2021-10-01").unwrap();
+        let version = date.to_version();
+
+        assert_eq!(version.to_string(), "1.57.0-nightly+2021-10-01");
+        assert_eq!(NightlyDate::from_version(&version), Some(date));
+        assert_eq!(toolchain_channel(&version), "nightly-2021-10-01");
+        assert_eq!(toolchain_channel(&semver::Version::new(1, 56, 0)), "1.56.0");
+    }
+
+    #[test]
+    fn versions_order_by_date() {
+        let index = nightly_index(
+            NightlyDate::parse("2021-09-20").unwrap(),
+            NightlyDate::parse("2021-10-10").unwrap(),
+        );
+
+        assert_eq!(index.releases().len(), 21);
+        assert!(index
+            .releases()
+            .windows(2)
+            .all(|pair| pair[0].version() > pair[1].version()));
+    }
+}
@@ -0,0 +1,214 @@
+//! A release index built from a local file, so the MSRV can be determined without network access.
+//!
+//! The file may be one of:
+//! * a saved copy of the Rust changelog (`RELEASES.md`), in which each release starts with a
+//!   `Version 1.56.0 (2021-10-21)` line;
+//! * a saved listing of the Rust distribution server, in which each stable release shows up as
+//!   `dist/rustc-1.56.0-<target>...`;
+//! * a JSON list of versions (`["1.56.0", "1.55.0"]`, or `{ "versions": [...] }`), if the file has
+//!   the `json` extension;
+//! * a TOML list of versions (`versions = ["1.56.0", "1.55.0"]`), if the file has the `toml`
+//!   extension.
+
+use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::BareVersion;
+use crate::nightly::NightlyDate;
+use decent_toml_rs_alternative::TomlValue;
+use rust_releases::{semver, Release, ReleaseIndex};
+use std::convert::TryFrom;
+use std::path::Path;
+
+const CHANGELOG_PREFIX: &str = "Version ";
+const DIST_PREFIX: &str = "dist/rustc-";
+
+/// Builds a release index of the stable releases listed in the given file, ordered from the most
+/// recent to the least recent release.
+pub fn release_index_from_file(path: &Path) -> TResult<ReleaseIndex> {
+    let invalid = |reason: String| CargoMSRVError::InvalidReleaseFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let contents = std::fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+
+    let versions = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => versions_from_json(&contents),
+        Some("toml") => versions_from_toml(&contents),
+        _ => Ok(versions_from_text(&contents, &NightlyDate::latest())),
+    }
+    .map_err(invalid)?;
+
+    if versions.is_empty() {
+        return Err(invalid("no stable releases are listed".to_string()));
+    }
+
+    Ok(into_index(versions))
+}
+
+fn into_index(mut versions: Vec<semver::Version>) -> ReleaseIndex {
+    versions.sort_by(|lhs, rhs| rhs.cmp(lhs));
+    versions.dedup();
+
+    versions.into_iter().map(Release::new_stable).collect()
+}
+
+fn parse_version(version: &str) -> Result<semver::Version, String> {
+    match BareVersion::try_from(version.trim()) {
+        Ok(BareVersion::TwoComponents(major, minor)) => Ok(semver::Version::new(major, minor, 0)),
+        Ok(BareVersion::ThreeComponents(major, minor, patch)) => {
+            Ok(semver::Version::new(major, minor, patch))
+        }
+        Err(_) => Err(format!("'{}' is not a stable Rust version", version)),
+    }
+}
+
+fn versions_from_json(contents: &str) -> Result<Vec<semver::Version>, String> {
+    let document = json::parse(contents).map_err(|err| err.to_string())?;
+
+    let list = if document.is_array() {
+        &document
+    } else {
+        &document["versions"]
+    };
+
+    if !list.is_array() {
+        return Err("expected a list of versions, or an object with a 'versions' list".to_string());
+    }
+
+    list.members()
+        .map(|version| {
+            version
+                .as_str()
+                .ok_or_else(|| format!("expected a version, but found '{}'", version))
+                .and_then(parse_version)
+        })
+        .collect()
+}
+
+fn versions_from_toml(contents: &str) -> Result<Vec<semver::Version>, String> {
+    let document =
+        decent_toml_rs_alternative::parse_toml(contents).map_err(|err| format!("{:?}", err))?;
+
+    match document.get("versions") {
+        Some(TomlValue::Array(versions)) => versions
+            .iter()
+            .map(|version| match version {
+                TomlValue::String(version) => parse_version(version),
+                other => Err(format!("expected a version, but found '{:?}'", other)),
+            })
+            .collect(),
+        _ => Err("expected a 'versions' list".to_string()),
+    }
+}
+
+// Collects the versions in both the changelog and distribution listing formats. Releases which the
+// changelog dates after `latest` are not yet published, and are left out.
+fn versions_from_text(contents: &str, latest: &NightlyDate) -> Vec<semver::Version> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            if let Some(release) = line.strip_prefix(CHANGELOG_PREFIX) {
+                changelog_release(release, latest)
+            } else if let Some(release) = line.strip_prefix(DIST_PREFIX) {
+                dist_release(release)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// e.g. `1.56.0 (2021-10-21)`; releases without a date, like 0.9, are skipped
+fn changelog_release(release: &str, latest: &NightlyDate) -> Option<semver::Version> {
+    let mut parts = release.split_ascii_whitespace();
+    let version = semver::Version::parse(parts.next()?).ok()?;
+    let date = parts.next()?.trim_start_matches('(').trim_end_matches(')');
+
+    match NightlyDate::parse(date) {
+        Ok(date) if date <= *latest => Some(version),
+        _ => None,
+    }
+}
+
+// e.g. `1.56.0-x86_64-unknown-linux-gnu.tar.gz`, but not `1.56.0-beta.1-x86_64-unknown-linux-gnu.tar.gz`
+fn dist_release(release: &str) -> Option<semver::Version> {
+    let end = release
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(release.len());
+    let (version, rest) = release.split_at(end);
+    let version = version.trim_end_matches('.');
+
+    let pre_release = ["-alpha", "-beta", "-nightly"]
+        .iter()
+        .any(|channel| rest.starts_with(channel));
+
+    if pre_release || version.split('.').count() != 3 {
+        return None;
+    }
+
+    semver::Version::parse(version).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(index: ReleaseIndex) -> Vec<String> {
+        index
+            .releases()
+            .iter()
+            .map(|release| release.version().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn changelog() {
+        let changelog = "Version 1.57.0 (2099-12-02)\n==========================\n\nVersion 1.56.1 (2021-11-01)\n===\n\n- fix\n\nVersion 1.56.0 (2021-10-21)\n\nVersion 0.9 (2014-01-09)\n";
+        let latest = NightlyDate::parse("2021-11-17").unwrap();
+
+        assert_eq!(
+            versions(into_index(versions_from_text(changelog, &latest))),
+            vec!["1.56.1", "1.56.0"]
+        );
+    }
+
+    #[test]
+    fn dist_listing() {
+        let listing = "dist/rustc-1.55.0-x86_64-unknown-linux-gnu.tar.gz\ndist/rustc-1.56.0-beta.1-x86_64-unknown-linux-gnu.tar.gz\ndist/rustc-1.56.0-src.tar.gz\ndist/rustc-1.56.0.tar.gz.sha256\ndist/cargo-1.57.0-x86_64-unknown-linux-gnu.tar.gz\n";
+        let latest = NightlyDate::parse("2021-11-17").unwrap();
+
+        assert_eq!(
+            versions(into_index(versions_from_text(listing, &latest))),
+            vec!["1.56.0", "1.55.0"]
+        );
+    }
+
+    #[yare::parameterized(
+        list = { r#"["1.54.0", "1.56", "1.55.1"]"# },
+        object = { r#"{ "versions": ["1.55.1", "1.54.0", "1.56"] }"# },
+    )]
+    fn json_list(contents: &str) {
+        assert_eq!(
+            versions(into_index(versions_from_json(contents).unwrap())),
+            vec!["1.56.0", "1.55.1", "1.54.0"]
+        );
+    }
+
+    #[yare::parameterized(
+        not_a_list = { r#"{ "releases": [] }"# },
+        not_a_version = { r#"["1.56.0-beta.1"]"# },
+    )]
+    fn invalid_json_list(contents: &str) {
+        assert!(versions_from_json(contents).is_err());
+    }
+
+    #[test]
+    fn toml_list() {
+        let contents = "versions = [\"1.54.0\", \"1.56.0\"]\n";
+
+        assert_eq!(
+            versions(into_index(versions_from_toml(contents).unwrap())),
+            vec!["1.56.0", "1.54.0"]
+        );
+    }
+}
@@ -2,8 +2,16 @@ use json::object;
 use std::cell::Cell;
 
 use crate::config::ModeIntent;
-use crate::reporter::ProgressAction;
+use crate::dependencies::{BlockingDependency, DependencyMsrv};
+use crate::diagnostics::Diagnostics;
+use crate::downgrade::PinnedPackage;
+use crate::failure::Failure;
+use crate::manifest::BareVersion;
+use crate::manifest_editor::MsrvField;
+use crate::reporter::{ProgressAction, Scope};
 use rust_releases::semver;
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct JsonPrinter<'s, 't> {
@@ -23,10 +31,77 @@ impl<'s, 't> JsonPrinter<'s, 't> {
         }
     }
 
+    fn check_complete(&self, version: &semver::Version, success: bool, cached: bool) {
+        println!(
+            "{}",
+            object! {
+                reason: "check-complete",
+                version: version.to_string(),
+                step: self.finished.get(),
+                total_steps: self.steps.get(),
+                success: success,
+                cached: cached,
+                toolchain: self.toolchain,
+                check_cmd: self.cmd,
+            }
+        );
+        self.finished.set(self.finished.get() + 1);
+    }
+
+    fn scope_reason(scope: Scope) -> &'static str {
+        match scope {
+            Scope::FeatureSet(_) => "feature-set",
+            Scope::WorkspaceMember(_) => "workspace-member",
+            Scope::Target(_) => "target",
+            Scope::Resolution(_) => "resolution",
+        }
+    }
+
+    fn scope_object(scope: Scope) -> json::JsonValue {
+        match scope {
+            Scope::FeatureSet(set) => object! {
+                features: set.features(),
+            },
+            Scope::WorkspaceMember(name) => object! {
+                member: name,
+            },
+            Scope::Target(target) => object! {
+                target: target,
+            },
+            Scope::Resolution(resolution) => object! {
+                resolution: resolution,
+            },
+        }
+    }
+
+    fn failed_below(version: &semver::Version, diagnostics: &Diagnostics) -> json::JsonValue {
+        let errors = diagnostics
+            .errors()
+            .iter()
+            .map(|error| {
+                object! {
+                    message: error.message(),
+                    code: error.code(),
+                    file: error.file(),
+                    line: error.line(),
+                    column: error.column(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        object! {
+            version: version.to_string(),
+            errors: errors,
+        }
+    }
+
     fn complete_reason(&self, mode: ModeIntent) -> &'static str {
         match mode {
             ModeIntent::DetermineMSRV => "msrv-complete",
             ModeIntent::VerifyMSRV => "verify-complete",
+            ModeIntent::GenerateLockfile => "lockfile-complete",
+            ModeIntent::ListDependencies => "list-complete",
+            ModeIntent::SetMSRV => "set-complete",
         }
     }
 }
@@ -80,14 +155,22 @@ impl<'s, 't> crate::Output for JsonPrinter<'s, 't> {
     }
 
     fn complete_step(&self, version: &semver::Version, success: bool) {
+        self.check_complete(version, success, false);
+    }
+
+    fn complete_cached_step(&self, version: &semver::Version, success: bool) {
+        self.check_complete(version, success, true);
+    }
+
+    fn complete_timed_out_step(&self, version: &semver::Version, timeout: Duration) {
         println!(
             "{}",
             object! {
-                reason: "check-complete",
+                reason: "check-timed-out",
                 version: version.to_string(),
                 step: self.finished.get(),
                 total_steps: self.steps.get(),
-                success: success,
+                timeout: timeout.as_secs_f64(),
                 toolchain: self.toolchain,
                 check_cmd: self.cmd,
             }
@@ -95,19 +178,44 @@ impl<'s, 't> crate::Output for JsonPrinter<'s, 't> {
         self.finished.set(self.finished.get() + 1);
     }
 
-    fn finish_success(&self, mode: ModeIntent, version: &semver::Version) {
-        let reason = self.complete_reason(mode);
-
+    fn retry_check(&self, version: &semver::Version, failure: Failure, attempt: u32, retries: u32) {
         println!(
             "{}",
             object! {
-                reason: reason,
-                success: true,
-                msrv: version.to_string(),
+                reason: "check-retry",
+                version: version.to_string(),
+                step: self.finished.get(),
+                total_steps: self.steps.get(),
+                cause: failure.to_string(),
+                attempt: attempt,
+                retries: retries,
                 toolchain: self.toolchain,
                 check_cmd: self.cmd,
             }
-        )
+        );
+    }
+
+    fn finish_success(
+        &self,
+        mode: ModeIntent,
+        version: &semver::Version,
+        failed_below: Option<(&semver::Version, &Diagnostics)>,
+    ) {
+        let reason = self.complete_reason(mode);
+
+        let mut event = object! {
+            reason: reason,
+            success: true,
+            msrv: version.to_string(),
+            toolchain: self.toolchain,
+            check_cmd: self.cmd,
+        };
+
+        if let Some((version, diagnostics)) = failed_below {
+            let _ = event.insert("failed_below", Self::failed_below(version, diagnostics));
+        }
+
+        println!("{}", event)
     }
 
     fn finish_failure(&self, mode: ModeIntent, _: &str) {
@@ -123,4 +231,144 @@ impl<'s, 't> crate::Output for JsonPrinter<'s, 't> {
             }
         );
     }
+
+    fn blocking_dependencies(
+        &self,
+        version: &semver::Version,
+        dependencies: &[BlockingDependency],
+    ) {
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| {
+                object! {
+                    name: dependency.name(),
+                    version: dependency.version(),
+                    rust_version: dependency.rust_version().map(ToString::to_string),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        println!(
+            "{}",
+            object! {
+                reason: "blocking-dependencies",
+                version: version.to_string(),
+                dependencies: dependencies,
+            }
+        );
+    }
+
+    fn lockfile_generated(&self, lockfile: &Path, pinned: &[PinnedPackage]) {
+        let pinned = pinned
+            .iter()
+            .map(|package| {
+                object! {
+                    name: package.name(),
+                    from: package.from().to_string(),
+                    to: package.to().to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        println!(
+            "{}",
+            object! {
+                reason: "lockfile-generated",
+                lockfile: lockfile.display().to_string(),
+                pinned: pinned,
+            }
+        );
+    }
+
+    fn dependency_msrvs(&self, lockfile: &Path, dependencies: &[DependencyMsrv]) {
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| {
+                object! {
+                    name: dependency.name(),
+                    version: dependency.version(),
+                    rust_version: dependency.rust_version().map(ToString::to_string),
+                    manifest: dependency.manifest().map(|manifest| manifest.display().to_string()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        println!(
+            "{}",
+            object! {
+                reason: "dependency-msrvs",
+                lockfile: lockfile.display().to_string(),
+                dependencies: dependencies,
+            }
+        );
+    }
+
+    fn msrv_written(&self, manifest: &Path, field: MsrvField, version: &BareVersion) {
+        println!(
+            "{}",
+            object! {
+                reason: "msrv-written",
+                manifest: manifest.display().to_string(),
+                field: field.to_string(),
+                msrv: version.to_string(),
+            }
+        );
+    }
+
+    fn finish_not_minimal(&self, msrv: &semver::Version, compatible: &semver::Version) {
+        println!(
+            "{}",
+            object! {
+                reason: self.complete_reason(ModeIntent::VerifyMSRV),
+                success: false,
+                msrv: msrv.to_string(),
+                compatible_below_msrv: compatible.to_string(),
+                toolchain: self.toolchain,
+                check_cmd: self.cmd,
+            }
+        );
+    }
+
+    fn scope(&self, scope: Scope) {
+        let mut event = Self::scope_object(scope);
+        let _ = event.insert("reason", Self::scope_reason(scope));
+        let _ = event.insert("toolchain", self.toolchain);
+        let _ = event.insert("check_cmd", self.cmd);
+
+        println!("{}", event);
+    }
+
+    fn finish_scopes(
+        &self,
+        results: &[(Scope, Option<&semver::Version>)],
+        overall: Option<&semver::Version>,
+    ) {
+        let reason = match results.first() {
+            Some((scope, _)) => format!("{}s-complete", Self::scope_reason(*scope)),
+            None => return,
+        };
+
+        let mut scopes = json::JsonValue::new_array();
+        for (scope, version) in results {
+            let mut result = Self::scope_object(*scope);
+            let _ = result.insert("success", version.is_some());
+            if let Some(version) = version {
+                let _ = result.insert("msrv", version.to_string());
+            }
+            let _ = scopes.push(result);
+        }
+
+        let mut event = object! {
+            reason: reason,
+            success: overall.is_some(),
+            results: scopes,
+            toolchain: self.toolchain,
+            check_cmd: self.cmd,
+        };
+        if let Some(version) = overall {
+            let _ = event.insert("msrv", version.to_string());
+        }
+
+        println!("{}", event);
+    }
 }
@@ -1,7 +1,16 @@
 use crate::config::ModeIntent;
+use crate::dependencies::{BlockingDependency, DependencyMsrv};
+use crate::diagnostics::Diagnostics;
+use crate::downgrade::PinnedPackage;
+use crate::failure::Failure;
+use crate::features::FeatureSet;
+use crate::manifest::BareVersion;
+use crate::manifest_editor::MsrvField;
 
 use rust_releases::semver;
 use std::fmt::Debug;
+use std::path::Path;
+use std::time::Duration;
 
 pub mod json;
 pub mod ui;
@@ -13,6 +22,16 @@ pub enum ProgressAction<'a> {
     FetchingIndex,
 }
 
+/// A part of a crate, for which the MSRV is determined separately
+#[derive(Debug, Clone, Copy)]
+pub enum Scope<'a> {
+    FeatureSet(&'a FeatureSet),
+    WorkspaceMember(&'a str),
+    Target(&'a str),
+    /// The way the versions of the dependencies are resolved, e.g. `minimal-versions`
+    Resolution(&'a str),
+}
+
 pub trait Output: Debug {
     // Shows the mode in which cargo-msrv will operate
     fn mode(&self, mode: ModeIntent);
@@ -23,16 +42,58 @@ pub trait Output: Debug {
     // Reports the currently running
     fn progress(&self, action: ProgressAction);
     fn complete_step(&self, version: &semver::Version, success: bool);
-    fn finish_success(&self, mode: ModeIntent, version: &semver::Version);
+    // Reports a step of which the outcome was taken from the cache, instead of running the check
+    fn complete_cached_step(&self, version: &semver::Version, success: bool);
+    // Reports a step of which the check was stopped, because it took longer than the check timeout
+    fn complete_timed_out_step(&self, version: &semver::Version, timeout: Duration);
+    // Reports a check which failed for a reason unrelated to the Rust version, and is run once more;
+    // `attempt` counts the retries of the check, of at most `retries`
+    fn retry_check(&self, version: &semver::Version, failure: Failure, attempt: u32, retries: u32);
+    // Reports the outcome, together with the most recent version which failed the check (if any),
+    // and the errors it failed with
+    fn finish_success(
+        &self,
+        mode: ModeIntent,
+        version: &semver::Version,
+        failed_below: Option<(&semver::Version, &Diagnostics)>,
+    );
     fn finish_failure(&self, mode: ModeIntent, cmd: &str);
+    // Reports the dependencies which failed to compile with the given version, below the MSRV
+    fn blocking_dependencies(&self, version: &semver::Version, dependencies: &[BlockingDependency]);
+    // Reports the generated lockfile, and the packages which were downgraded in it
+    fn lockfile_generated(&self, lockfile: &Path, pinned: &[PinnedPackage]);
+    // Reports the MSRVs declared by the dependencies in the given lockfile, from the highest to the
+    // lowest MSRV
+    fn dependency_msrvs(&self, lockfile: &Path, dependencies: &[DependencyMsrv]);
+    // Reports that the MSRV was written to the given field of the given manifest
+    fn msrv_written(&self, manifest: &Path, field: MsrvField, version: &BareVersion);
+    // Reports that the declared MSRV could be lowered, since an earlier version is compatible too
+    fn finish_not_minimal(&self, msrv: &semver::Version, compatible: &semver::Version);
+
+    // Reports that the MSRV will be determined for the given scope, until the next scope starts
+    fn scope(&self, scope: Scope);
+    // Reports the MSRV of each scope (if any), and the overall MSRV, which is the highest of them
+    fn finish_scopes(
+        &self,
+        results: &[(Scope, Option<&semver::Version>)],
+        overall: Option<&semver::Version>,
+    );
 }
 
 pub mod __private {
     use crate::config::ModeIntent;
-    use crate::reporter::{Output, ProgressAction};
+    use crate::dependencies::{BlockingDependency, DependencyMsrv};
+    use crate::diagnostics::Diagnostics;
+    use crate::downgrade::PinnedPackage;
+    use crate::failure::Failure;
+    use crate::manifest::BareVersion;
+    use crate::manifest_editor::MsrvField;
+    use crate::reporter::{Output, ProgressAction, Scope};
     use rust_releases::semver;
     use std::cell::RefCell;
+    use std::path::Path;
     use std::rc::Rc;
+    use std::time::Duration;
 
     /// This is meant to be used for testing
     #[derive(Debug)]
@@ -43,8 +104,41 @@ pub mod __private {
         fn set_steps(&self, _steps: u64) {}
         fn progress(&self, _action: ProgressAction) {}
         fn complete_step(&self, _version: &semver::Version, _success: bool) {}
-        fn finish_success(&self, _mode: ModeIntent, _version: &semver::Version) {}
+        fn complete_cached_step(&self, _version: &semver::Version, _success: bool) {}
+        fn complete_timed_out_step(&self, _version: &semver::Version, _timeout: Duration) {}
+        fn retry_check(
+            &self,
+            _version: &semver::Version,
+            _failure: Failure,
+            _attempt: u32,
+            _retries: u32,
+        ) {
+        }
+        fn finish_success(
+            &self,
+            _mode: ModeIntent,
+            _version: &semver::Version,
+            _failed_below: Option<(&semver::Version, &Diagnostics)>,
+        ) {
+        }
         fn finish_failure(&self, _mode: ModeIntent, _cmd: &str) {}
+        fn blocking_dependencies(
+            &self,
+            _version: &semver::Version,
+            _dependencies: &[BlockingDependency],
+        ) {
+        }
+        fn lockfile_generated(&self, _lockfile: &Path, _pinned: &[PinnedPackage]) {}
+        fn dependency_msrvs(&self, _lockfile: &Path, _dependencies: &[DependencyMsrv]) {}
+        fn msrv_written(&self, _manifest: &Path, _field: MsrvField, _version: &BareVersion) {}
+        fn finish_not_minimal(&self, _msrv: &semver::Version, _compatible: &semver::Version) {}
+        fn scope(&self, _scope: Scope) {}
+        fn finish_scopes(
+            &self,
+            _results: &[(Scope, Option<&semver::Version>)],
+            _overall: Option<&semver::Version>,
+        ) {
+        }
     }
 
     /// This is meant to be used for testing
@@ -67,8 +161,45 @@ pub mod __private {
             let mut successes = self.successes.borrow_mut();
             successes.push((success, version.to_owned()));
         }
-        fn finish_success(&self, _mode: ModeIntent, _version: &semver::Version) {}
+        fn complete_cached_step(&self, version: &semver::Version, success: bool) {
+            self.complete_step(version, success);
+        }
+        fn complete_timed_out_step(&self, version: &semver::Version, _timeout: Duration) {
+            self.complete_step(version, false);
+        }
+        fn retry_check(
+            &self,
+            _version: &semver::Version,
+            _failure: Failure,
+            _attempt: u32,
+            _retries: u32,
+        ) {
+        }
+        fn finish_success(
+            &self,
+            _mode: ModeIntent,
+            _version: &semver::Version,
+            _failed_below: Option<(&semver::Version, &Diagnostics)>,
+        ) {
+        }
         fn finish_failure(&self, _mode: ModeIntent, _cmd: &str) {}
+        fn blocking_dependencies(
+            &self,
+            _version: &semver::Version,
+            _dependencies: &[BlockingDependency],
+        ) {
+        }
+        fn lockfile_generated(&self, _lockfile: &Path, _pinned: &[PinnedPackage]) {}
+        fn dependency_msrvs(&self, _lockfile: &Path, _dependencies: &[DependencyMsrv]) {}
+        fn msrv_written(&self, _manifest: &Path, _field: MsrvField, _version: &BareVersion) {}
+        fn finish_not_minimal(&self, _msrv: &semver::Version, _compatible: &semver::Version) {}
+        fn scope(&self, _scope: Scope) {}
+        fn finish_scopes(
+            &self,
+            _results: &[(Scope, Option<&semver::Version>)],
+            _overall: Option<&semver::Version>,
+        ) {
+        }
     }
 
     impl Default for SuccessOutput {
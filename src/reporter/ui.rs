@@ -1,17 +1,122 @@
 use crate::config::ModeIntent;
+use crate::dependencies::{BlockingDependency, DependencyMsrv};
+use crate::diagnostics::Diagnostics;
+use crate::downgrade::PinnedPackage;
+use crate::failure::Failure;
+use crate::manifest::BareVersion;
+use crate::manifest_editor::MsrvField;
+use crate::reporter::Scope;
 
 use console::{style, Term};
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rust_releases::semver;
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The number of errors shown of the most recent version which failed the check
+const MAX_SHOWN_ERRORS: usize = 5;
 
 pub struct HumanPrinter<'s, 't> {
     term: Term,
     progress: ProgressBar,
+    jobs: usize,
+    job_lines: RefCell<Option<JobLines>>,
     toolchain: &'s str,
     cmd: &'t str,
 }
 
+/// One additional line per job, showing the check it's currently running, for when multiple
+/// checks run at the same time.
+struct JobLines {
+    multi: Arc<MultiProgress>,
+    lines: Vec<ProgressBar>,
+    running: RefCell<Vec<Option<semver::Version>>>,
+    drawing: RefCell<Option<JoinHandle<()>>>,
+}
+
+impl JobLines {
+    fn new(jobs: usize, main: &ProgressBar) -> Self {
+        let multi = MultiProgress::new();
+        multi.add(main.clone());
+
+        let lines =
+            (0..jobs)
+                .map(|_| {
+                    multi.add(ProgressBar::new_spinner().with_style(
+                        ProgressStyle::default_spinner().template("   {spinner} {msg}"),
+                    ))
+                })
+                .collect::<Vec<_>>();
+
+        Self {
+            multi: Arc::new(multi),
+            running: RefCell::new(vec![None; lines.len()]),
+            lines,
+            drawing: RefCell::new(None),
+        }
+    }
+
+    // The lines will only be drawn after `MultiProgress::join` has been called, which blocks until
+    // all lines are finished, so we join on a separate thread
+    fn start_drawing(&self) {
+        let mut drawing = self.drawing.borrow_mut();
+
+        if drawing.is_none() {
+            for line in &self.lines {
+                line.set_message(format!("{}", style("Idle").dim()));
+                line.enable_steady_tick(250);
+            }
+
+            let multi = Arc::clone(&self.multi);
+            *drawing = Some(std::thread::spawn(move || {
+                let _ = multi.join();
+            }));
+        }
+    }
+
+    fn start(&self, version: &semver::Version) {
+        let mut running = self.running.borrow_mut();
+
+        let slot = running
+            .iter()
+            .position(|v| v.as_ref() == Some(version))
+            .or_else(|| running.iter().position(Option::is_none));
+
+        if let Some(slot) = slot {
+            running[slot] = Some(version.clone());
+            self.lines[slot].set_message(format!(
+                "{} {}",
+                style("Checking").green().bold(),
+                style(version).cyan()
+            ));
+        }
+    }
+
+    fn complete(&self, version: &semver::Version) {
+        let mut running = self.running.borrow_mut();
+
+        if let Some(slot) = running.iter().position(|v| v.as_ref() == Some(version)) {
+            running[slot] = None;
+            self.lines[slot].set_message(format!("{}", style("Idle").dim()));
+        }
+    }
+
+    // Finishes all lines and waits until the final state has been drawn
+    fn finish(&self) {
+        for line in &self.lines {
+            line.finish_and_clear();
+        }
+
+        if let Some(drawing) = self.drawing.borrow_mut().take() {
+            let _ = drawing.join();
+        }
+    }
+}
+
 impl std::fmt::Debug for HumanPrinter<'_, '_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
@@ -33,15 +138,26 @@ impl<'s, 't> HumanPrinter<'s, 't> {
         Self {
             term,
             progress,
+            jobs: 1,
+            job_lines: RefCell::new(None),
             toolchain,
             cmd,
         }
     }
 
+    /// Show a line for each of the given number of concurrently running checks.
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
     fn welcome(&self, target: &str, cmd: &str, action_intent: ModeIntent) {
         let verb = match action_intent {
             ModeIntent::DetermineMSRV => "Determining",
             ModeIntent::VerifyMSRV => "Verifying",
+            ModeIntent::GenerateLockfile => "Downgrading dependencies for",
+            // nothing is checked, so there's no toolchain or check command to show
+            ModeIntent::ListDependencies | ModeIntent::SetMSRV => return,
         };
 
         let _ = self.term.write_line(
@@ -62,7 +178,19 @@ impl<'s, 't> HumanPrinter<'s, 't> {
             .as_str(),
         );
 
+        // the progress bar is reused when the MSRV is determined for multiple scopes
+        if self.progress.is_finished() {
+            self.progress.reset();
+        }
+
         self.progress.enable_steady_tick(250);
+
+        // a `MultiProgress` can only be drawn once, so each search gets its own job lines
+        if self.jobs > 1 {
+            let jobs = JobLines::new(self.jobs, &self.progress);
+            jobs.start_drawing();
+            *self.job_lines.borrow_mut() = Some(jobs);
+        }
     }
 
     fn show_progress(&self, action: &str, version: &semver::Version) {
@@ -89,11 +217,58 @@ impl<'s, 't> HumanPrinter<'s, 't> {
             style("Finished").green().bold(),
             message,
             style(version).cyan()
-        ))
+        ));
+
+        self.finish_jobs();
+    }
+
+    // Shows the first few errors of the most recent version which failed the check
+    fn show_failed_below(&self, version: &semver::Version, diagnostics: &Diagnostics) {
+        let errors = diagnostics.errors();
+
+        if errors.is_empty() {
+            return;
+        }
+
+        let _ = self.term.write_line(
+            format!(
+                "   {} {} failed the check with:",
+                style("Reason").yellow().bold(),
+                style(version).cyan()
+            )
+            .as_str(),
+        );
+
+        for error in errors.iter().take(MAX_SHOWN_ERRORS) {
+            let code = error
+                .code()
+                .map(|code| format!("[{}]", code))
+                .unwrap_or_default();
+            let _ = self.term.write_line(
+                format!("     {}{}: {}", style("error").red(), code, error.message()).as_str(),
+            );
+
+            if let (Some(file), Some(line)) = (error.file(), error.line()) {
+                let location = match error.column() {
+                    Some(column) => format!("{}:{}:{}", file, line, column),
+                    None => format!("{}:{}", file, line),
+                };
+                let _ = self
+                    .term
+                    .write_line(format!("       {} {}", style("-->").dim(), location).as_str());
+            }
+        }
+
+        if errors.len() > MAX_SHOWN_ERRORS {
+            let _ = self.term.write_line(
+                format!("     ... and {} more", errors.len() - MAX_SHOWN_ERRORS).as_str(),
+            );
+        }
     }
 
     fn finish_with_err(&self, cmd: &str) {
         self.progress.abandon();
+        self.finish_jobs();
         let _ = self.term.write_line(
             format!(
                 "   {} {} command {} didn't succeed",
@@ -104,6 +279,12 @@ impl<'s, 't> HumanPrinter<'s, 't> {
             .as_str(),
         );
     }
+
+    fn finish_jobs(&self) {
+        if let Some(jobs) = self.job_lines.borrow_mut().take() {
+            jobs.finish();
+        }
+    }
 }
 
 impl<'s, 't> crate::Output for HumanPrinter<'s, 't> {
@@ -116,6 +297,14 @@ impl<'s, 't> crate::Output for HumanPrinter<'s, 't> {
     }
 
     fn progress(&self, action: crate::ProgressAction) {
+        // with multiple jobs, each running check is shown on its own line
+        if let (Some(jobs), crate::ProgressAction::Checking(version)) =
+            (self.job_lines.borrow().as_ref(), action)
+        {
+            jobs.start(version);
+            return;
+        }
+
         let (action, version) = match action {
             crate::ProgressAction::Installing(version) => ("Installing", Some(version)),
             crate::ProgressAction::Checking(version) => ("Checking", Some(version)),
@@ -130,6 +319,10 @@ impl<'s, 't> crate::Output for HumanPrinter<'s, 't> {
     }
 
     fn complete_step(&self, version: &semver::Version, success: bool) {
+        if let Some(jobs) = self.job_lines.borrow().as_ref() {
+            jobs.complete(version);
+        }
+
         if success {
             self.complete_step(format!(
                 "{} Good check for {}",
@@ -145,14 +338,331 @@ impl<'s, 't> crate::Output for HumanPrinter<'s, 't> {
         }
     }
 
-    fn finish_success(&self, mode: ModeIntent, version: &semver::Version) {
+    fn complete_timed_out_step(&self, version: &semver::Version, timeout: Duration) {
+        if let Some(jobs) = self.job_lines.borrow().as_ref() {
+            jobs.complete(version);
+        }
+
+        self.complete_step(format!(
+            "{} Check for {} took longer than {}s",
+            style("Timeout").yellow().bold(),
+            style(version).cyan(),
+            timeout.as_secs()
+        ));
+    }
+
+    fn retry_check(&self, version: &semver::Version, failure: Failure, attempt: u32, retries: u32) {
+        if let Some(jobs) = self.job_lines.borrow().as_ref() {
+            jobs.complete(version);
+        }
+
+        self.progress.println(format!(
+            "{} Check for {} ({} of {}): {}",
+            style("Retry").yellow().bold(),
+            style(version).cyan(),
+            attempt,
+            retries,
+            failure
+        ));
+    }
+
+    fn complete_cached_step(&self, version: &semver::Version, success: bool) {
+        let verdict = if success { "Good" } else { "Bad" };
+
+        self.complete_step(format!(
+            "{} {} check for {} {}",
+            style("Done").green().bold(),
+            verdict,
+            style(version).cyan(),
+            style("(cached)").dim()
+        ));
+    }
+
+    fn finish_success(
+        &self,
+        mode: ModeIntent,
+        version: &semver::Version,
+        failed_below: Option<(&semver::Version, &Diagnostics)>,
+    ) {
         match mode {
             ModeIntent::DetermineMSRV => self.finish_with_ok("The MSRV is:", version),
             ModeIntent::VerifyMSRV => self.finish_with_ok("Satisfied MSRV check:", version),
+            ModeIntent::GenerateLockfile => {
+                self.finish_with_ok("Generated a lockfile for:", version)
+            }
+            ModeIntent::ListDependencies => {
+                self.finish_with_ok("The highest MSRV of the dependencies is:", version)
+            }
+            ModeIntent::SetMSRV => self.finish_with_ok("Set the MSRV to:", version),
+        }
+
+        if let Some((version, diagnostics)) = failed_below {
+            self.show_failed_below(version, diagnostics);
         }
     }
 
     fn finish_failure(&self, _mode: ModeIntent, cmd: &str) {
         self.finish_with_err(cmd)
     }
+
+    fn blocking_dependencies(
+        &self,
+        version: &semver::Version,
+        dependencies: &[BlockingDependency],
+    ) {
+        if dependencies.is_empty() {
+            let _ = self.term.write_line(
+                format!(
+                    "   {} no dependency failed to compile with {}",
+                    style("Blocking").yellow().bold(),
+                    style(version).cyan()
+                )
+                .as_str(),
+            );
+            return;
+        }
+
+        let _ = self.term.write_line(
+            format!(
+                "   {} dependencies which failed to compile with {}:",
+                style("Blocking").yellow().bold(),
+                style(version).cyan()
+            )
+            .as_str(),
+        );
+
+        for dependency in dependencies {
+            let rust_version = match dependency.rust_version() {
+                Some(rust_version) => {
+                    format!("declares rust-version {}", style(rust_version).cyan())
+                }
+                None => "declares no rust-version".to_string(),
+            };
+
+            let _ = self.term.write_line(
+                format!(
+                    "     {} {} ({})",
+                    style(dependency.name()).bold(),
+                    dependency.version().unwrap_or_default(),
+                    rust_version
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    fn lockfile_generated(&self, lockfile: &Path, pinned: &[PinnedPackage]) {
+        let _ = self.term.write_line(
+            format!(
+                "   {} lockfile to '{}'",
+                style("Written").green().bold(),
+                lockfile.display()
+            )
+            .as_str(),
+        );
+
+        for package in pinned {
+            let _ = self.term.write_line(
+                format!(
+                    "     {} {} -> {}",
+                    style(package.name()).bold(),
+                    package.from(),
+                    style(package.to()).cyan()
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    fn dependency_msrvs(&self, lockfile: &Path, dependencies: &[DependencyMsrv]) {
+        if dependencies.is_empty() {
+            let _ = self.term.write_line(
+                format!(
+                    "   {} no dependencies in '{}'",
+                    style("Listing").green().bold(),
+                    lockfile.display()
+                )
+                .as_str(),
+            );
+            return;
+        }
+
+        let _ = self.term.write_line(
+            format!(
+                "   {} MSRVs declared by the {} dependencies in '{}':",
+                style("Listing").green().bold(),
+                dependencies.len(),
+                lockfile.display()
+            )
+            .as_str(),
+        );
+
+        // the dependencies are ordered by their MSRV, so those with the same MSRV are listed on one line
+        let mut groups: Vec<Vec<&DependencyMsrv>> = Vec::new();
+        for dependency in dependencies {
+            match groups.last_mut() {
+                Some(group)
+                    if group[0].rust_version() == dependency.rust_version()
+                        && group[0].manifest().is_some() == dependency.manifest().is_some() =>
+                {
+                    group.push(dependency)
+                }
+                _ => groups.push(vec![dependency]),
+            }
+        }
+
+        for group in groups {
+            let label = match (group[0].rust_version(), group[0].manifest()) {
+                (Some(rust_version), _) => style(format!("{:<8}", rust_version.to_string())).cyan(),
+                (None, Some(_)) => style(format!("{:<8}", "none")).yellow(),
+                (None, None) => style(format!("{:<8}", "unknown")).red(),
+            };
+
+            // the MSRV is only shown on the first line of its group
+            for (index, dependency) in group.iter().enumerate() {
+                let label = if index == 0 {
+                    label.to_string()
+                } else {
+                    " ".repeat(8)
+                };
+
+                let _ = self.term.write_line(
+                    format!(
+                        "     {} {} {}",
+                        label,
+                        style(dependency.name()).bold(),
+                        dependency.version()
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        if dependencies
+            .iter()
+            .any(|dependency| dependency.manifest().is_none())
+        {
+            let _ = self.term.write_line(
+                "   The MSRV of the dependencies which aren't vendored, or downloaded by cargo, is unknown",
+            );
+        }
+    }
+
+    fn msrv_written(&self, manifest: &Path, field: MsrvField, version: &BareVersion) {
+        let _ = self.term.write_line(
+            format!(
+                "   {} {} to {} in '{}'",
+                style("Written").green().bold(),
+                style(version).cyan(),
+                style(field).bold(),
+                manifest.display()
+            )
+            .as_str(),
+        );
+    }
+
+    fn finish_not_minimal(&self, msrv: &semver::Version, compatible: &semver::Version) {
+        self.progress.abandon();
+        self.finish_jobs();
+        let _ = self.term.write_line(
+            format!(
+                "   {} the MSRV {} could be lowered, since {} is compatible too",
+                style("Failed").red().bold(),
+                style(msrv).cyan(),
+                style(compatible).cyan(),
+            )
+            .as_str(),
+        );
+    }
+
+    fn scope(&self, scope: Scope) {
+        let _ = self.term.write_line(
+            format!(
+                "\n{} {}",
+                style(scope_header(scope)).bold(),
+                style(scope_name(scope)).cyan().bold()
+            )
+            .as_str(),
+        );
+    }
+
+    fn finish_scopes(
+        &self,
+        results: &[(Scope, Option<&semver::Version>)],
+        overall: Option<&semver::Version>,
+    ) {
+        let header = match results.first() {
+            Some((scope, _)) => scope_header(*scope),
+            None => return,
+        };
+
+        let names = results
+            .iter()
+            .map(|(scope, _)| scope_name(*scope))
+            .collect::<Vec<_>>();
+        let width = names
+            .iter()
+            .map(String::len)
+            .chain(std::iter::once(header.len()))
+            .max()
+            .unwrap_or(0);
+
+        let _ = self.term.write_line(
+            format!(
+                "\n   {:<width$}   {}",
+                style(header).bold(),
+                style("MSRV").bold(),
+                width = width
+            )
+            .as_str(),
+        );
+
+        for (name, (_, version)) in names.iter().zip(results) {
+            let msrv = match version {
+                Some(version) => style(version.to_string()).cyan(),
+                None => style("none".to_string()).red(),
+            };
+
+            let _ = self
+                .term
+                .write_line(format!("   {:<width$}   {}", name, msrv, width = width).as_str());
+        }
+
+        let _ = match overall {
+            Some(version) => self.term.write_line(
+                format!(
+                    "{} The MSRV is: {}",
+                    style("Finished").green().bold(),
+                    style(version).cyan()
+                )
+                .as_str(),
+            ),
+            None => self.term.write_line(
+                format!(
+                    "   {} not every {} is compatible with any of the checked toolchains",
+                    style("Failed").red().bold(),
+                    header.to_lowercase(),
+                )
+                .as_str(),
+            ),
+        };
+    }
+}
+
+fn scope_header(scope: Scope) -> &'static str {
+    match scope {
+        Scope::FeatureSet(_) => "Feature set",
+        Scope::WorkspaceMember(_) => "Workspace member",
+        Scope::Target(_) => "Target",
+        Scope::Resolution(_) => "Dependency versions",
+    }
+}
+
+fn scope_name(scope: Scope) -> String {
+    match scope {
+        Scope::FeatureSet(set) => set.to_string(),
+        Scope::WorkspaceMember(name) => name.to_string(),
+        Scope::Target(target) => target.to_string(),
+        Scope::Resolution(resolution) => resolution.to_string(),
+    }
 }
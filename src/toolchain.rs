@@ -0,0 +1,339 @@
+//! Providers of the Rust toolchains which are checked.
+//!
+//! By default, toolchains are installed and run with rustup. With `--toolchain-dir`, the toolchains
+//! are run from a directory of extracted rustc and cargo builds instead, and any other
+//! [`ToolchainProvider`] can be selected with [`ConfigBuilder::toolchain_provider`].
+//!
+//! [`ConfigBuilder::toolchain_provider`]: crate::config::ConfigBuilder::toolchain_provider
+
+use crate::command::{command, command_with_env, command_with_output};
+use crate::errors::{CargoMSRVError, TResult};
+use crate::fetch::ToolchainSpecifier;
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+
+const RUSTUP: &str = "rustup";
+
+/// Installs, lists and runs Rust toolchains, identified by their toolchain specifier, e.g.
+/// `1.56.0-x86_64-unknown-linux-gnu`.
+pub trait ToolchainProvider: Debug + Send + Sync {
+    /// Installs the toolchain, together with the standard library of the given cross compilation
+    /// target, if any. Toolchains which are already installed are left as is.
+    fn install(&self, toolchain: &str, cross_target: Option<&str>) -> TResult<()>;
+
+    /// Whether the toolchain is installed.
+    fn is_installed(&self, toolchain: &str) -> TResult<bool> {
+        Ok(self.list()?.iter().any(|installed| installed == toolchain))
+    }
+
+    /// Spawns the given command, e.g. `cargo check`, with the toolchain. The output of the command
+    /// is piped, so it can be collected with [`Child::wait_with_output`].
+    fn run(
+        &self,
+        toolchain: &str,
+        command: &[String],
+        dir: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+    ) -> TResult<Child>;
+
+    /// The installed toolchains.
+    fn list(&self) -> TResult<Vec<ToolchainSpecifier>>;
+
+    /// The target of the host, which is checked unless another target is given, e.g.
+    /// `x86_64-unknown-linux-gnu`.
+    fn default_target(&self) -> TResult<String>;
+
+    /// The targets which can be checked, i.e. of which the standard library can be installed.
+    fn targets(&self) -> TResult<Vec<String>>;
+}
+
+/// Installs and runs toolchains with rustup.
+#[derive(Debug, Default)]
+pub struct Rustup;
+
+impl ToolchainProvider for Rustup {
+    fn install(&self, toolchain: &str, cross_target: Option<&str>) -> TResult<()> {
+        tracing::info!("Installing toolchain {}", toolchain);
+
+        let args = ["install", "--profile", "minimal", toolchain];
+        let status = command(RUSTUP, args.iter(), None)
+            .and_then(|mut c| c.wait().map_err(CargoMSRVError::Io))?;
+
+        if !status.success() {
+            return Err(CargoMSRVError::RustupInstallFailed(toolchain.to_string()));
+        }
+
+        // when cross compiling, the toolchain also needs the standard library of the target
+        if let Some(target) = cross_target {
+            tracing::info!("Adding target {} to toolchain {}", target, toolchain);
+
+            let args = ["target", "add", "--toolchain", toolchain, target];
+            let status = command(RUSTUP, args.iter(), None)
+                .and_then(|mut c| c.wait().map_err(CargoMSRVError::Io))?;
+
+            if !status.success() {
+                return Err(CargoMSRVError::RustupTargetAddFailed {
+                    toolchain: toolchain.to_string(),
+                    target: target.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        toolchain: &str,
+        command: &[String],
+        dir: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+    ) -> TResult<Child> {
+        let mut cmd = vec!["run", toolchain];
+        cmd.extend(command.iter().map(String::as_str));
+
+        command_with_env(RUSTUP, &cmd, dir, envs.iter().copied())
+    }
+
+    fn list(&self) -> TResult<Vec<ToolchainSpecifier>> {
+        rustup_output(&["toolchain", "list"]).map(|list| first_words(&list))
+    }
+
+    fn default_target(&self) -> TResult<String> {
+        // the first line contains the default target
+        // e.g. `Default host: x86_64-unknown-linux-gnu`
+        rustup_output(&["show"])?
+            .lines()
+            .next()
+            .and_then(|line| line.split_ascii_whitespace().nth(2))
+            .map(String::from)
+            .ok_or(CargoMSRVError::DefaultHostTripleNotFound)
+    }
+
+    fn targets(&self) -> TResult<Vec<String>> {
+        rustup_output(&["target", "list"]).map(|list| first_words(&list))
+    }
+}
+
+fn rustup_output(args: &[&str]) -> TResult<String> {
+    let output = command_with_output(RUSTUP, args)?.wait_with_output()?;
+
+    String::from_utf8(output.stdout).map_err(From::from)
+}
+
+// Each toolchain or target is listed on a single line, e.g. <toolchain> (default) for the default
+// toolchain, or <target> (installed) for an installed target.
+fn first_words(list: &str) -> Vec<String> {
+    list.lines()
+        .filter_map(|line| line.split_ascii_whitespace().next())
+        .map(String::from)
+        .collect()
+}
+
+/// Runs the toolchains in a directory of extracted rustc and cargo builds, e.g. toolchains
+/// provided by Nix, instead of installing them. The directory contains a folder for each
+/// toolchain, named after its toolchain specifier, e.g. `1.56.0-x86_64-unknown-linux-gnu`, with
+/// the binaries in `bin`, and the standard library of each target in `lib/rustlib/<target>`.
+#[derive(Debug)]
+pub struct ToolchainDirectory {
+    path: PathBuf,
+}
+
+impl ToolchainDirectory {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn bin(&self, toolchain: &str) -> PathBuf {
+        self.path.join(toolchain).join("bin")
+    }
+
+    // The targets of which the standard library is part of the toolchain
+    fn targets_of(&self, toolchain: &str) -> Vec<String> {
+        let rustlib = self.path.join(toolchain).join("lib").join("rustlib");
+
+        read_folder_names(&rustlib, |target| target.join("lib").is_dir())
+    }
+}
+
+impl ToolchainProvider for ToolchainDirectory {
+    // The toolchains can't be installed, so they must be in the directory already
+    fn install(&self, toolchain: &str, cross_target: Option<&str>) -> TResult<()> {
+        if !self.bin(toolchain).is_dir() {
+            return Err(CargoMSRVError::ToolchainNotInDirectory {
+                toolchain: toolchain.to_string(),
+                directory: self.path.clone(),
+            });
+        }
+
+        match cross_target {
+            Some(target) if !self.targets_of(toolchain).iter().any(|t| t == target) => {
+                Err(CargoMSRVError::TargetNotInToolchain {
+                    toolchain: toolchain.to_string(),
+                    target: target.to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Like `rustup run`, the binaries of the toolchain are found before those on the `PATH`
+    fn run(
+        &self,
+        toolchain: &str,
+        command: &[String],
+        dir: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+    ) -> TResult<Child> {
+        let (program, args) = command
+            .split_first()
+            .ok_or(CargoMSRVError::UnableToRunCheck)?;
+
+        let mut paths = vec![self.bin(toolchain)];
+        if let Some(path) = std::env::var_os("PATH") {
+            paths.extend(std::env::split_paths(&path));
+        }
+        let path = std::env::join_paths(paths)
+            .map_err(|err| CargoMSRVError::GenericMessage(err.to_string()))?;
+
+        let envs = std::iter::once((OsStr::new("PATH"), path.as_os_str()))
+            .chain(envs.iter().map(|(name, value)| (OsStr::new(*name), *value)));
+
+        command_with_env(program, args, dir, envs)
+    }
+
+    fn list(&self) -> TResult<Vec<ToolchainSpecifier>> {
+        Ok(read_folder_names(&self.path, |toolchain| {
+            toolchain.join("bin").is_dir()
+        }))
+    }
+
+    // The rustc of any of the toolchains reports the host, e.g. `host: x86_64-unknown-linux-gnu`
+    fn default_target(&self) -> TResult<String> {
+        let toolchain = self
+            .list()?
+            .into_iter()
+            .next()
+            .ok_or(CargoMSRVError::DefaultHostTripleNotFound)?;
+
+        let rustc = self.bin(&toolchain).join("rustc");
+        let output = command_with_output(rustc, ["-vV"].iter())?.wait_with_output()?;
+
+        String::from_utf8(output.stdout)?
+            .lines()
+            .find_map(|line| line.strip_prefix("host: "))
+            .map(String::from)
+            .ok_or(CargoMSRVError::DefaultHostTripleNotFound)
+    }
+
+    fn targets(&self) -> TResult<Vec<String>> {
+        let mut targets = self
+            .list()?
+            .iter()
+            .flat_map(|toolchain| self.targets_of(toolchain))
+            .collect::<Vec<_>>();
+
+        targets.sort();
+        targets.dedup();
+
+        Ok(targets)
+    }
+}
+
+// The names of the folders in the given folder which satisfy the predicate, in alphabetical order
+fn read_folder_names<F: Fn(&Path) -> bool>(folder: &Path, predicate: F) -> Vec<String> {
+    let mut names = std::fs::read_dir(folder)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| predicate(&entry.path()))
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toolchain_list() {
+        let toolchains = first_words(
+            "stable-x86_64-unknown-linux-gnu (active, default)\n1.56.0-x86_64-unknown-linux-gnu\n",
+        );
+
+        assert_eq!(
+            toolchains,
+            vec![
+                "stable-x86_64-unknown-linux-gnu",
+                "1.56.0-x86_64-unknown-linux-gnu"
+            ]
+        );
+    }
+
+    #[test]
+    fn toolchain_directory() {
+        let root =
+            std::env::temp_dir().join(format!("cargo-msrv-toolchains-{}", std::process::id()));
+        let toolchain = |name: &str, targets: &[&str]| {
+            std::fs::create_dir_all(root.join(name).join("bin")).unwrap();
+
+            for target in targets {
+                let rustlib = root.join(name).join("lib").join("rustlib");
+                std::fs::create_dir_all(rustlib.join(target).join("lib")).unwrap();
+                std::fs::create_dir_all(rustlib.join("etc")).unwrap();
+            }
+        };
+
+        toolchain(
+            "1.56.0-x86_64-unknown-linux-gnu",
+            &["x86_64-unknown-linux-gnu"],
+        );
+        toolchain(
+            "1.38.0-x86_64-unknown-linux-gnu",
+            &["x86_64-unknown-linux-gnu", "wasm32-unknown-unknown"],
+        );
+        std::fs::create_dir_all(root.join("not-a-toolchain")).unwrap();
+
+        let directory = ToolchainDirectory::new(&root);
+        let list = directory.list();
+        let targets = directory.targets();
+        let installed = directory.install(
+            "1.38.0-x86_64-unknown-linux-gnu",
+            Some("wasm32-unknown-unknown"),
+        );
+        let missing_target = directory.install(
+            "1.56.0-x86_64-unknown-linux-gnu",
+            Some("wasm32-unknown-unknown"),
+        );
+        let missing_toolchain = directory.install("1.40.0-x86_64-unknown-linux-gnu", None);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            list.unwrap(),
+            vec![
+                "1.38.0-x86_64-unknown-linux-gnu",
+                "1.56.0-x86_64-unknown-linux-gnu"
+            ]
+        );
+        assert_eq!(
+            targets.unwrap(),
+            vec!["wasm32-unknown-unknown", "x86_64-unknown-linux-gnu"]
+        );
+        assert!(installed.is_ok());
+        assert!(matches!(
+            missing_target,
+            Err(CargoMSRVError::TargetNotInToolchain { .. })
+        ));
+        assert!(matches!(
+            missing_toolchain,
+            Err(CargoMSRVError::ToolchainNotInDirectory { .. })
+        ));
+    }
+}
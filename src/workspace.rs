@@ -0,0 +1,286 @@
+//! Support for determining the MSRV of each member of a cargo workspace separately.
+//!
+//! The members are read with `cargo metadata`, and checked in dependency order, so the MSRV of
+//! a member can be used as the lower bound for the members which depend on it.
+
+use crate::errors::{CargoMSRVError, TResult};
+use crate::manifest::TomlMap;
+use crate::read_manifest;
+use decent_toml_rs_alternative::TomlValue;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WorkspaceMember {
+    name: String,
+    manifest_path: PathBuf,
+    // names of the other workspace members this member depends on (excluding dev-dependencies)
+    dependencies: Vec<String>,
+}
+
+impl WorkspaceMember {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn manifest_path(&self) -> &Path {
+        &self.manifest_path
+    }
+
+    pub fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+    members: Vec<WorkspaceMember>,
+}
+
+impl Workspace {
+    /// Reads the members of the workspace which contains the given crate root, using `cargo metadata`.
+    pub fn load(crate_root: &Path) -> TResult<Self> {
+        Self::from_metadata(&metadata(crate_root)?)
+    }
+
+    /// Reads the members from the output of `cargo metadata --format-version 1 --no-deps`.
+    fn from_metadata(metadata: &json::JsonValue) -> TResult<Self> {
+        let packages = &metadata["packages"];
+
+        let names = packages
+            .members()
+            .filter_map(|package| package["name"].as_str())
+            .collect::<HashSet<_>>();
+
+        let members = packages
+            .members()
+            .map(|package| {
+                let name = package["name"].as_str().ok_or_else(|| {
+                    CargoMSRVError::UnableToReadWorkspace("package without a name".to_string())
+                })?;
+
+                let dependencies = package["dependencies"]
+                    .members()
+                    .filter(|dependency| dependency["kind"].as_str() != Some("dev"))
+                    .filter_map(|dependency| dependency["name"].as_str())
+                    .filter(|dependency| names.contains(dependency) && *dependency != name)
+                    .map(String::from)
+                    .collect();
+
+                Ok(WorkspaceMember {
+                    name: name.to_string(),
+                    manifest_path: PathBuf::from(package["manifest_path"].as_str().unwrap_or("")),
+                    dependencies,
+                })
+            })
+            .collect::<TResult<Vec<_>>>()?;
+
+        Ok(Self { members })
+    }
+
+    /// The members, ordered such that each member comes after the members it depends on.
+    /// Members without such an ordering between them keep the order given by cargo.
+    pub fn members_in_dependency_order(&self) -> TResult<Vec<&WorkspaceMember>> {
+        let mut ordered: Vec<&WorkspaceMember> = Vec::with_capacity(self.members.len());
+        let mut remaining = self.members.iter().collect::<Vec<_>>();
+
+        while !remaining.is_empty() {
+            let position = remaining.iter().position(|member| {
+                member
+                    .dependencies
+                    .iter()
+                    .all(|dependency| ordered.iter().any(|done| &done.name == dependency))
+            });
+
+            match position {
+                Some(position) => ordered.push(remaining.remove(position)),
+                None => {
+                    return Err(CargoMSRVError::UnableToReadWorkspace(format!(
+                        "the dependencies of workspace members {} form a cycle",
+                        remaining
+                            .iter()
+                            .map(|member| member.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )))
+                }
+            }
+        }
+
+        Ok(ordered)
+    }
+}
+
+/// The target directory of the workspace which contains the given crate root, as reported by
+/// `cargo metadata`. Unlike `<crate root>/target`, this takes `CARGO_TARGET_DIR` and the
+/// `build.target-dir` configuration into account, and is shared by the members of a workspace.
+pub fn target_directory(crate_root: &Path) -> TResult<PathBuf> {
+    target_directory_from_metadata(&metadata(crate_root)?)
+}
+
+fn target_directory_from_metadata(metadata: &json::JsonValue) -> TResult<PathBuf> {
+    metadata["target_directory"]
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| CargoMSRVError::UnableToReadWorkspace("no target directory".to_string()))
+}
+
+// Runs `cargo metadata --format-version 1 --no-deps` for the given crate root
+fn metadata(crate_root: &Path) -> TResult<json::JsonValue> {
+    // when invoked as `cargo msrv`, cargo tells us which cargo binary is running
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+
+    let output = Command::new(cargo)
+        .args(
+            [
+                "metadata",
+                "--format-version",
+                "1",
+                "--no-deps",
+                "--manifest-path",
+            ]
+            .iter(),
+        )
+        .arg(crate_root.join("Cargo.toml"))
+        .output()?;
+
+    if !output.status.success() {
+        return Err(CargoMSRVError::UnableToReadWorkspace(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    json::parse(&String::from_utf8_lossy(&output.stdout))
+        .map_err(|err| CargoMSRVError::UnableToReadWorkspace(err.to_string()))
+}
+
+/// A virtual manifest defines a workspace, but no package of its own.
+pub fn is_virtual_manifest(manifest: &TomlMap) -> bool {
+    manifest.contains_key("workspace") && !manifest.contains_key("package")
+}
+
+/// Finds the manifest of the workspace root of the crate in the given folder, like cargo does: the
+/// `package.workspace` key points to it, or else it's the first manifest with a `[workspace]`
+/// table, starting at the crate itself. A crate which isn't part of a workspace has none.
+pub fn workspace_root(crate_root: &Path, manifest: &TomlMap) -> TResult<Option<PathBuf>> {
+    if manifest.contains_key("workspace") {
+        return Ok(Some(crate_root.join("Cargo.toml")));
+    }
+
+    if let Some(root) = manifest
+        .get("package")
+        .and_then(|package| package.get("workspace"))
+        .and_then(TomlValue::as_string)
+    {
+        return Ok(Some(crate_root.join(root).join("Cargo.toml")));
+    }
+
+    for folder in crate_root.ancestors().skip(1) {
+        let path = folder.join("Cargo.toml");
+        if path.is_file() && read_manifest(&path)?.contains_key("workspace") {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{CargoManifestParser, TomlParser};
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {
+                "name": "app",
+                "manifest_path": "/ws/app/Cargo.toml",
+                "dependencies": [
+                    { "name": "core", "kind": null },
+                    { "name": "serde", "kind": null }
+                ]
+            },
+            {
+                "name": "core",
+                "manifest_path": "/ws/core/Cargo.toml",
+                "dependencies": [
+                    { "name": "macros", "kind": "build" },
+                    { "name": "app", "kind": "dev" }
+                ]
+            },
+            {
+                "name": "macros",
+                "manifest_path": "/ws/macros/Cargo.toml",
+                "dependencies": []
+            }
+        ],
+        "workspace_members": [],
+        "target_directory": "/ws/target"
+    }"#;
+
+    fn workspace(metadata: &str) -> Workspace {
+        Workspace::from_metadata(&json::parse(metadata).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn members_from_metadata() {
+        let workspace = workspace(METADATA);
+
+        let app = &workspace.members[0];
+        assert_eq!(app.name(), "app");
+        assert_eq!(app.manifest_path(), Path::new("/ws/app/Cargo.toml"));
+        assert_eq!(app.dependencies(), &["core".to_string()]);
+
+        // dev-dependencies don't need to build with the toolchain of the member
+        let core = &workspace.members[1];
+        assert_eq!(core.dependencies(), &["macros".to_string()]);
+    }
+
+    #[test]
+    fn target_directory_from_metadata() {
+        let metadata = json::parse(METADATA).unwrap();
+
+        assert_eq!(
+            super::target_directory_from_metadata(&metadata).unwrap(),
+            Path::new("/ws/target")
+        );
+    }
+
+    #[test]
+    fn dependency_order() {
+        let workspace = workspace(METADATA);
+
+        let order = workspace
+            .members_in_dependency_order()
+            .unwrap()
+            .iter()
+            .map(|member| member.name())
+            .collect::<Vec<_>>();
+
+        assert_eq!(order, vec!["macros", "core", "app"]);
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let workspace = workspace(
+            r#"{ "packages": [
+                { "name": "a", "manifest_path": "", "dependencies": [{ "name": "b", "kind": null }] },
+                { "name": "b", "manifest_path": "", "dependencies": [{ "name": "a", "kind": null }] }
+            ] }"#,
+        );
+
+        assert!(workspace.members_in_dependency_order().is_err());
+    }
+
+    #[yare::parameterized(
+        virtual_manifest = { "[workspace]\nmembers = [\"a\"]\n", true },
+        root_package = { "[package]\nname = \"a\"\n\n[workspace]\n", false },
+        package = { "[package]\nname = \"a\"\n", false },
+    )]
+    fn virtual_manifest(contents: &str, expected: bool) {
+        let manifest = CargoManifestParser.parse::<TomlMap>(contents).unwrap();
+
+        assert_eq!(is_virtual_manifest(&manifest), expected);
+    }
+}
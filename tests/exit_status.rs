@@ -0,0 +1,76 @@
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn exits_with_failure_on_error() {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.36.0");
+
+    // an invalid minimum version is rejected before any check is run
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-msrv"))
+        .args(&["--no-log", "--path", folder.to_str().unwrap()])
+        .args(&["--min", "not-a-version"])
+        .output()
+        .expect("Unable to run cargo-msrv");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!output.stderr.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn exits_with_failure_on_incompatible_msrv() {
+    // the declared MSRV is 1.36
+    assert_eq!(verify_with_fake_rustup("1.36.0-*|1.37.0-*"), Some(0));
+    assert_eq!(verify_with_fake_rustup("1.37.0-*"), Some(1));
+}
+
+// Verifies the MSRV with a fake rustup, of which the toolchains matching the given pattern pass
+// the check, while the other toolchains fail it
+#[cfg(unix)]
+fn verify_with_fake_rustup(compatible: &str) -> Option<i32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.36.0");
+    let bin = std::env::temp_dir().join(format!(
+        "cargo-msrv-exit-status-{}-{}",
+        std::process::id(),
+        compatible.len()
+    ));
+    std::fs::create_dir_all(&bin).unwrap();
+
+    let rustup = bin.join("rustup");
+    let script = format!(
+        "#!/bin/sh\n\
+         case \"$1\" in\n\
+         show) echo 'Default host: x86_64-unknown-linux-gnu' ;;\n\
+         run) case \"$2\" in {}) exit 0 ;; *) exit 1 ;; esac ;;\n\
+         esac\n",
+        compatible
+    );
+    std::fs::write(&rustup, script).unwrap();
+    std::fs::set_permissions(&rustup, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let releases = bin.join("releases.json");
+    std::fs::write(&releases, r#"["1.37.0", "1.36.0", "1.35.0"]"#).unwrap();
+
+    let mut paths = vec![bin.clone()];
+    paths.extend(std::env::split_paths(
+        &std::env::var_os("PATH").unwrap_or_default(),
+    ));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-msrv"))
+        .args(&["--no-log", "--no-cache", "--verify"])
+        .args(&["--path", folder.to_str().unwrap()])
+        .arg("--release-source")
+        .arg(format!("file:{}", releases.display()))
+        .env("PATH", std::env::join_paths(paths).unwrap())
+        .output()
+        .expect("Unable to run cargo-msrv");
+    std::fs::remove_dir_all(&bin).unwrap();
+
+    output.status.code()
+}
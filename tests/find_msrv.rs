@@ -58,6 +58,33 @@ fn msrv_using_bisect_method(folder: &str, expected_version: semver::Version) {
     assert_eq!(actual_version, expected_version);
 }
 
+#[parameterized(
+    folder = {
+        "1.35.0",
+        "1.38.0",
+    },
+    expected_version = {
+        semver::Version::new(1,35,0),
+        semver::Version::new(1,38,0),
+    }
+)]
+fn msrv_using_galloping_method(folder: &str, expected_version: semver::Version) {
+    let folder = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join(folder);
+    let with_args = vec![
+        "cargo-msrv",
+        "--galloping",
+        "--path",
+        folder.to_str().unwrap(),
+    ];
+
+    let result = run_msrv(with_args);
+    let actual_version = result.unwrap_version();
+
+    assert_eq!(actual_version, expected_version);
+}
+
 #[test]
 fn msrv_unsupported() {
     let folder = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
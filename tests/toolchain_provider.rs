@@ -0,0 +1,568 @@
+extern crate cargo_msrv;
+mod common;
+
+use cargo_msrv::config::{test_config_from_matches, ConfigBuilder, SearchMethod};
+use cargo_msrv::errors::{CargoMSRVError, TResult};
+use cargo_msrv::nightly::{nightly_index, NightlyDate};
+use cargo_msrv::toolchain::ToolchainProvider;
+use cargo_msrv::MinimalCompatibility;
+use common::*;
+use parameterized::parameterized;
+use rust_releases::{semver, Release, ReleaseIndex};
+use std::ffi::OsStr;
+use std::iter::FromIterator;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// A toolchain provider which doesn't run any toolchain: the check passes for the toolchains of
+/// `compatible_since` and later releases.
+#[derive(Debug)]
+struct FakeToolchains {
+    compatible_since: semver::Version,
+    // the first check with the toolchain of this release is killed, as if it ran out of memory
+    flaky: Option<semver::Version>,
+    installed: Mutex<Vec<String>>,
+    runs: Mutex<Vec<String>>,
+    // the environment variables of each run, by toolchain
+    envs: Mutex<Vec<(String, Vec<String>)>>,
+    commands: Mutex<Vec<Vec<String>>>,
+}
+
+impl FakeToolchains {
+    fn new(compatible_since: semver::Version) -> Self {
+        Self {
+            compatible_since,
+            flaky: None,
+            installed: Mutex::new(Vec::new()),
+            runs: Mutex::new(Vec::new()),
+            envs: Mutex::new(Vec::new()),
+            commands: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn runs_of(&self, toolchain: &str) -> usize {
+        let runs = self.runs.lock().unwrap();
+        runs.iter().filter(|run| *run == toolchain).count()
+    }
+
+    fn envs_of(&self, toolchain: &str) -> Vec<String> {
+        let envs = self.envs.lock().unwrap();
+        envs.iter()
+            .find(|(run, _)| run == toolchain)
+            .map(|(_, vars)| vars.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl ToolchainProvider for FakeToolchains {
+    fn install(&self, toolchain: &str, _cross_target: Option<&str>) -> TResult<()> {
+        self.installed.lock().unwrap().push(toolchain.to_string());
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        toolchain: &str,
+        command: &[String],
+        _dir: Option<&Path>,
+        envs: &[(&str, &OsStr)],
+    ) -> TResult<Child> {
+        let version = semver::Version::parse(toolchain.split('-').next().unwrap()).unwrap();
+        self.commands.lock().unwrap().push(command.to_vec());
+
+        let vars = envs
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value.to_string_lossy()))
+            .collect();
+        self.envs
+            .lock()
+            .unwrap()
+            .push((toolchain.to_string(), vars));
+
+        let first_run = {
+            let mut runs = self.runs.lock().unwrap();
+            runs.push(toolchain.to_string());
+            runs.iter().filter(|run| *run == toolchain).count() == 1
+        };
+
+        if first_run && self.flaky.as_ref() == Some(&version) {
+            return Command::new("sh")
+                .args(["-c", "kill -9 $$"].iter())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(From::from);
+        }
+
+        // `cargo --version` succeeds, while an unknown subcommand fails
+        let arg = if version >= self.compatible_since {
+            "--version"
+        } else {
+            "not-a-cargo-subcommand"
+        };
+
+        Command::new("cargo")
+            .arg(arg)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(From::from)
+    }
+
+    fn list(&self) -> TResult<Vec<String>> {
+        Ok(self.installed.lock().unwrap().clone())
+    }
+
+    fn default_target(&self) -> TResult<String> {
+        Ok("x86_64-unknown-linux-gnu".to_string())
+    }
+
+    fn targets(&self) -> TResult<Vec<String>> {
+        Ok(vec![self.default_target()?])
+    }
+}
+
+/// A toolchain provider with a nightly for most, but not all days: the nightlies of the `missing`
+/// dates can't be installed. The check passes for the nightlies from `compatible_since` onwards.
+#[derive(Debug)]
+struct FakeNightlies {
+    compatible_since: &'static str,
+    missing: &'static [&'static str],
+    installed: Mutex<Vec<String>>,
+}
+
+// The date of a nightly toolchain, e.g. `2021-10-01` of `nightly-2021-10-01-<target>`
+fn nightly_date(toolchain: &str) -> &str {
+    &toolchain["nightly-".len().."nightly-YYYY-MM-DD".len()]
+}
+
+impl ToolchainProvider for FakeNightlies {
+    fn install(&self, toolchain: &str, _cross_target: Option<&str>) -> TResult<()> {
+        if self.missing.contains(&nightly_date(toolchain)) {
+            return Err(CargoMSRVError::RustupInstallFailed(toolchain.to_string()));
+        }
+
+        self.installed.lock().unwrap().push(toolchain.to_string());
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        toolchain: &str,
+        _command: &[String],
+        _dir: Option<&Path>,
+        _envs: &[(&str, &OsStr)],
+    ) -> TResult<Child> {
+        let arg = if nightly_date(toolchain) >= self.compatible_since {
+            "--version"
+        } else {
+            "not-a-cargo-subcommand"
+        };
+
+        Command::new("cargo")
+            .arg(arg)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(From::from)
+    }
+
+    fn list(&self) -> TResult<Vec<String>> {
+        Ok(self.installed.lock().unwrap().clone())
+    }
+
+    fn default_target(&self) -> TResult<String> {
+        Ok("x86_64-unknown-linux-gnu".to_string())
+    }
+
+    fn targets(&self) -> TResult<Vec<String>> {
+        Ok(vec![self.default_target()?])
+    }
+}
+
+#[test]
+fn msrv_with_fake_toolchains() {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeToolchains::new(semver::Version::new(1, 36, 0)));
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .build();
+
+    let compatibility = cargo_msrv::determine_msrv(&config, &fake_reporter(), &releases()).unwrap();
+
+    assert_eq!(
+        compatibility,
+        MinimalCompatibility::CapableToolchain {
+            toolchain: format!("1.36.0-{}", config.target()),
+            version: semver::Version::new(1, 36, 0),
+        }
+    );
+
+    // the linear search stops at the first release which fails the check
+    let installed = provider.list().unwrap();
+    assert_eq!(installed.len(), 4);
+    assert!(provider
+        .is_installed(&format!("1.35.0-{}", config.target()))
+        .unwrap());
+}
+
+// A single target is cross compiled with the toolchains of the host, just like several targets
+#[test]
+fn msrv_of_single_target() {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let releases = std::env::temp_dir().join(format!(
+        "cargo-msrv-single-target-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&releases, r#"["1.38.0", "1.37.0", "1.36.0", "1.35.0"]"#).unwrap();
+    let release_source = format!("file:{}", releases.display());
+
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+        "--target",
+        "wasm32-unknown-unknown",
+        "--release-source",
+        &release_source,
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeToolchains::new(semver::Version::new(1, 36, 0)));
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .build();
+
+    let result = cargo_msrv::run_app(&config, &fake_reporter());
+    std::fs::remove_file(&releases).unwrap();
+    result.unwrap();
+
+    assert_ne!(config.target(), "wasm32-unknown-unknown");
+    let installed = provider.list().unwrap();
+    assert!(installed
+        .iter()
+        .all(|toolchain| toolchain.ends_with(config.target().as_str())));
+
+    let commands = provider.commands.lock().unwrap();
+    assert!(!commands.is_empty());
+    assert!(commands.iter().all(|command| command
+        .windows(2)
+        .any(|args| args == ["--target", "wasm32-unknown-unknown"])));
+}
+
+#[cfg(unix)]
+#[parameterized(
+    retries = { 2, 0 },
+    expected_version = {
+        semver::Version::new(1,36,0),
+        semver::Version::new(1,38,0),
+    },
+    expected_runs = { 2, 1 }
+)]
+fn msrv_with_flaky_toolchain(
+    retries: u32,
+    expected_version: semver::Version,
+    expected_runs: usize,
+) {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeToolchains {
+        flaky: Some(semver::Version::new(1, 37, 0)),
+        ..FakeToolchains::new(semver::Version::new(1, 36, 0))
+    });
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .retries(retries)
+        .build();
+
+    let compatibility = cargo_msrv::determine_msrv(&config, &fake_reporter(), &releases()).unwrap();
+
+    assert_eq!(
+        compatibility,
+        MinimalCompatibility::CapableToolchain {
+            toolchain: format!("{}-{}", expected_version, config.target()),
+            version: expected_version,
+        }
+    );
+
+    // the check with 1.37.0 is killed by a signal the first time it runs
+    let flaky = format!("1.37.0-{}", config.target());
+    assert_eq!(provider.runs_of(&flaky), expected_runs);
+}
+
+#[test]
+fn msrv_with_env_and_target_dirs() {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+        "--env",
+        "CARGO_NET_OFFLINE=true",
+        "--env",
+        "[<1.37]RUSTFLAGS=-Zfoo",
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeToolchains::new(semver::Version::new(1, 36, 0)));
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .build();
+
+    cargo_msrv::determine_msrv(&config, &fake_reporter(), &releases()).unwrap();
+
+    // each toolchain builds the crate in its own target directory
+    let target_dir = |version: &str| {
+        let dir = folder.join("target").join("cargo-msrv").join(version);
+        format!("CARGO_TARGET_DIR={}", dir.display())
+    };
+
+    assert_eq!(
+        provider.envs_of(&format!("1.37.0-{}", config.target())),
+        vec!["CARGO_NET_OFFLINE=true".to_string(), target_dir("1.37.0")]
+    );
+    assert_eq!(
+        provider.envs_of(&format!("1.36.0-{}", config.target())),
+        vec![
+            "CARGO_NET_OFFLINE=true".to_string(),
+            "RUSTFLAGS=-Zfoo".to_string(),
+            target_dir("1.36.0")
+        ]
+    );
+}
+
+#[test]
+fn msrv_with_user_target_dir() {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+        "--env",
+        "[<1.37]CARGO_TARGET_DIR=/tmp/old-target",
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeToolchains::new(semver::Version::new(1, 36, 0)));
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .build();
+
+    cargo_msrv::determine_msrv(&config, &fake_reporter(), &releases()).unwrap();
+
+    // a target directory set by the user takes precedence over the one of the toolchain
+    let dir = folder.join("target").join("cargo-msrv").join("1.37.0");
+    assert_eq!(
+        provider.envs_of(&format!("1.37.0-{}", config.target())),
+        vec![format!("CARGO_TARGET_DIR={}", dir.display())]
+    );
+    assert_eq!(
+        provider.envs_of(&format!("1.36.0-{}", config.target())),
+        vec!["CARGO_TARGET_DIR=/tmp/old-target".to_string()]
+    );
+}
+
+#[parameterized(
+    args = { vec![], vec!["--max", "1.38.0"] },
+    expected_maximum = {
+        semver::Version::new(1,37,0),
+        semver::Version::new(1,38,0),
+    }
+)]
+fn msrv_with_config_file(args: Vec<&str>, expected_maximum: semver::Version) {
+    // the crate sets the maximum, the environment and `no-cache` in its `Cargo.toml`
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0-config-file");
+    let mut with_args = vec!["cargo-msrv", "--path", folder.to_str().unwrap()];
+    with_args.extend(args);
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeToolchains::new(semver::Version::new(1, 36, 0)));
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .build();
+
+    // options given on the command line take precedence over those in the file
+    assert_eq!(config.maximum_version(), Some(&expected_maximum));
+    assert!(config.no_cache());
+
+    let compatibility = cargo_msrv::determine_msrv(&config, &fake_reporter(), &releases()).unwrap();
+    assert_eq!(
+        compatibility,
+        MinimalCompatibility::CapableToolchain {
+            toolchain: format!("1.36.0-{}", config.target()),
+            version: semver::Version::new(1, 36, 0),
+        }
+    );
+
+    assert!(provider
+        .envs_of(&format!("1.36.0-{}", config.target()))
+        .contains(&"CARGO_NET_OFFLINE=true".to_string()));
+}
+
+// The toolchains in the directory are fake as well: their cargo passes the check for 1.37.0 and
+// later releases, and their rustc reports the host
+#[cfg(unix)]
+#[test]
+fn msrv_with_toolchain_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let toolchains =
+        std::env::temp_dir().join(format!("cargo-msrv-toolchain-dir-{}", std::process::id()));
+
+    let script = |path: &Path, contents: &str| {
+        std::fs::write(path, format!("#!/bin/sh\n{}\n", contents)).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    for (version, status) in &[("1.38.0", 0), ("1.37.0", 0), ("1.36.0", 1)] {
+        let bin = toolchains
+            .join(format!("{}-x86_64-fake-linux-gnu", version))
+            .join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        script(&bin.join("cargo"), &format!("exit {}", status));
+        script(&bin.join("rustc"), "echo 'host: x86_64-fake-linux-gnu'");
+    }
+
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+        "--toolchain-dir",
+        toolchains.to_str().unwrap(),
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches);
+    let compatibility = config
+        .as_ref()
+        .map_err(|err| err.to_string())
+        .and_then(|config| {
+            let releases = cargo_msrv::fetch::installed_release_index(
+                config.toolchain_provider().as_ref(),
+                config.target(),
+            )
+            .map_err(|err| err.to_string())?;
+
+            cargo_msrv::determine_msrv(config, &fake_reporter(), &releases)
+                .map_err(|err| err.to_string())
+        });
+    std::fs::remove_dir_all(&toolchains).unwrap();
+
+    assert_eq!(config.unwrap().target(), "x86_64-fake-linux-gnu");
+    assert_eq!(
+        compatibility.unwrap(),
+        MinimalCompatibility::CapableToolchain {
+            toolchain: "1.37.0-x86_64-fake-linux-gnu".to_string(),
+            version: semver::Version::new(1, 37, 0),
+        }
+    );
+}
+
+// The first nightly which passes the check, and the one after it, are missing, so the MSRV is the
+// nightly after those
+#[parameterized(
+    search_method = { SearchMethod::Bisect, SearchMethod::Bisect, SearchMethod::Linear },
+    jobs = { "1", "2", "1" },
+)]
+fn nightly_msrv_with_missing_nightlies(search_method: SearchMethod, jobs: &str) {
+    let folder = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.38.0");
+    let with_args = vec![
+        "cargo-msrv",
+        "--path",
+        folder.to_str().unwrap(),
+        "--no-cache",
+        "--nightly",
+        "--min",
+        "2021-10-01",
+        "--max",
+        "2021-10-20",
+        "--jobs",
+        jobs,
+    ];
+
+    let matches = cargo_msrv::cli::cli().get_matches_from(with_args);
+    let config = test_config_from_matches(&matches).expect("Unable to parse cli arguments");
+
+    let provider = Arc::new(FakeNightlies {
+        compatible_since: "2021-10-10",
+        missing: &["2021-10-03", "2021-10-10", "2021-10-11"],
+        installed: Mutex::new(Vec::new()),
+    });
+    let config = ConfigBuilder::from_config(config)
+        .toolchain_provider(provider.clone())
+        .search_method(search_method)
+        .build();
+
+    let index = nightly_index(
+        NightlyDate::parse("2021-10-01").unwrap(),
+        NightlyDate::parse("2021-10-20").unwrap(),
+    );
+    let compatibility = cargo_msrv::determine_msrv(&config, &fake_reporter(), &index).unwrap();
+
+    let msrv = NightlyDate::parse("2021-10-12").unwrap().to_version();
+    assert_eq!(
+        compatibility,
+        MinimalCompatibility::CapableToolchain {
+            toolchain: format!("nightly-2021-10-12-{}", config.target()),
+            version: msrv,
+        }
+    );
+}
+
+fn releases() -> ReleaseIndex {
+    FromIterator::from_iter(vec![
+        Release::new_stable(semver::Version::new(1, 38, 0)),
+        Release::new_stable(semver::Version::new(1, 37, 0)),
+        Release::new_stable(semver::Version::new(1, 36, 0)),
+        Release::new_stable(semver::Version::new(1, 35, 0)),
+        Release::new_stable(semver::Version::new(1, 34, 0)),
+    ])
+}
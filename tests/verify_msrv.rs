@@ -58,3 +58,60 @@ fn verify_failed_no_msrv_specified(folder: &str) {
 
     assert!(result.is_err());
 }
+
+#[parameterized(
+    folder = {
+        "1.35.0",
+        "1.36.0",
+    }
+)]
+fn verify_strict(folder: &str) {
+    let folder = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join(folder);
+    let with_args = vec![
+        "cargo-msrv",
+        "--verify",
+        "--strict",
+        "--path",
+        folder.to_str().unwrap(),
+    ];
+
+    let result = run_verify(
+        with_args,
+        vec![
+            Release::new_stable(semver::Version::new(1, 37, 0)),
+            Release::new_stable(semver::Version::new(1, 36, 0)),
+            Release::new_stable(semver::Version::new(1, 35, 0)),
+            Release::new_stable(semver::Version::new(1, 34, 0)),
+        ],
+    );
+
+    assert!(result.is_ok());
+}
+
+#[test]
+fn verify_strict_failed_msrv_not_minimal() {
+    let folder = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("features")
+        .join("1.35.0-declared-1.36.0");
+    let with_args = vec![
+        "cargo-msrv",
+        "--verify",
+        "--strict",
+        "--path",
+        folder.to_str().unwrap(),
+    ];
+
+    let result = run_verify(
+        with_args,
+        vec![
+            Release::new_stable(semver::Version::new(1, 37, 0)),
+            Release::new_stable(semver::Version::new(1, 36, 0)),
+            Release::new_stable(semver::Version::new(1, 35, 0)),
+            Release::new_stable(semver::Version::new(1, 34, 0)),
+        ],
+    );
+
+    assert!(result.is_err());
+}